@@ -0,0 +1,2 @@
+INSERT INTO admin_audit_log (admin_id, admin_username, action, target_user_id, target_username, detail)
+VALUES (:admin_id, :admin_username, :action, :target_user_id, :target_username, :detail)
@@ -0,0 +1,3 @@
+DELETE
+FROM users
+WHERE id = :id
@@ -0,0 +1,4 @@
+SELECT id, admin_username, action, target_user_id, target_username, detail, created_at
+FROM admin_audit_log
+ORDER BY id DESC
+LIMIT :limit;
@@ -0,0 +1,13 @@
+SELECT u.id,
+       u.username,
+       u.email,
+       u.is_admin,
+       u.disabled_at,
+       u.must_reset_password,
+       (SELECT COUNT(*)
+        FROM user_login_tokens ult
+        WHERE ult.user_id = u.id
+          AND ult.expire_after > datetime('now')) AS sessions
+FROM users AS u
+WHERE u.id = :id
+LIMIT 1;
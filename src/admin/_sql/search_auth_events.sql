@@ -0,0 +1,10 @@
+SELECT id, event, outcome, user_id, username, ip, user_agent, detail, created_at
+FROM auth_events
+WHERE (:user_id IS NULL OR user_id = :user_id)
+  AND (:username IS NULL OR username = :username COLLATE NOCASE)
+  AND (:event IS NULL OR event = :event)
+  AND (:outcome IS NULL OR outcome = :outcome)
+  AND (:ip IS NULL OR ip = :ip)
+  AND (:before_id IS NULL OR id < :before_id)
+ORDER BY id DESC
+LIMIT :limit
\ No newline at end of file
@@ -0,0 +1,16 @@
+SELECT u.id,
+       u.username,
+       u.email,
+       u.is_admin,
+       u.disabled_at,
+       u.must_reset_password,
+       (SELECT COUNT(*)
+        FROM user_login_tokens ult
+        WHERE ult.user_id = u.id
+          AND ult.expire_after > datetime('now')) AS sessions
+FROM users AS u
+WHERE :query = ''
+   OR instr(lower(u.username), lower(:query)) > 0
+   OR instr(lower(coalesce(u.email, '')), lower(:query)) > 0
+ORDER BY u.username
+LIMIT :limit OFFSET :offset;
@@ -0,0 +1,3 @@
+UPDATE users
+SET must_reset_password = 1
+WHERE id = :id
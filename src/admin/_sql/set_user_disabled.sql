@@ -0,0 +1,3 @@
+UPDATE users
+SET disabled_at = CASE WHEN :disabled THEN datetime('now') END
+WHERE id = :id
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+
+pub struct AdminUser {
+    pub id: i64,
+    pub username: String,
+    pub email: Option<String>,
+    pub is_admin: bool,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub must_reset_password: bool,
+    /// Login sessions that have not expired yet.
+    pub sessions: i64,
+}
+
+pub struct AuditEntry {
+    pub id: i64,
+    pub admin_username: String,
+    pub action: String,
+    pub target_user_id: i64,
+    pub target_username: String,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminAction {
+    Disable,
+    Enable,
+    ForcePasswordReset,
+    RevokeSessions,
+    Delete,
+}
+
+impl AdminAction {
+    /// Name stored in the audit log.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminAction::Disable => "disable",
+            AdminAction::Enable => "enable",
+            AdminAction::ForcePasswordReset => "force_password_reset",
+            AdminAction::RevokeSessions => "revoke_sessions",
+            AdminAction::Delete => "delete",
+        }
+    }
+}
+
+pub enum AdminActionFailure {
+    NotFound,
+    /// Admins can not disable, reset or delete their own account.
+    OwnAccount,
+    Failed,
+}
+
+/// Query of the auth event search, every field narrows it down.
+#[derive(FromForm, Default)]
+pub struct AuthEventFilter {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub event: Option<String>,
+    pub outcome: Option<String>,
+    pub ip: Option<String>,
+    /// Id of the last event of the previous page.
+    pub before_id: Option<i64>,
+    pub limit: Option<i64>,
+}
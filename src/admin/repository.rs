@@ -0,0 +1,283 @@
+use crate::admin::model::{AdminAction, AdminUser, AuditEntry, AuthEventFilter};
+use crate::db::SqliteClient;
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::error::ErrorStatus;
+use crate::user::model::AuthEvent;
+use crate::user::repository::map_auth_event;
+use error_stack::{Report, ResultExt};
+use rocket::http::Status;
+use rusqlite::{Row, named_params};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AdminRepositoryError {
+    #[error("Query error")]
+    QueryError,
+    #[error("Row Value error")]
+    RowValueError,
+    #[error("Lock error")]
+    LockError,
+    #[error("Not found error")]
+    NotFoundError,
+}
+
+impl ErrorStatus for AdminRepositoryError {
+    fn error_status(&self) -> Status {
+        match self {
+            AdminRepositoryError::NotFoundError => Status::NotFound,
+            _ => Status::InternalServerError,
+        }
+    }
+}
+
+fn map_admin_user(row: &Row) -> rusqlite::Result<AdminUser> {
+    Ok(AdminUser {
+        id: row.get("id")?,
+        username: row.get("username")?,
+        email: row.get("email")?,
+        is_admin: row.get("is_admin")?,
+        disabled_at: row.get("disabled_at")?,
+        must_reset_password: row.get("must_reset_password")?,
+        sessions: row.get("sessions")?,
+    })
+}
+
+pub struct AdminRepository {
+    sqlite_client: SqliteClient,
+}
+
+impl AdminRepository {
+    pub fn new(sqlite_client: SqliteClient) -> Self {
+        Self { sqlite_client }
+    }
+
+    /// Matches the query anywhere in the username or email, an empty query lists everyone.
+    pub fn search_users(
+        &self,
+        query: String,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Box<[AdminUser]>, Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/search_users.sql"))
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":query": query,
+                    ":limit": limit,
+                    ":offset": offset,
+                },
+                map_admin_user,
+            )
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(AdminRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    pub fn get_user(&self, id: i64) -> Result<AdminUser, Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user.sql"))
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(named_params! { ":id": id }, map_admin_user)
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(AdminRepositoryError::NotFoundError))?;
+
+        item.change_context(AdminRepositoryError::RowValueError)
+    }
+
+    pub fn set_user_disabled(
+        &self,
+        id: i64,
+        disabled: bool,
+    ) -> Result<(), Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/set_user_disabled.sql"),
+            named_params! {
+                ":id": id,
+                ":disabled": disabled,
+            },
+        )
+        .change_context(AdminRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Cleared again when the user stores a new password.
+    pub fn set_must_reset_password(&self, id: i64) -> Result<(), Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/set_must_reset_password.sql"),
+            named_params! { ":id": id },
+        )
+        .change_context(AdminRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Sessions, tokens and identities of the user go with it through their foreign keys.
+    pub fn delete_user(&self, id: i64) -> Result<bool, Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        let deleted = conn
+            .execute(
+                include_str!("_sql/delete_user.sql"),
+                named_params! { ":id": id },
+            )
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Usernames are copied into the entry, so it still reads right after a user is deleted.
+    pub fn add_audit_entry(
+        &self,
+        admin_id: i64,
+        admin_username: String,
+        action: AdminAction,
+        target: &AdminUser,
+        detail: String,
+    ) -> Result<(), Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_audit_entry.sql"),
+            named_params! {
+                ":admin_id": admin_id,
+                ":admin_username": admin_username,
+                ":action": action.as_str(),
+                ":target_user_id": target.id,
+                ":target_username": target.username,
+                ":detail": detail,
+            },
+        )
+        .change_context(AdminRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn get_audit_entries(
+        &self,
+        limit: i64,
+    ) -> Result<Box<[AuditEntry]>, Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_audit_entries.sql"))
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(named_params! { ":limit": limit }, |row| {
+                Ok(AuditEntry {
+                    id: row.get("id")?,
+                    admin_username: row.get("admin_username")?,
+                    action: row.get("action")?,
+                    target_user_id: row.get("target_user_id")?,
+                    target_username: row.get("target_username")?,
+                    detail: row.get("detail")?,
+                    created_at: row.get("created_at")?,
+                })
+            })
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(AdminRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    /// Newest first, `limit` has to be clamped by the caller.
+    pub fn search_auth_events(
+        &self,
+        filter: &AuthEventFilter,
+        limit: i64,
+    ) -> Result<Box<[AuthEvent]>, Report<AdminRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(AdminRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/search_auth_events.sql"))
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": filter.user_id,
+                    ":username": filter.username,
+                    ":event": filter.event,
+                    ":outcome": filter.outcome,
+                    ":ip": filter.ip,
+                    ":before_id": filter.before_id,
+                    ":limit": limit,
+                },
+                map_auth_event,
+            )
+            .change_context(AdminRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(AdminRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+}
+
+impl FromGlobalContext for AdminRepository {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(dependency_global_context.inject().await?))
+    }
+}
@@ -0,0 +1,468 @@
+use crate::admin::model::{AdminActionFailure, AdminUser, AuthEventFilter};
+use crate::admin::repository::AdminRepositoryError;
+use crate::admin::service::AdminService;
+use crate::csrf::{CsrfForm, CsrfPost};
+use crate::error::{ErrorOutput, ErrorReportResponse};
+use crate::html_base::ContextHtmlBuilder;
+use crate::user::dependency::UserDep;
+use crate::user::flag::AdminFlag;
+use crate::user::model::AuthEvent;
+use crate::user::repository::UserRepositoryError;
+use error_stack::ResultExt;
+use maud::{Markup, html};
+use rocket::fairing::AdHoc;
+use rocket::response::{Flash, Redirect};
+use rocket::serde::json::Json;
+
+fn user_status(user: &AdminUser) -> String {
+    let mut status = vec![];
+    if user.is_admin {
+        status.push("admin");
+    }
+    if user.disabled_at.is_some() {
+        status.push("disabled");
+    }
+    if user.must_reset_password {
+        status.push("password reset pending");
+    }
+    if status.is_empty() {
+        "active".to_string()
+    } else {
+        status.join(", ")
+    }
+}
+
+#[get("/users?<q>&<page>")]
+pub async fn users(
+    q: Option<String>,
+    page: Option<u32>,
+    context_html_builder: UserDep<ContextHtmlBuilder, AdminFlag>,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Result<Markup, ErrorReportResponse<AdminRepositoryError>> {
+    let q = q.unwrap_or_default();
+    let page = page.unwrap_or(0);
+    let (users, has_next_page) = admin_service
+        .0
+        .search_users(&q, page)
+        .map_err(ErrorReportResponse)?;
+    let page_url = |page: u32| uri!("/admin", users(q = Some(&q), page = Some(page))).to_string();
+
+    let title = "Users";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p {
+                a href="/admin/audit" { "Audit log" }
+                " · "
+                a href="/admin/invites" { "Invites" }
+            }
+            form method="get" action="/admin/users" .form {
+                input .form-item type="search" name="q" value=(q) placeholder="Search username or email";
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Search" };
+            }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "ID" }
+                    span .bucket-list-col { "Username" }
+                    span .bucket-list-col { "Email" }
+                    span .bucket-list-col { "Status" }
+                    span .bucket-list-col { "Sessions" }
+                }
+                @for user in &users {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (user.id) }
+                        span .bucket-list-col {
+                            a href=(format!("/admin/users/{}", user.id)) { (user.username) }
+                        }
+                        span .bucket-list-col { (user.email.as_deref().unwrap_or("-")) }
+                        span .bucket-list-col { (user_status(user)) }
+                        span .bucket-list-col { (user.sessions) }
+                    }
+                }
+            }
+            @if users.is_empty() {
+                p .mt-3 { "No users found." }
+            }
+            p .mt-3 {
+                @if page > 0 {
+                    a .btn .btn-sky-blue href=(page_url(page - 1)) { "Previous" }
+                }
+                @if has_next_page {
+                    a .btn .btn-sky-blue href=(page_url(page + 1)) { "Next" }
+                }
+            }
+        })
+        .build())
+}
+
+#[get("/users/<id>")]
+pub async fn user(
+    id: i64,
+    context_html_builder: UserDep<ContextHtmlBuilder, AdminFlag>,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Result<Markup, ErrorReportResponse<AdminRepositoryError>> {
+    let user = admin_service.0.get_user(id).map_err(ErrorReportResponse)?;
+    let own_account = user.id == context_html_builder.1.id;
+    let action = |name: &str| format!("/admin/users/{}/{}", user.id, name);
+
+    let title = format!("User: {}", user.username);
+    Ok(context_html_builder
+        .0
+        .attach_title(title.clone())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { a href="/admin/users" { "Back to all users" } }
+            p { "Email: " (user.email.as_deref().unwrap_or("-")) }
+            p { "Status: " (user_status(&user)) }
+            @if let Some(disabled_at) = user.disabled_at {
+                p { "Disabled since " (context_html_builder.1.format_datetime(disabled_at)) "." }
+            }
+            p { "Active sessions: " (user.sessions) }
+            form method="post" action=(action("revoke-sessions")) {
+                (context_html_builder.0.csrf_input())
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Revoke sessions" }
+            }
+            @if own_account {
+                p .mt-3 { "You can not disable, reset or delete your own account." }
+            } @else {
+                @if user.disabled_at.is_some() {
+                    form method="post" action=(action("enable")) {
+                        (context_html_builder.0.csrf_input())
+                        button .btn .btn-sky-blue .mt-3 type="submit" { "Enable account" }
+                    }
+                } @else {
+                    form method="post" action=(action("disable")) {
+                        (context_html_builder.0.csrf_input())
+                        button .btn .btn-sky-blue .mt-3 type="submit" { "Disable account" }
+                    }
+                }
+                form method="post" action=(action("force-password-reset")) {
+                    (context_html_builder.0.csrf_input())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Force password reset" }
+                }
+                h2 .mt-5 { "Delete user" }
+                p { "Deletes the account with its sessions, tokens and linked identities." }
+                form method="post" action=(action("delete")) {
+                    (context_html_builder.0.csrf_input())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Delete user" }
+                }
+            }
+        })
+        .build())
+}
+
+fn action_flash(id: i64, result: Result<&str, AdminActionFailure>) -> Flash<Redirect> {
+    let redirect = Redirect::to(format!("/admin/users/{}", id));
+    match result {
+        Ok(message) => Flash::success(redirect, message),
+        Err(AdminActionFailure::NotFound) => Flash::error(
+            Redirect::to(uri!("/admin/users")),
+            "The user does not exist.",
+        ),
+        Err(AdminActionFailure::OwnAccount) => Flash::error(
+            redirect,
+            "You can not disable, reset or delete your own account.",
+        ),
+        Err(AdminActionFailure::Failed) => Flash::error(redirect, "The action failed."),
+    }
+}
+
+#[post("/users/<id>/disable", data = "<_csrf>")]
+pub async fn disable_user(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    action_flash(
+        id,
+        admin_service
+            .0
+            .disable(id)
+            .map(|_| "Account disabled, its sessions were ended."),
+    )
+}
+
+#[post("/users/<id>/enable", data = "<_csrf>")]
+pub async fn enable_user(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    action_flash(id, admin_service.0.enable(id).map(|_| "Account enabled."))
+}
+
+#[post("/users/<id>/force-password-reset", data = "<_csrf>")]
+pub async fn force_password_reset(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    action_flash(
+        id,
+        admin_service
+            .0
+            .force_password_reset(id)
+            .await
+            .map(|mail_sent| {
+                if mail_sent {
+                    "Password reset required, a reset mail was sent."
+                } else {
+                    "Password reset required, no mail was sent as the user has no email address."
+                }
+            }),
+    )
+}
+
+#[post("/users/<id>/revoke-sessions", data = "<_csrf>")]
+pub async fn revoke_user_sessions(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    action_flash(
+        id,
+        admin_service
+            .0
+            .revoke_sessions(id)
+            .map(|_| "Sessions revoked."),
+    )
+}
+
+#[post("/users/<id>/delete", data = "<_csrf>")]
+pub async fn delete_user(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    match admin_service.0.delete(id) {
+        Ok(()) => Flash::success(Redirect::to(uri!("/admin/users")), "User deleted."),
+        Err(failure) => action_flash(id, Err(failure)),
+    }
+}
+
+#[get("/audit")]
+pub async fn audit_log(
+    context_html_builder: UserDep<ContextHtmlBuilder, AdminFlag>,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Result<Markup, ErrorReportResponse<AdminRepositoryError>> {
+    let entries = admin_service.0.audit_log().map_err(ErrorReportResponse)?;
+    let title = "Audit log";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { a href="/admin/users" { "Back to all users" } }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Time" }
+                    span .bucket-list-col { "Admin" }
+                    span .bucket-list-col { "Action" }
+                    span .bucket-list-col { "User" }
+                    span .bucket-list-col { "Detail" }
+                }
+                @for entry in &entries {
+                    div .bucket-list-item id=(format!("audit-{}", entry.id)) {
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(entry.created_at)) }
+                        span .bucket-list-col { (entry.admin_username) }
+                        span .bucket-list-col { (entry.action) }
+                        span .bucket-list-col {
+                            (entry.target_username) " (" (entry.target_user_id) ")"
+                        }
+                        span .bucket-list-col { (entry.detail) }
+                    }
+                }
+            }
+        })
+        .build())
+}
+
+/// JSON for scripts and log shipping, pages back with `before_id`.
+#[get("/auth-events?<filter..>")]
+pub async fn auth_events(
+    filter: AuthEventFilter,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Result<Json<Box<[AuthEvent]>>, ErrorReportResponse<AdminRepositoryError>> {
+    let events = admin_service
+        .0
+        .search_auth_events(&filter)
+        .attach(ErrorOutput::Json)
+        .map_err(ErrorReportResponse)?;
+
+    Ok(Json(events))
+}
+
+#[get("/invites")]
+pub async fn invites(
+    context_html_builder: UserDep<ContextHtmlBuilder, AdminFlag>,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let invites = admin_service
+        .0
+        .list_invites()
+        .map_err(ErrorReportResponse)?;
+    let title = "Invites";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { a href="/admin/users" { "Back to all users" } }
+            p { "Invite codes are only asked for when registration is invite-only." }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Note" }
+                    span .bucket-list-col { "Uses" }
+                    span .bucket-list-col { "Expires" }
+                    span .bucket-list-col { "Created" }
+                    span .bucket-list-col { "" }
+                }
+                @for invite in &invites {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (invite.note) }
+                        span .bucket-list-col { (invite.uses) " / " (invite.max_uses) }
+                        span .bucket-list-col {
+                            @match invite.expire_after {
+                                Some(expire_after) => (context_html_builder.1.format_datetime(expire_after)),
+                                None => "Never",
+                            }
+                        }
+                        span .bucket-list-col {
+                            (context_html_builder.1.format_datetime(invite.created_at))
+                            @if let Some(created_by) = &invite.created_by {
+                                " by " (created_by)
+                            }
+                        }
+                        span .bucket-list-col {
+                            form method="post" action=(format!("/admin/invites/{}/delete", invite.id)) {
+                                (context_html_builder.0.csrf_input())
+                                button .btn .btn-sky-blue type="submit" { "Delete" }
+                            }
+                        }
+                    }
+                }
+            }
+            @if invites.is_empty() {
+                p .mt-3 { "No invites yet." }
+            }
+            h2 .mt-5 { "Create invite" }
+            form method="post" action="/admin/invites" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="note" placeholder="Note, e.g. who it is for" maxlength="100";
+                input .form-item type="number" name="max_uses" value="1" min="1" max="1000";
+                select .form-item name="valid_days" {
+                    option value="1" { "Valid for a day" }
+                    option value="7" selected { "Valid for 7 days" }
+                    option value="30" { "Valid for 30 days" }
+                    option value="0" { "Never expires" }
+                }
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Create invite" };
+            }
+        })
+        .build())
+}
+
+#[derive(FromForm)]
+pub struct InviteForm {
+    pub note: String,
+    pub max_uses: i64,
+    pub valid_days: u32,
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum InvitePostResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[post("/invites", data = "<data>")]
+async fn invites_post(
+    data: CsrfForm<InviteForm>,
+    context_html_builder: UserDep<ContextHtmlBuilder, AdminFlag>,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> InvitePostResponse {
+    let note = data.note.trim().to_string();
+    if note.chars().count() > 100 || !(1..=1000).contains(&data.max_uses) {
+        return InvitePostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/admin/invites")),
+            "The note can have at most 100 characters and an invite 1 to 1000 uses.",
+        ));
+    }
+
+    let Some(invite_code) =
+        admin_service
+            .0
+            .create_invite(note, data.max_uses, data.valid_days.min(365))
+    else {
+        return InvitePostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/admin/invites")),
+            "The invite could not be created.",
+        ));
+    };
+
+    let register_url = format!("/user/register?invite={}", invite_code);
+    let title = "Invite created";
+    InvitePostResponse::Markup(
+        context_html_builder
+            .0
+            .attach_title(title.to_string())
+            .set_current_tag("user".to_string())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                p { "Copy the invite code now, it will not be shown again." }
+                p .mt-3 { code { (invite_code) } }
+                p .mt-3 { "Or share the register link: " a href=(register_url) { (register_url) } }
+                a .btn .btn-sky-blue .mt-3 href="/admin/invites" { "Done" }
+            })
+            .build(),
+    )
+}
+
+#[post("/invites/<id>/delete", data = "<_csrf>")]
+pub async fn delete_invite(
+    id: i64,
+    _csrf: CsrfPost,
+    admin_service: UserDep<AdminService, AdminFlag>,
+) -> Flash<Redirect> {
+    if admin_service.0.delete_invite(id) {
+        Flash::success(Redirect::to(uri!("/admin/invites")), "Invite deleted.")
+    } else {
+        Flash::error(
+            Redirect::to(uri!("/admin/invites")),
+            "The invite does not exist.",
+        )
+    }
+}
+
+pub struct AdminRoute;
+
+impl AdminRoute {
+    pub fn adhoc() -> AdHoc {
+        AdHoc::on_ignite("AdminRoute", |r| async {
+            r.mount(
+                "/admin",
+                routes![
+                    users,
+                    user,
+                    disable_user,
+                    enable_user,
+                    force_password_reset,
+                    revoke_user_sessions,
+                    delete_user,
+                    audit_log,
+                    auth_events,
+                    invites,
+                    invites_post,
+                    delete_invite
+                ],
+            )
+        })
+    }
+}
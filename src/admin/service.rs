@@ -0,0 +1,222 @@
+use crate::admin::model::{
+    AdminAction, AdminActionFailure, AdminUser, AuditEntry, AuthEventFilter,
+};
+use crate::admin::repository::{AdminRepository, AdminRepositoryError};
+use crate::dependency::DependencyError;
+use crate::user::dependency::{DependencyUserContext, FromUserContext};
+use crate::user::model::{AuthEvent, UserContext, UserInvite};
+use crate::user::repository::{UserRepository, UserRepositoryError};
+use crate::user::service::UserPasswordResetService;
+use crate::user::token::generate_token;
+use error_stack::Report;
+use std::sync::Arc;
+
+pub const USERS_PER_PAGE: i64 = 50;
+const AUDIT_ENTRIES_SHOWN: i64 = 200;
+const AUTH_EVENTS_DEFAULT_LIMIT: i64 = 100;
+const AUTH_EVENTS_MAX_LIMIT: i64 = 1000;
+
+/// Every change to a user is written to the audit log with the acting admin.
+pub struct AdminService {
+    admin_repository: AdminRepository,
+    user_repository: UserRepository,
+    user_password_reset_service: UserPasswordResetService,
+    user_context: Arc<UserContext>,
+}
+
+impl AdminService {
+    fn new(
+        admin_repository: AdminRepository,
+        user_repository: UserRepository,
+        user_password_reset_service: UserPasswordResetService,
+        user_context: Arc<UserContext>,
+    ) -> Self {
+        Self {
+            admin_repository,
+            user_repository,
+            user_password_reset_service,
+            user_context,
+        }
+    }
+
+    /// Returns one page of users and whether there is a next one.
+    pub fn search_users(
+        &self,
+        query: &str,
+        page: u32,
+    ) -> Result<(Box<[AdminUser]>, bool), Report<AdminRepositoryError>> {
+        let mut users = self
+            .admin_repository
+            .search_users(
+                query.trim().to_string(),
+                USERS_PER_PAGE + 1,
+                page as i64 * USERS_PER_PAGE,
+            )?
+            .into_vec();
+        let has_next_page = users.len() as i64 > USERS_PER_PAGE;
+        users.truncate(USERS_PER_PAGE as usize);
+
+        Ok((users.into_boxed_slice(), has_next_page))
+    }
+
+    pub fn get_user(&self, id: i64) -> Result<AdminUser, Report<AdminRepositoryError>> {
+        self.admin_repository.get_user(id)
+    }
+
+    pub fn audit_log(&self) -> Result<Box<[AuditEntry]>, Report<AdminRepositoryError>> {
+        self.admin_repository.get_audit_entries(AUDIT_ENTRIES_SHOWN)
+    }
+
+    fn target(&self, id: i64, action: AdminAction) -> Result<AdminUser, AdminActionFailure> {
+        let target =
+            self.admin_repository
+                .get_user(id)
+                .map_err(|err| match err.current_context() {
+                    AdminRepositoryError::NotFoundError => AdminActionFailure::NotFound,
+                    _ => AdminActionFailure::Failed,
+                })?;
+
+        let locks_out = matches!(
+            action,
+            AdminAction::Disable | AdminAction::ForcePasswordReset | AdminAction::Delete
+        );
+        if locks_out && target.id == self.user_context.id {
+            return Err(AdminActionFailure::OwnAccount);
+        }
+
+        Ok(target)
+    }
+
+    fn audit(&self, action: AdminAction, target: &AdminUser, detail: &str) {
+        let _ = self.admin_repository.add_audit_entry(
+            self.user_context.id,
+            self.user_context.username.clone(),
+            action,
+            target,
+            detail.to_string(),
+        );
+    }
+
+    /// Also ends the user's sessions, a disabled user can not log in or use API tokens.
+    pub fn disable(&self, id: i64) -> Result<(), AdminActionFailure> {
+        let target = self.target(id, AdminAction::Disable)?;
+        self.admin_repository
+            .set_user_disabled(target.id, true)
+            .map_err(|_| AdminActionFailure::Failed)?;
+        let _ = self.user_repository.delete_all_tokens(target.id);
+
+        self.audit(AdminAction::Disable, &target, "");
+        Ok(())
+    }
+
+    pub fn enable(&self, id: i64) -> Result<(), AdminActionFailure> {
+        let target = self.target(id, AdminAction::Enable)?;
+        self.admin_repository
+            .set_user_disabled(target.id, false)
+            .map_err(|_| AdminActionFailure::Failed)?;
+
+        self.audit(AdminAction::Enable, &target, "");
+        Ok(())
+    }
+
+    /// Logs the user out and blocks password logins until a new password is set.
+    /// Returns whether a reset mail went out, which needs an email address.
+    pub async fn force_password_reset(&self, id: i64) -> Result<bool, AdminActionFailure> {
+        let target = self.target(id, AdminAction::ForcePasswordReset)?;
+        self.admin_repository
+            .set_must_reset_password(target.id)
+            .map_err(|_| AdminActionFailure::Failed)?;
+        let _ = self.user_repository.delete_all_tokens(target.id);
+
+        let mail_sent = self
+            .user_password_reset_service
+            .request_reset(target.username.clone())
+            .await;
+
+        self.audit(
+            AdminAction::ForcePasswordReset,
+            &target,
+            if mail_sent {
+                "reset mail sent"
+            } else {
+                "no reset mail sent"
+            },
+        );
+        Ok(mail_sent)
+    }
+
+    pub fn revoke_sessions(&self, id: i64) -> Result<(), AdminActionFailure> {
+        let target = self.target(id, AdminAction::RevokeSessions)?;
+        self.user_repository
+            .delete_all_tokens(target.id)
+            .map_err(|_| AdminActionFailure::Failed)?;
+
+        self.audit(
+            AdminAction::RevokeSessions,
+            &target,
+            &format!("{} active", target.sessions),
+        );
+        Ok(())
+    }
+
+    pub fn delete(&self, id: i64) -> Result<(), AdminActionFailure> {
+        let target = self.target(id, AdminAction::Delete)?;
+        if !self
+            .admin_repository
+            .delete_user(target.id)
+            .map_err(|_| AdminActionFailure::Failed)?
+        {
+            return Err(AdminActionFailure::NotFound);
+        }
+
+        self.audit(AdminAction::Delete, &target, "");
+        Ok(())
+    }
+
+    pub fn search_auth_events(
+        &self,
+        filter: &AuthEventFilter,
+    ) -> Result<Box<[AuthEvent]>, Report<AdminRepositoryError>> {
+        let limit = filter
+            .limit
+            .unwrap_or(AUTH_EVENTS_DEFAULT_LIMIT)
+            .clamp(1, AUTH_EVENTS_MAX_LIMIT);
+        self.admin_repository.search_auth_events(filter, limit)
+    }
+
+    pub fn list_invites(&self) -> Result<Box<[UserInvite]>, Report<UserRepositoryError>> {
+        self.user_repository.get_invites()
+    }
+
+    /// Returns the code, it can not be shown again later. A `valid_days` of 0 never expires.
+    pub fn create_invite(&self, note: String, max_uses: i64, valid_days: u32) -> Option<String> {
+        let invite_code = generate_token();
+        self.user_repository
+            .add_invite(
+                invite_code.clone(),
+                note,
+                max_uses,
+                valid_days,
+                self.user_context.id,
+            )
+            .ok()
+            .map(|_| invite_code)
+    }
+
+    pub fn delete_invite(&self, id: i64) -> bool {
+        self.user_repository.delete_invite(id).unwrap_or(false)
+    }
+}
+
+impl FromUserContext for AdminService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+            Arc::clone(&dependency_user_context.user_context),
+        ))
+    }
+}
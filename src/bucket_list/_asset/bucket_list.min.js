@@ -1,3 +1,147 @@
-import{createApp}from "vue";createApp({data(){return{bucket_list:[],input_name:"",input_description:"",error:false,}},methods:{getBucketList(){fetch('/bucket-list/all').then(res=>res.json()).then(data=>{this.bucket_list=data;this.formatDate();});},formatDate(){this.bucket_list.forEach(item=>{item.timestamp=new Date(item.timestamp).toLocaleString();});},addToBucketList(){let json={name:this.input_name,description:this.input_description}
-fetch('/bucket-list/add',{method:'POST',headers:{'Content-Type':'application/json'},body:JSON.stringify(json)}).then(res=>{if(res.status===200){this.getBucketList();this.input_name="";this.input_description="";this.error=false;}else if(res.status===422){let content=res.json();content.then(data=>{let sorted={};for(let key in data){sorted[data[key].field_name]=data[key].messages;}
-this.error=sorted;});}})}},mounted(){this.getBucketList()},}).mount('#bucket-list');
\ No newline at end of file
+import {createApp} from "vue";
+const csrfToken = document.querySelector('meta[name="csrf-token"]').content;
+createApp({
+data() {
+return {
+bucket_list: [],
+input_name: "",
+input_description: "",
+input_tags: "",
+editing: null,
+error: false,
+conflict: false,
+search_query: "",
+search_results: [],
+}
+},
+methods: {
+getBucketList() {
+fetch('/bucket-list/all')
+.then(res => res.json())
+.then(data => {
+this.bucket_list = data;
+this.formatDate();
+});
+},
+searchBucketList() {
+let query = this.search_query;
+if (!query.trim()) {
+this.search_results = [];
+return;
+}
+fetch(`/bucket-list/search?q=${encodeURIComponent(query)}`)
+.then(res => res.json())
+.then(data => {
+if (query === this.search_query) {
+this.search_results = data;
+}
+});
+},
+formatDate() {
+this.bucket_list.forEach(item => {
+item.timestamp = new Date(item.timestamp).toLocaleString();
+});
+},
+resetForm() {
+this.input_name = "";
+this.input_description = "";
+this.input_tags = "";
+this.editing = null;
+this.error = false;
+this.conflict = false;
+},
+inputJson() {
+return {
+name: this.input_name,
+description: this.input_description,
+tags: this.input_tags.split(',').map(tag => tag.trim()).filter(tag => tag)
+}
+},
+showValidationError(res) {
+res.json().then(data => {
+let sorted = {};
+for (let key in data) {
+sorted[data[key].field_name] = data[key].messages;
+}
+this.error = sorted;
+});
+},
+submitForm() {
+if (this.editing) {
+this.updateBucketListItem();
+} else {
+this.addToBucketList();
+}
+},
+editItem(item) {
+this.resetForm();
+this.editing = item;
+this.input_name = item.name;
+this.input_description = item.description;
+this.input_tags = item.tags.join(', ');
+},
+completeItem(item) {
+fetch(`/bucket-list/${item.id}/complete`, {
+method: 'POST',
+headers: {
+'Content-Type': 'application/json',
+'X-CSRF-Token': csrfToken,
+'If-Match': `"${item.id}-${item.version}"`
+},
+body: JSON.stringify({completed: !item.completed_at})
+}).then(res => {
+if (res.status === 200 || res.status === 409) {
+this.getBucketList();
+}
+})
+},
+addToBucketList(force = false) {
+let json = this.inputJson();
+json.force = force;
+fetch('/bucket-list/add', {
+method: 'POST',
+headers: {
+'Content-Type': 'application/json',
+'X-CSRF-Token': csrfToken
+},
+body: JSON.stringify(json)
+}).then(res => {
+if (res.status === 200) {
+this.getBucketList();
+this.resetForm();
+} else if (res.status === 422) {
+this.showValidationError(res);
+}
+})
+},
+updateBucketListItem() {
+let json = this.inputJson();
+fetch(`/bucket-list/${this.editing.id}`, {
+method: 'PUT',
+headers: {
+'Content-Type': 'application/json',
+'X-CSRF-Token': csrfToken,
+'If-Match': `"${this.editing.id}-${this.editing.version}"`
+},
+body: JSON.stringify(json)
+}).then(res => {
+if (res.status === 200) {
+this.getBucketList();
+this.resetForm();
+} else if (res.status === 409) {
+res.json().then(data => {
+this.editing = data;
+this.conflict = `This item was changed elsewhere, it is now "${data.name}". ` +
+"Save again to overwrite it.";
+this.getBucketList();
+});
+} else if (res.status === 422) {
+this.showValidationError(res);
+}
+})
+}
+},
+mounted() {
+this.getBucketList()
+},
+}).mount('#bucket-list');
\ No newline at end of file
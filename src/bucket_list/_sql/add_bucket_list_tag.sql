@@ -0,0 +1,2 @@
+INSERT INTO bucket_list_tags (bucket_list_id, tag)
+VALUES (:bucket_list_id, :tag);
\ No newline at end of file
@@ -0,0 +1,5 @@
+UPDATE bucket_list
+SET completed_at = CASE WHEN :completed THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+    version      = version + 1
+WHERE id = :id
+  AND version = :version;
\ No newline at end of file
@@ -0,0 +1,3 @@
+DELETE
+FROM bucket_list_tags
+WHERE bucket_list_id = :bucket_list_id;
\ No newline at end of file
@@ -1,3 +1,11 @@
-SELECT id, name, description, timestamp
+SELECT id,
+       name,
+       description,
+       timestamp,
+       version,
+       completed_at,
+       (SELECT GROUP_CONCAT(tag, ',')
+        FROM bucket_list_tags
+        WHERE bucket_list_id = bucket_list.id) AS tags
 FROM bucket_list
 ORDER BY timestamp DESC;
@@ -0,0 +1,2 @@
+SELECT name
+FROM bucket_list;
\ No newline at end of file
@@ -0,0 +1,12 @@
+SELECT id,
+       name,
+       description,
+       timestamp,
+       version,
+       completed_at,
+       (SELECT GROUP_CONCAT(tag, ',')
+        FROM bucket_list_tags
+        WHERE bucket_list_id = bucket_list.id) AS tags
+FROM bucket_list
+WHERE id = :id
+LIMIT 1;
\ No newline at end of file
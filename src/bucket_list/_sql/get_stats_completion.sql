@@ -0,0 +1,4 @@
+SELECT COUNT(*)                                            AS total,
+       COUNT(completed_at)                                 AS completed,
+       AVG(julianday(completed_at) - julianday(timestamp)) AS average_days_to_complete
+FROM bucket_list;
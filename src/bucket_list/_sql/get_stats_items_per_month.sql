@@ -0,0 +1,4 @@
+SELECT strftime('%Y-%m', timestamp) AS month, COUNT(*) AS total
+FROM bucket_list
+GROUP BY month
+ORDER BY month;
@@ -0,0 +1,5 @@
+SELECT tag, COUNT(*) AS total
+FROM bucket_list_tags
+GROUP BY tag
+ORDER BY total DESC, tag
+LIMIT 10;
@@ -0,0 +1,9 @@
+SELECT b.id,
+       b.name,
+       highlight(bucket_list_fts, 0, char(2), char(3))        AS name_highlight,
+       snippet(bucket_list_fts, 1, char(2), char(3), '…', 16) AS description_snippet
+FROM bucket_list_fts
+         INNER JOIN bucket_list b ON b.id = bucket_list_fts.rowid
+WHERE bucket_list_fts MATCH :query
+ORDER BY bm25(bucket_list_fts)
+LIMIT 50;
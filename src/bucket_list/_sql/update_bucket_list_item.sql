@@ -0,0 +1,6 @@
+UPDATE bucket_list
+SET name        = :name,
+    description = :description,
+    version     = version + 1
+WHERE id = :id
+  AND version = :version;
\ No newline at end of file
@@ -1,7 +1,9 @@
 use crate::bucket_list::validate::description::Description;
-use crate::bucket_list::validate::name::Name;
+use crate::bucket_list::validate::name::{Name, NameCheckResult};
+use crate::bucket_list::validate::tags::Tags;
 use crate::validation::{ValidationErrorResponse, ValidationErrorsBuilder};
 use chrono::{DateTime, Utc};
+use maud::html;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Debug)]
@@ -9,16 +11,113 @@ pub struct BucketListItem {
     pub id: i64,
     pub name: String,
     pub description: String,
+    pub description_html: String,
     pub timestamp: DateTime<Utc>,
+    pub version: i64,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub tags: Box<[String]>,
+}
+
+impl BucketListItem {
+    pub fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.id, self.version)
+    }
+
+    pub fn version_from_etag(id: i64, etag: &str) -> Option<i64> {
+        let etag = etag.trim().trim_start_matches("W/").trim_matches('"');
+        let (etag_id, version) = etag.split_once('-')?;
+        if etag_id.parse::<i64>().ok()? != id {
+            return None;
+        }
+        version.parse().ok()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct BucketListSearchResult {
+    pub id: i64,
+    pub name: String,
+    pub name_html: String,
+    pub snippet_html: String,
+}
+
+impl BucketListSearchResult {
+    /// Turns free text into an FTS5 query of quoted prefix terms, so user input cannot use
+    /// FTS5 query syntax.
+    pub fn to_fts_query(query: &str) -> Option<String> {
+        let terms = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{}\"*", term))
+            .collect::<Vec<_>>();
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// Escapes a highlighted FTS5 column, then swaps the `char(2)`/`char(3)` markers for
+    /// `<mark>` tags.
+    pub fn highlight_to_html(highlighted: &str) -> String {
+        html! { (highlighted) }
+            .into_string()
+            .replace('\u{2}', "<mark>")
+            .replace('\u{3}', "</mark>")
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AddToBucketList {
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub force: bool,
 }
 
 impl AddToBucketList {
+    /// `existing_names` is loaded by the caller, so a failed query is an error instead of
+    /// "no duplicates".
+    pub fn to_validated(
+        &self,
+        existing_names: &[String],
+    ) -> Result<AddToBucketListValidated, ValidationErrorResponse> {
+        let mut builder = ValidationErrorsBuilder::new();
+
+        let name = builder
+            .add_item_from_trait(
+                Name::parse(self.name.clone(), None).check_duplicate_name_result(
+                    existing_names,
+                    self.force,
+                    None,
+                ),
+            )
+            .unwrap_or_default();
+        let description = builder
+            .add_item_from_trait(Description::parse(self.description.clone(), None))
+            .unwrap_or_default();
+        let tags = builder
+            .add_item_from_trait(Tags::parse(self.tags.clone(), None))
+            .unwrap_or_default();
+
+        builder.build_result()?;
+
+        Ok(AddToBucketListValidated {
+            name,
+            description,
+            tags,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateBucketList {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub version: Option<i64>,
+}
+
+impl UpdateBucketList {
     pub fn to_validated(&self) -> Result<AddToBucketListValidated, ValidationErrorResponse> {
         let mut builder = ValidationErrorsBuilder::new();
 
@@ -28,14 +127,93 @@ impl AddToBucketList {
         let description = builder
             .add_item_from_trait(Description::parse(self.description.clone(), None))
             .unwrap_or_default();
+        let tags = builder
+            .add_item_from_trait(Tags::parse(self.tags.clone(), None))
+            .unwrap_or_default();
 
         builder.build_result()?;
 
-        Ok(AddToBucketListValidated { name, description })
+        Ok(AddToBucketListValidated {
+            name,
+            description,
+            tags,
+        })
     }
 }
 
 pub struct AddToBucketListValidated {
     pub name: Name,
     pub description: Description,
+    pub tags: Tags,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompleteBucketList {
+    pub completed: bool,
+    pub version: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MonthCount {
+    pub month: String,
+    pub total: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TagCount {
+    pub tag: String,
+    pub total: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BucketListStats {
+    pub total: i64,
+    pub completed: i64,
+    pub completion_rate: f64,
+    pub average_days_to_complete: Option<f64>,
+    pub items_per_month: Box<[MonthCount]>,
+    pub top_tags: Box<[TagCount]>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_fts_query() {
+        assert_eq!(
+            BucketListSearchResult::to_fts_query("visit \"japan\" OR*"),
+            Some("\"visit\"* \"japan\"* \"OR\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_to_fts_query_empty() {
+        assert_eq!(BucketListSearchResult::to_fts_query(" ()* "), None);
+    }
+
+    #[test]
+    fn test_highlight_to_html() {
+        assert_eq!(
+            BucketListSearchResult::highlight_to_html("<b>\u{2}Japan\u{3}</b>"),
+            "&lt;b&gt;<mark>Japan</mark>&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_version_from_etag() {
+        assert_eq!(BucketListItem::version_from_etag(3, "\"3-2\""), Some(2));
+        assert_eq!(BucketListItem::version_from_etag(3, "W/\"3-2\""), Some(2));
+    }
+
+    #[test]
+    fn test_version_from_etag_error_other_item() {
+        assert_eq!(BucketListItem::version_from_etag(4, "\"3-2\""), None);
+    }
+
+    #[test]
+    fn test_version_from_etag_error_malformed() {
+        assert_eq!(BucketListItem::version_from_etag(3, "*"), None);
+        assert_eq!(BucketListItem::version_from_etag(3, "\"3-abc\""), None);
+    }
 }
@@ -1,10 +1,14 @@
-use crate::bucket_list::model::{AddToBucketListValidated, BucketListItem};
+use crate::bucket_list::model::{
+    AddToBucketListValidated, BucketListItem, BucketListSearchResult, BucketListStats, MonthCount,
+    TagCount,
+};
 use crate::db::SqliteClient;
 use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
 use crate::error::ErrorStatus;
+use crate::markdown::render_markdown;
 use error_stack::{Report, ResultExt};
 use rocket::http::Status;
-use rusqlite::named_params;
+use rusqlite::{Connection, Row, named_params};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -23,6 +27,48 @@ impl ErrorStatus for BucketListRepositoryError {
     }
 }
 
+fn map_bucket_list_item(row: &Row) -> rusqlite::Result<BucketListItem> {
+    let description: String = row.get("description")?;
+    Ok(BucketListItem {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        description_html: render_markdown(&description),
+        description,
+        timestamp: row.get("timestamp")?,
+        version: row.get("version")?,
+        completed_at: row.get("completed_at")?,
+        tags: row
+            .get::<_, Option<String>>("tags")?
+            .map(|tags| tags.split(',').map(|tag| tag.to_string()).collect())
+            .unwrap_or_default(),
+    })
+}
+
+fn replace_bucket_list_tags(
+    conn: &Connection,
+    bucket_list_id: i64,
+    tags: &[String],
+) -> Result<(), Report<BucketListRepositoryError>> {
+    conn.execute(
+        include_str!("_sql/delete_bucket_list_tags.sql"),
+        named_params! { ":bucket_list_id": bucket_list_id },
+    )
+    .change_context(BucketListRepositoryError::QueryError)?;
+
+    for tag in tags {
+        conn.execute(
+            include_str!("_sql/add_bucket_list_tag.sql"),
+            named_params! {
+                ":bucket_list_id": bucket_list_id,
+                ":tag": tag,
+            },
+        )
+        .change_context(BucketListRepositoryError::QueryError)?;
+    }
+
+    Ok(())
+}
+
 pub struct BucketListRepository {
     sqlite_client: SqliteClient,
 }
@@ -46,14 +92,7 @@ impl BucketListRepository {
             .change_context(BucketListRepositoryError::QueryError)?;
 
         let item_iter = stmt
-            .query_map([], |row| {
-                Ok(BucketListItem {
-                    id: row.get("id")?,
-                    name: row.get("name")?,
-                    description: row.get("description")?,
-                    timestamp: row.get("timestamp")?,
-                })
-            })
+            .query_map([], map_bucket_list_item)
             .change_context(BucketListRepositoryError::RowValueError)?;
 
         let mut items: Vec<BucketListItem> = Vec::new();
@@ -64,6 +103,60 @@ impl BucketListRepository {
         Ok(items.into())
     }
 
+    pub fn search_bucket_list(
+        &self,
+        query: &str,
+    ) -> Result<Box<[BucketListSearchResult]>, Report<BucketListRepositoryError>> {
+        let Some(query) = BucketListSearchResult::to_fts_query(query) else {
+            return Ok(Box::new([]));
+        };
+
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/search_bucket_list.sql"))
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        stmt.query_map(named_params! { ":query": query }, |row| {
+            Ok(BucketListSearchResult {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                name_html: BucketListSearchResult::highlight_to_html(
+                    &row.get::<_, String>("name_highlight")?,
+                ),
+                snippet_html: BucketListSearchResult::highlight_to_html(
+                    &row.get::<_, String>("description_snippet")?,
+                ),
+            })
+        })
+        .change_context(BucketListRepositoryError::QueryError)?
+        .collect::<Result<Box<[_]>, _>>()
+        .change_context(BucketListRepositoryError::RowValueError)
+    }
+
+    pub fn get_all_names_from_bucket_list(
+        &self,
+    ) -> Result<Box<[String]>, Report<BucketListRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_all_names_from_bucket_list.sql"))
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        stmt.query_map([], |row| row.get("name"))
+            .change_context(BucketListRepositoryError::QueryError)?
+            .collect::<Result<Box<[_]>, _>>()
+            .change_context(BucketListRepositoryError::RowValueError)
+    }
+
     pub fn add_to_bucket_list(
         &self,
         add_to_bucket_list: &AddToBucketListValidated,
@@ -74,7 +167,11 @@ impl BucketListRepository {
             .lock()
             .map_err(|_| BucketListRepositoryError::LockError)?;
 
-        conn.execute(
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        tx.execute(
             include_str!("_sql/add_to_bucket_list.sql"),
             named_params! {
                 ":name": add_to_bucket_list.name.as_str(),
@@ -82,8 +179,165 @@ impl BucketListRepository {
             },
         )
         .change_context(BucketListRepositoryError::QueryError)?;
+        replace_bucket_list_tags(
+            &tx,
+            tx.last_insert_rowid(),
+            add_to_bucket_list.tags.as_slice(),
+        )?;
+
+        tx.commit()
+            .change_context(BucketListRepositoryError::QueryError)
+    }
+
+    pub fn get_bucket_list_item(
+        &self,
+        id: i64,
+    ) -> Result<Option<BucketListItem>, Report<BucketListRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_bucket_list_item.sql"))
+            .change_context(BucketListRepositoryError::QueryError)?;
 
-        Ok(())
+        let mut item_iter = stmt
+            .query_map(named_params! { ":id": id }, map_bucket_list_item)
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        item_iter
+            .next()
+            .transpose()
+            .change_context(BucketListRepositoryError::RowValueError)
+    }
+
+    /// Returns `false` when the item does not exist or `version` is no longer current.
+    pub fn update_bucket_list_item(
+        &self,
+        id: i64,
+        version: i64,
+        update_bucket_list: &AddToBucketListValidated,
+    ) -> Result<bool, Report<BucketListRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        let updated = tx
+            .execute(
+                include_str!("_sql/update_bucket_list_item.sql"),
+                named_params! {
+                    ":id": id,
+                    ":version": version,
+                    ":name": update_bucket_list.name.as_str(),
+                    ":description": update_bucket_list.description.as_str(),
+                },
+            )
+            .change_context(BucketListRepositoryError::QueryError)?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        replace_bucket_list_tags(&tx, id, update_bucket_list.tags.as_slice())?;
+
+        tx.commit()
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        Ok(true)
+    }
+
+    /// Returns `false` when the item does not exist or `version` is outdated.
+    pub fn complete_bucket_list_item(
+        &self,
+        id: i64,
+        version: i64,
+        completed: bool,
+    ) -> Result<bool, Report<BucketListRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let updated = conn
+            .execute(
+                include_str!("_sql/complete_bucket_list_item.sql"),
+                named_params! {
+                    ":id": id,
+                    ":version": version,
+                    ":completed": completed,
+                },
+            )
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        Ok(updated > 0)
+    }
+
+    pub fn get_bucket_list_stats(
+        &self,
+    ) -> Result<BucketListStats, Report<BucketListRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| BucketListRepositoryError::LockError)?;
+
+        let (total, completed, average_days_to_complete): (i64, i64, Option<f64>) = conn
+            .query_row(include_str!("_sql/get_stats_completion.sql"), [], |row| {
+                Ok((
+                    row.get("total")?,
+                    row.get("completed")?,
+                    row.get("average_days_to_complete")?,
+                ))
+            })
+            .change_context(BucketListRepositoryError::QueryError)?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_stats_items_per_month.sql"))
+            .change_context(BucketListRepositoryError::QueryError)?;
+        let items_per_month = stmt
+            .query_map([], |row| {
+                Ok(MonthCount {
+                    month: row.get("month")?,
+                    total: row.get("total")?,
+                })
+            })
+            .change_context(BucketListRepositoryError::QueryError)?
+            .collect::<Result<Box<[_]>, _>>()
+            .change_context(BucketListRepositoryError::RowValueError)?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_stats_top_tags.sql"))
+            .change_context(BucketListRepositoryError::QueryError)?;
+        let top_tags = stmt
+            .query_map([], |row| {
+                Ok(TagCount {
+                    tag: row.get("tag")?,
+                    total: row.get("total")?,
+                })
+            })
+            .change_context(BucketListRepositoryError::QueryError)?
+            .collect::<Result<Box<[_]>, _>>()
+            .change_context(BucketListRepositoryError::RowValueError)?;
+
+        Ok(BucketListStats {
+            total,
+            completed,
+            completion_rate: if total > 0 {
+                completed as f64 / total as f64
+            } else {
+                0.0
+            },
+            average_days_to_complete,
+            items_per_month,
+            top_tags,
+        })
     }
 }
 
@@ -1,10 +1,17 @@
-use crate::bucket_list::model::{AddToBucketList, BucketListItem};
+use crate::bucket_list::model::{
+    AddToBucketList, BucketListItem, BucketListSearchResult, BucketListStats, CompleteBucketList,
+    UpdateBucketList,
+};
 use crate::bucket_list::repository::{BucketListRepository, BucketListRepositoryError};
+use crate::csrf::CsrfHeader;
 use crate::dependency::Dep;
 use crate::error::{ErrorOutput, ErrorReportResponse};
 use crate::html_base::ContextHtmlBuilder;
 use crate::icon::plus_icon;
 use crate::user::dependency::UserDep;
+use crate::user::flag::{ApiReadFlag, ApiWriteFlag};
+use crate::user::service::NoopService;
+use crate::utils::{IfMatch, WithEtag};
 use crate::validation::ValidationErrorResponse;
 use error_stack::ResultExt;
 use maud::{Markup, PreEscaped, html};
@@ -23,27 +30,71 @@ pub async fn main_bucket_list(context_html_builder: UserDep<ContextHtmlBuilder>)
         .attach_content(html! {
             h1 .mt-3 { (title) }
             div #bucket-list .mt-3 v-cloak {
+                div .bucket-form {
+                    input .bucket-list-col .bucket-form-input
+                        type="search" placeholder="Search names and descriptions"
+                        "v-model"="search_query" "v-on:input"="searchBucketList";
+                }
+                div "v-if"="search_query" {
+                    div .bucket-list-header {
+                        span .bucket-list-col { "Name" }
+                        span .bucket-list-col { "Match" }
+                    }
+                    div .bucket-list-item "v-for"="result in search_results" {
+                        span .bucket-list-col "v-html"="result.name_html" {}
+                        span .bucket-list-col "v-html"="result.snippet_html" {}
+                    }
+                    p .mt-3 "v-if"="search_results.length === 0" { "No matches." }
+                    h2 .mt-5 { "All items" }
+                }
                 div .bucket-list-header {
                     span .bucket-list-col { "ID" }
                     span .bucket-list-col { "Name" }
                     span .bucket-list-col { "Description" }
+                    span .bucket-list-col { "Tags" }
                     span .bucket-list-col { "Timestamp" }
+                    span .bucket-list-col {}
                 }
                 div .bucket-list-item "v-for"="item in bucket_list" {
                     span .bucket-list-col { "{{ item.id }}" }
                     span .bucket-list-col { "{{ item.name }}" }
-                    span .bucket-list-col { "{{ item.description }}" }
+                    span .bucket-list-col "v-html"="item.description_html" {}
+                    span .bucket-list-col { "{{ item.tags.join(', ') }}" }
                     span .bucket-list-col { "{{ item.timestamp }}" }
+                    span .bucket-list-col {
+                        button .btn .btn-sky-blue "v-on:click"="editItem(item)" { "Edit" }
+                        button .btn .btn-sky-blue .mt-3 "v-on:click"="completeItem(item)" {
+                            "{{ item.completed_at ? 'Reopen' : 'Complete' }}"
+                        }
+                    }
                 }
                 div .bucket-form .mt-5 {
                     input .bucket-list-col .bucket-form-input
                         type="text" placeholder="Name" "v-model"="input_name";
+                    textarea .bucket-list-col .bucket-form-input
+                        placeholder="Description (Markdown)" "v-model"="input_description" {}
                     input .bucket-list-col .bucket-form-input
-                        type="text" placeholder="Description" "v-model"="input_description";
-                    button .bucket-list-col .btn .btn-sky-blue "v-on:click"="addToBucketList" {
-                        "Add"
-                        (plus_icon())
+                        type="text" placeholder="Tags, comma separated" "v-model"="input_tags";
+                    button .bucket-list-col .btn .btn-sky-blue "v-on:click"="submitForm" {
+                        span "v-if"="editing" { "Save" }
+                        span "v-else" { "Add" (plus_icon()) }
+                    }
+                    button .bucket-list-col .btn .btn-sky-blue "v-if"="editing" "v-on:click"="resetForm" {
+                        "Cancel"
+                    }
+                }
+                div .bucket-form-error "v-if"="error && error.duplicate" {
+                    span .bucket-list-col {
+                        ul {
+                            li "v-for"="message in error.duplicate" { "{{ message }}" }
+                        }
                     }
+                    button .bucket-list-col .btn .btn-sky-blue "v-on:click"="addToBucketList(true)" {
+                        "Add anyway"
+                    }
+                }
+                div .bucket-form-error "v-if"="conflict" {
+                    span .bucket-list-col { "{{ conflict }}" }
                 }
                 div .bucket-form-error "v-if"="error" {
                     span .bucket-list-col {
@@ -56,6 +107,11 @@ pub async fn main_bucket_list(context_html_builder: UserDep<ContextHtmlBuilder>)
                             li "v-for"="message in error.description" { "{{ message }}" }
                         }
                     }
+                    span .bucket-list-col {
+                        ul {
+                            li "v-for"="message in error.tags" { "{{ message }}" }
+                        }
+                    }
                     span .bucket-list-col {}
                 }
             }
@@ -76,12 +132,27 @@ pub fn get_bucket_list_js() -> Markup {
 
 #[get("/all")]
 pub async fn all_bucket_list(
+    _api: UserDep<NoopService, ApiReadFlag>,
     repo: Dep<BucketListRepository>,
 ) -> Result<Json<Box<[BucketListItem]>>, ErrorReportResponse<BucketListRepositoryError>> {
     let items = repo
         .get_all_from_bucket_list()
         .attach(ErrorOutput::Json)
-        .map_err(|e| ErrorReportResponse(e))?;
+        .map_err(ErrorReportResponse)?;
+
+    Ok(Json(items))
+}
+
+#[get("/search?<q>")]
+pub async fn search_bucket_list(
+    q: &str,
+    _api: UserDep<NoopService, ApiReadFlag>,
+    repo: Dep<BucketListRepository>,
+) -> Result<Json<Box<[BucketListSearchResult]>>, ErrorReportResponse<BucketListRepositoryError>> {
+    let items = repo
+        .search_bucket_list(q)
+        .attach(ErrorOutput::Json)
+        .map_err(ErrorReportResponse)?;
 
     Ok(Json(items))
 }
@@ -95,11 +166,20 @@ pub enum AddBucketListRouteError {
 #[post("/add", data = "<data>")]
 pub async fn add_bucket_list(
     data: Json<AddToBucketList>,
+    _csrf: CsrfHeader,
+    _api: UserDep<NoopService, ApiWriteFlag>,
     repo: Dep<BucketListRepository>,
 ) -> Result<Value, AddBucketListRouteError> {
+    let existing_names = match data.force {
+        true => Box::default(),
+        false => repo
+            .get_all_names_from_bucket_list()
+            .attach(ErrorOutput::Json)
+            .map_err(|e| AddBucketListRouteError::Repo(ErrorReportResponse(e)))?,
+    };
     let data = data
-        .to_validated()
-        .map_err(|e| AddBucketListRouteError::Validate(e))?;
+        .to_validated(&existing_names)
+        .map_err(AddBucketListRouteError::Validate)?;
 
     repo.add_to_bucket_list(&data)
         .attach(ErrorOutput::Json)
@@ -108,6 +188,223 @@ pub async fn add_bucket_list(
     Ok(json!({"message": "success"}))
 }
 
+#[derive(Responder)]
+pub enum BucketListItemRouteError {
+    Repo(ErrorReportResponse<BucketListRepositoryError>),
+    #[response(status = 404)]
+    NotFound(Value),
+}
+
+fn find_bucket_list_item(
+    id: i64,
+    repo: &BucketListRepository,
+) -> Result<WithEtag<Json<BucketListItem>>, BucketListItemRouteError> {
+    let item = repo
+        .get_bucket_list_item(id)
+        .attach(ErrorOutput::Json)
+        .map_err(|e| BucketListItemRouteError::Repo(ErrorReportResponse(e)))?
+        .ok_or_else(|| BucketListItemRouteError::NotFound(json!({"message": "not found"})))?;
+
+    let etag = item.etag();
+    Ok(WithEtag::new(Json(item), etag))
+}
+
+#[get("/<id>")]
+pub async fn get_bucket_list_item(
+    id: i64,
+    _api: UserDep<NoopService, ApiReadFlag>,
+    repo: Dep<BucketListRepository>,
+) -> Result<WithEtag<Json<BucketListItem>>, BucketListItemRouteError> {
+    find_bucket_list_item(id, &repo)
+}
+
+#[derive(Responder)]
+pub enum PreconditionError {
+    /// `If-Match` was sent, but it is malformed, `*` or names another item.
+    #[response(status = 412)]
+    Failed(Value),
+    #[response(status = 428)]
+    Required(Value),
+}
+
+/// The version the client last saw, from `If-Match` or else the body.
+fn expected_version(
+    id: i64,
+    if_match: &IfMatch,
+    version: Option<i64>,
+) -> Result<i64, PreconditionError> {
+    match if_match.etag() {
+        Some(etag) => BucketListItem::version_from_etag(id, etag).ok_or_else(|| {
+            PreconditionError::Failed(
+                json!({"message": "If-Match does not name a version of this item"}),
+            )
+        }),
+        None => version.ok_or_else(|| {
+            PreconditionError::Required(
+                json!({"message": "If-Match header or version is required"}),
+            )
+        }),
+    }
+}
+
+#[derive(Responder)]
+pub enum UpdateBucketListRouteError {
+    Repo(ErrorReportResponse<BucketListRepositoryError>),
+    Validate(ValidationErrorResponse),
+    #[response(status = 404)]
+    NotFound(Value),
+    #[response(status = 409)]
+    Conflict(WithEtag<Json<BucketListItem>>),
+    Precondition(PreconditionError),
+}
+
+#[put("/<id>", data = "<data>")]
+pub async fn update_bucket_list_item(
+    id: i64,
+    data: Json<UpdateBucketList>,
+    _csrf: CsrfHeader,
+    _api: UserDep<NoopService, ApiWriteFlag>,
+    if_match: IfMatch,
+    repo: Dep<BucketListRepository>,
+) -> Result<WithEtag<Json<BucketListItem>>, UpdateBucketListRouteError> {
+    let version = expected_version(id, &if_match, data.version)
+        .map_err(UpdateBucketListRouteError::Precondition)?;
+
+    let validated = data
+        .to_validated()
+        .map_err(UpdateBucketListRouteError::Validate)?;
+
+    let updated = repo
+        .update_bucket_list_item(id, version, &validated)
+        .attach(ErrorOutput::Json)
+        .map_err(|e| UpdateBucketListRouteError::Repo(ErrorReportResponse(e)))?;
+
+    let item = repo
+        .get_bucket_list_item(id)
+        .attach(ErrorOutput::Json)
+        .map_err(|e| UpdateBucketListRouteError::Repo(ErrorReportResponse(e)))?
+        .ok_or_else(|| UpdateBucketListRouteError::NotFound(json!({"message": "not found"})))?;
+
+    let etag = item.etag();
+    if updated {
+        Ok(WithEtag::new(Json(item), etag))
+    } else {
+        Err(UpdateBucketListRouteError::Conflict(WithEtag::new(
+            Json(item),
+            etag,
+        )))
+    }
+}
+
+#[post("/<id>/complete", data = "<data>")]
+pub async fn complete_bucket_list_item(
+    id: i64,
+    data: Json<CompleteBucketList>,
+    _csrf: CsrfHeader,
+    _api: UserDep<NoopService, ApiWriteFlag>,
+    if_match: IfMatch,
+    repo: Dep<BucketListRepository>,
+) -> Result<WithEtag<Json<BucketListItem>>, UpdateBucketListRouteError> {
+    let version = expected_version(id, &if_match, data.version)
+        .map_err(UpdateBucketListRouteError::Precondition)?;
+
+    let updated = repo
+        .complete_bucket_list_item(id, version, data.completed)
+        .attach(ErrorOutput::Json)
+        .map_err(|e| UpdateBucketListRouteError::Repo(ErrorReportResponse(e)))?;
+
+    let item = repo
+        .get_bucket_list_item(id)
+        .attach(ErrorOutput::Json)
+        .map_err(|e| UpdateBucketListRouteError::Repo(ErrorReportResponse(e)))?
+        .ok_or_else(|| UpdateBucketListRouteError::NotFound(json!({"message": "not found"})))?;
+
+    let etag = item.etag();
+    if updated {
+        Ok(WithEtag::new(Json(item), etag))
+    } else {
+        Err(UpdateBucketListRouteError::Conflict(WithEtag::new(
+            Json(item),
+            etag,
+        )))
+    }
+}
+
+#[get("/stats")]
+pub async fn bucket_list_stats(
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    repo: Dep<BucketListRepository>,
+) -> Result<Markup, ErrorReportResponse<BucketListRepositoryError>> {
+    let stats = repo.get_bucket_list_stats().map_err(ErrorReportResponse)?;
+    let title = "Bucket List Statistics";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("bucket-list-stats".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Items" }
+                    span .bucket-list-col { "Completed" }
+                    span .bucket-list-col { "Completion rate" }
+                    span .bucket-list-col { "Average time to complete" }
+                }
+                div .bucket-list-item {
+                    span .bucket-list-col { (stats.total) }
+                    span .bucket-list-col { (stats.completed) }
+                    span .bucket-list-col { (format!("{:.0}%", stats.completion_rate * 100.0)) }
+                    span .bucket-list-col {
+                        @match stats.average_days_to_complete {
+                            Some(days) => (format!("{:.1} days", days)),
+                            None => "-",
+                        }
+                    }
+                }
+            }
+            h2 .mt-3 { "Items added per month" }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Month" }
+                    span .bucket-list-col { "Items" }
+                }
+                @for month in &stats.items_per_month {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (month.month) }
+                        span .bucket-list-col { (month.total) }
+                    }
+                }
+            }
+            h2 .mt-3 { "Top tags" }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Tag" }
+                    span .bucket-list-col { "Items" }
+                }
+                @for tag in &stats.top_tags {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (tag.tag) }
+                        span .bucket-list-col { (tag.total) }
+                    }
+                }
+            }
+        })
+        .build())
+}
+
+#[get("/stats.json")]
+pub async fn bucket_list_stats_json(
+    _api: UserDep<NoopService, ApiReadFlag>,
+    repo: Dep<BucketListRepository>,
+) -> Result<Json<BucketListStats>, ErrorReportResponse<BucketListRepositoryError>> {
+    let stats = repo
+        .get_bucket_list_stats()
+        .attach(ErrorOutput::Json)
+        .map_err(ErrorReportResponse)?;
+
+    Ok(Json(stats))
+}
+
 pub struct BucketListRoute;
 
 impl BucketListRoute {
@@ -115,7 +412,17 @@ impl BucketListRoute {
         AdHoc::on_ignite("BucketListRoute", |rocket| async {
             rocket.mount(
                 "/bucket-list",
-                routes![main_bucket_list, all_bucket_list, add_bucket_list],
+                routes![
+                    main_bucket_list,
+                    all_bucket_list,
+                    search_bucket_list,
+                    add_bucket_list,
+                    get_bucket_list_item,
+                    update_bucket_list_item,
+                    complete_bucket_list_item,
+                    bucket_list_stats,
+                    bucket_list_stats_json
+                ],
             )
         })
     }
@@ -34,12 +34,11 @@ impl Description {
         check_count.then(|| {
             (description_validator.count_graphemes() < 5)
                 .then(|| message.push(format!("{} must be at least 5 characters", &field_name)));
-            (description_validator.count_graphemes() > 100)
-                .then(|| message.push(format!("{} must be at most 100 characters", &field_name)));
+            (description_validator.count_graphemes() > 1000)
+                .then(|| message.push(format!("{} must be at most 1000 characters", &field_name)));
         });
 
-        ValidateErrorItem::from_vec(field_name, message)
-            .then_err_report(|i| DescriptionError(i))?;
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(DescriptionError)?;
         Ok(Description(description))
     }
 
@@ -58,6 +57,15 @@ mod tests {
         assert!(description.is_ok());
     }
 
+    #[test]
+    fn test_parse_description_markdown() {
+        let description = Description::parse(
+            "Visit **Tokyo** and [Kyoto](https://example.com)".to_string(),
+            None,
+        );
+        assert!(description.is_ok());
+    }
+
     #[test]
     fn test_parse_description_error_empty_description() {
         let description = Description::parse("".to_string(), None);
@@ -72,7 +80,7 @@ mod tests {
 
     #[test]
     fn test_parse_description_error_description_length_too_long() {
-        let description = Description::parse("a".repeat(101), None);
+        let description = Description::parse("a".repeat(1001), None);
         assert!(description.is_err());
     }
 }
@@ -1,2 +1,3 @@
 pub mod description;
 pub mod name;
+pub mod tags;
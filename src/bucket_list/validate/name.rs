@@ -35,7 +35,7 @@ impl Name {
                 .then(|| message.push(format!("{} must be at most 20 characters", &field_name)));
         });
 
-        ValidateErrorItem::from_vec(field_name, message).then_err_report(|i| NameError(i))?;
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(NameError)?;
         Ok(Name(name))
     }
 
@@ -44,6 +44,108 @@ impl Name {
     }
 }
 
+/// Lowercase, drop punctuation and collapse whitespace, so "Visit Japan" and "visit  japan!"
+/// compare equal.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub enum DuplicateName {
+    Exact(String),
+    Similar(String),
+}
+
+/// Shorter names are only compared exactly, one changed letter already makes "Ski" "Sky".
+const MIN_SIMILAR_LENGTH: usize = 6;
+
+pub fn find_duplicate_name(name: &str, existing_names: &[String]) -> Option<DuplicateName> {
+    let normalized = normalize_name(name);
+    let mut similar: Option<DuplicateName> = None;
+    for existing_name in existing_names {
+        let existing_normalized = normalize_name(existing_name);
+        if existing_normalized == normalized {
+            return Some(DuplicateName::Exact(existing_name.clone()));
+        }
+        let length = normalized.chars().count();
+        let existing_length = existing_normalized.chars().count();
+        if length.min(existing_length) < MIN_SIMILAR_LENGTH {
+            continue;
+        }
+        let threshold = (length.max(existing_length) / 5).max(1);
+        if similar.is_none() && edit_distance(&normalized, &existing_normalized) <= threshold {
+            similar = Some(DuplicateName::Similar(existing_name.clone()));
+        }
+    }
+    similar
+}
+
+trait Sealed {}
+
+#[allow(private_bounds)]
+pub trait NameCheckResult: Sealed {
+    fn check_duplicate_name_result(
+        self,
+        existing_names: &[String],
+        force: bool,
+        field_name: Option<String>,
+    ) -> Self;
+}
+
+impl Sealed for Result<Name, Report<NameError>> {}
+
+impl NameCheckResult for Result<Name, Report<NameError>> {
+    fn check_duplicate_name_result(
+        self,
+        existing_names: &[String],
+        force: bool,
+        field_name: Option<String>,
+    ) -> Self {
+        match self {
+            Ok(v) if !force => {
+                let mut message: Vec<String> = vec![];
+                let field_name = field_name.unwrap_or("duplicate".to_string());
+
+                match find_duplicate_name(v.as_str(), existing_names) {
+                    Some(DuplicateName::Exact(existing_name)) => message.push(format!(
+                        "name matches '{}' already on the bucket list",
+                        existing_name
+                    )),
+                    Some(DuplicateName::Similar(existing_name)) => message.push(format!(
+                        "name is similar to '{}' already on the bucket list",
+                        existing_name
+                    )),
+                    None => {}
+                }
+
+                ValidateErrorItem::from_vec(field_name, message).then_err_report(NameError)?;
+
+                Ok(v)
+            }
+            _ => self,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,4 +173,67 @@ mod tests {
         let name = Name::parse("a".repeat(21), None);
         assert!(name.is_err());
     }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("  Visit   Japan! "), "visit japan");
+    }
+
+    #[test]
+    fn test_find_duplicate_name_exact() {
+        let existing = ["Visit Japan".to_string()];
+        assert!(matches!(
+            find_duplicate_name("visit japan!", &existing),
+            Some(DuplicateName::Exact(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_duplicate_name_similar() {
+        let existing = ["Visit Japan".to_string()];
+        assert!(matches!(
+            find_duplicate_name("Visit Japon", &existing),
+            Some(DuplicateName::Similar(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_duplicate_name_none() {
+        let existing = ["Visit Japan".to_string()];
+        assert!(find_duplicate_name("Skydiving", &existing).is_none());
+    }
+
+    #[test]
+    fn test_find_duplicate_name_short_names_exact_only() {
+        let existing = ["Run".to_string(), "Ski".to_string(), "Hikes".to_string()];
+        assert!(find_duplicate_name("Sun", &existing).is_none());
+        assert!(find_duplicate_name("Sky", &existing).is_none());
+        assert!(find_duplicate_name("Bikes", &existing).is_none());
+        assert!(matches!(
+            find_duplicate_name("ski!", &existing),
+            Some(DuplicateName::Exact(_))
+        ));
+    }
+
+    #[test]
+    fn name_is_duplicate() {
+        let name_result: Result<Name, Report<NameError>> = Ok(Name("visit japan".to_string()));
+
+        assert!(
+            name_result
+                .check_duplicate_name_result(&["Visit Japan".to_string()], false, None)
+                .is_err()
+        )
+    }
+
+    #[test]
+    fn name_is_duplicate_forced() {
+        let name_result: Result<Name, Report<NameError>> = Ok(Name("visit japan".to_string()));
+
+        assert!(
+            name_result
+                .check_duplicate_name_result(&["Visit Japan".to_string()], true, None)
+                .is_ok()
+        )
+    }
 }
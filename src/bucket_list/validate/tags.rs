@@ -0,0 +1,91 @@
+use crate::validation::{
+    OptionValidateErrorItemTrait, StrValidationExtension, ValidateErrorItem, ValidateErrorItemTrait,
+};
+use error_stack::Report;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Tags Error")]
+pub struct TagsError(ValidateErrorItem);
+
+impl ValidateErrorItemTrait for TagsError {
+    fn get_validate_error_item(&self) -> Option<ValidateErrorItem> {
+        Some(self.0.clone())
+    }
+}
+
+#[derive(Default)]
+pub struct Tags(Box<[String]>);
+
+impl Tags {
+    pub fn parse(tags: Vec<String>, field_name: Option<String>) -> Result<Self, Report<TagsError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("tags".to_string());
+
+        let mut normalized: Vec<String> = vec![];
+        for tag in tags {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+
+        (normalized.len() > 5)
+            .then(|| message.push(format!("{} must have at most 5 tags", &field_name)));
+        normalized
+            .iter()
+            .any(|tag| tag.as_str().as_string_validator().count_graphemes() > 20)
+            .then(|| {
+                message.push(format!(
+                    "{} must be at most 20 characters each",
+                    &field_name
+                ))
+            });
+        normalized
+            .iter()
+            .any(|tag| tag.contains(','))
+            .then(|| message.push(format!("{} cannot contain commas", &field_name)));
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(TagsError)?;
+        Ok(Tags(normalized.into()))
+    }
+
+    pub fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        let tags = Tags::parse(vec!["Travel".to_string(), " travel ".to_string()], None);
+        assert_eq!(tags.unwrap().as_slice(), ["travel".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_tags_empty() {
+        let tags = Tags::parse(vec![], None);
+        assert!(tags.unwrap().as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_parse_tags_error_too_many() {
+        let tags = Tags::parse((0..6).map(|i| format!("tag{}", i)).collect(), None);
+        assert!(tags.is_err());
+    }
+
+    #[test]
+    fn test_parse_tags_error_too_long() {
+        let tags = Tags::parse(vec!["a".repeat(21)], None);
+        assert!(tags.is_err());
+    }
+
+    #[test]
+    fn test_parse_tags_error_comma() {
+        let tags = Tags::parse(vec!["a,b".to_string()], None);
+        assert!(tags.is_err());
+    }
+}
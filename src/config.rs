@@ -1,12 +1,18 @@
+use crate::user::token::generate_token;
 use figment::providers::{Format, Serialized, Toml};
 use figment::{Figment, Profile};
 use rocket::Config as RocketConfig;
+use rocket::config::SecretKey;
 use serde::{Deserialize, Serialize};
 use std::env::var;
 
+/// Release builds need `secret_key` configured. Debug builds get a random one per run when it is
+/// missing, sessions then end with each restart.
 pub fn get_figment_for_rocket() -> Figment {
-    Figment::from(rocket::Config::figment())
+    let figment = Figment::from(rocket::Config::figment())
         .merge(Serialized::defaults(RocketConfig::default()))
+        // The client IP keys the login throttle, a proxy header has to be named in the config.
+        .merge(("ip_header", false))
         .merge(Toml::file("exercise_rocket.toml").nested())
         .merge(
             Toml::file(
@@ -15,18 +21,296 @@ pub fn get_figment_for_rocket() -> Figment {
             )
             .nested(),
         )
-        .select(Profile::from_env_or("EXERCISE_ROCKET_PROFILE", "default"))
+        .select(Profile::from_env_or("EXERCISE_ROCKET_PROFILE", "default"));
+
+    let has_secret_key = figment
+        .extract_inner::<SecretKey>("secret_key")
+        .is_ok_and(|secret_key| !secret_key.is_zero());
+    if cfg!(debug_assertions) && !has_secret_key {
+        figment.merge(("secret_key", generate_token()))
+    } else {
+        figment
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub sqlite_path: String,
+    /// Used to build absolute links, e.g. in mails.
+    pub public_url: String,
+    pub mail: MailConfig,
+    pub email_verification: EmailVerificationConfig,
+    pub profile: ProfileConfig,
+    pub registration: RegistrationMode,
+    pub username: UsernameConfig,
+    /// Key for hashing login and reset tokens and for encrypting TOTP secrets, users with TOTP
+    /// can not log in after it changed. When empty, a random one is generated once and kept in
+    /// `<sqlite_path>.token_secret`.
+    pub token_secret: String,
+    pub session: SessionConfig,
+    pub login_throttle: LoginThrottleConfig,
+    pub password_hash: PasswordHashConfig,
+    /// Sorted SHA-1 hash list, lines `HASH[:count]`, new passwords found in it are refused.
+    /// Empty disables the check.
+    pub breached_password_path: String,
+    pub oidc: OidcConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             sqlite_path: "./sqlite.db".to_string(),
+            public_url: "http://127.0.0.1:8000".to_string(),
+            mail: MailConfig::default(),
+            email_verification: EmailVerificationConfig::default(),
+            profile: ProfileConfig::default(),
+            registration: RegistrationMode::Open,
+            username: UsernameConfig::default(),
+            token_secret: "".to_string(),
+            session: SessionConfig::default(),
+            login_throttle: LoginThrottleConfig::default(),
+            password_hash: PasswordHashConfig::default(),
+            breached_password_path: "".to_string(),
+            oidc: OidcConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransportKind {
+    /// Writes every mail to `file_path`, for development.
+    File,
+    Smtp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, only for local SMTP stand-ins.
+    None,
+    StartTls,
+    Tls,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MailConfig {
+    pub transport: MailTransportKind,
+    pub from: String,
+    pub file_path: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_tls: SmtpTls,
+    pub smtp_username: String,
+    pub smtp_password: String,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            transport: MailTransportKind::File,
+            from: "Rust Vue Exercise <no-reply@localhost>".to_string(),
+            file_path: "./mail".to_string(),
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: 1025,
+            smtp_tls: SmtpTls::None,
+            smtp_username: "".to_string(),
+            smtp_password: "".to_string(),
+        }
+    }
+}
+
+/// Registration mails a signed link, it is checked against `token_secret`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmailVerificationConfig {
+    /// Users with an unverified address can not log in with their password.
+    pub required: bool,
+    pub link_valid_hours: i64,
+}
+
+impl Default for EmailVerificationConfig {
+    fn default() -> Self {
+        EmailVerificationConfig {
+            required: false,
+            link_valid_hours: 24,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileConfig {
+    /// Avatars are stored in the database, Rocket's `file` limit has to be at least as large.
+    pub avatar_max_kib: usize,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        ProfileConfig {
+            avatar_max_kib: 256,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionSameSite {
+    Strict,
+    /// Still sent when the OIDC provider redirects back.
+    Lax,
+    /// Needs `secure`, browsers drop it otherwise.
+    None,
+}
+
+/// The `login-token` cookie and the session row behind it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionConfig {
+    pub lifetime_days: u32,
+    /// Moves the expiry of the cookie and the session ahead on use, at most once a minute.
+    pub sliding: bool,
+    /// Only sent over HTTPS, browsers treat `localhost` as secure too.
+    pub secure: bool,
+    pub same_site: SessionSameSite,
+    /// Encrypts the cookie with Rocket's `secret_key`, release builds do not start without one
+    /// configured. Switching it logs everyone out.
+    pub private: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            lifetime_days: 30,
+            sliding: true,
+            secure: true,
+            same_site: SessionSameSite::Lax,
+            private: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    Open,
+    /// Registration needs an invite code created by an admin.
+    InviteOnly,
+    /// Only admins can add users, the register page is gone.
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UsernameCharset {
+    /// ASCII letters and digits, with `.`, `_` and `-`.
+    Ascii,
+    /// Letters and digits of any script, with `.`, `_` and `-`.
+    Unicode,
+}
+
+/// Rules for new usernames, existing accounts keep their names.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UsernameConfig {
+    pub charset: UsernameCharset,
+    /// Compared after normalization, so `Admin` is refused as well.
+    pub reserved: Vec<String>,
+}
+
+impl Default for UsernameConfig {
+    fn default() -> Self {
+        UsernameConfig {
+            charset: UsernameCharset::Ascii,
+            reserved: [
+                "admin",
+                "administrator",
+                "root",
+                "default",
+                "system",
+                "support",
+                "security",
+                "moderator",
+                "postmaster",
+                "webmaster",
+                "hostmaster",
+                "anonymous",
+                "visitor",
+                "null",
+                "undefined",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoginThrottleConfig {
+    /// Failures before the username is locked.
+    pub max_failures_per_username: u32,
+    /// Failures before the client IP is locked, higher as an IP may be shared.
+    pub max_failures_per_ip: u32,
+    /// Wait after the first failure, doubled after each further one.
+    pub backoff_base_seconds: i64,
+    pub lockout_minutes: i64,
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        LoginThrottleConfig {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 20,
+            backoff_base_seconds: 1,
+            lockout_minutes: 15,
+        }
+    }
+}
+
+/// Argon2id cost, stored hashes with other values are rehashed on the next login.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PasswordHashConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Hashes running at once on the blocking pool, each one holds `memory_kib` of memory.
+    pub max_concurrent: usize,
+    /// How long a request waits for a free slot before it gets a 503.
+    pub wait_timeout_ms: u64,
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        PasswordHashConfig {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            max_concurrent: 4,
+            wait_timeout_ms: 5000,
+        }
+    }
+}
+
+/// Sign in with an OpenID Connect provider, the redirect uri to register there is
+/// `public_url` + `/user/oidc/callback`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OidcConfig {
+    /// Empty disables single sign-on, plain http is only meant for a local mock provider.
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Empty for public clients, which rely on PKCE alone.
+    pub client_secret: String,
+    pub scopes: String,
+    pub button_label: String,
+    /// Unknown identities get a new local user, otherwise they have to be linked from the user page.
+    pub auto_register: bool,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        OidcConfig {
+            issuer_url: "".to_string(),
+            client_id: "".to_string(),
+            client_secret: "".to_string(),
+            scopes: "openid profile email".to_string(),
+            button_label: "Sign in with SSO".to_string(),
+            auto_register: false,
         }
     }
 }
@@ -0,0 +1,275 @@
+use crate::user::api_token::bearer_token;
+use crate::user::token::generate_token;
+use rocket::data::{FromData, Outcome as DataOutcome, ToByteUnit};
+use rocket::form::{Form, ValueField};
+use rocket::http::{ContentType, Cookie, RawStr, SameSite, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request};
+use std::ops::Deref;
+use thiserror::Error;
+
+const COOKIE_NAME: &str = "csrf-token";
+pub const FIELD_NAME: &str = "csrf_token";
+const HEADER_NAME: &str = "X-CSRF-Token";
+
+#[derive(Error, Debug, Clone, Copy)]
+pub enum CsrfError {
+    #[error("The request has no CSRF token, please reload the page and try again.")]
+    Missing,
+    #[error("The CSRF token does not match, please reload the page and try again.")]
+    Mismatch,
+}
+
+/// Kept in the request cache, so the 403 catcher can tell why the request was rejected.
+#[derive(Clone)]
+pub struct CsrfRejection(pub Option<CsrfError>);
+
+fn reject<T>(req: &Request<'_>, error: CsrfError) -> (Status, T)
+where
+    T: From<CsrfError>,
+{
+    req.local_cache(|| CsrfRejection(Some(error)));
+    (Status::Forbidden, error.into())
+}
+
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn check_token(req: &Request<'_>, sent: Option<&str>) -> Result<(), CsrfError> {
+    let expected = req
+        .cookies()
+        .get(COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty());
+    match (expected, sent) {
+        (Some(expected), Some(sent)) if constant_time_eq(&expected, sent) => Ok(()),
+        (Some(_), Some(_)) => Err(CsrfError::Mismatch),
+        _ => Err(CsrfError::Missing),
+    }
+}
+
+/// Per browser session token, the cookie is created on first use.
+pub struct CsrfToken(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = req.local_cache(|| {
+            if let Some(cookie) = req.cookies().get(COOKIE_NAME)
+                && !cookie.value().is_empty()
+            {
+                return cookie.value().to_string();
+            }
+
+            let token = generate_token();
+            req.cookies().add(
+                Cookie::build((COOKIE_NAME, token.clone()))
+                    .path("/")
+                    .http_only(true)
+                    .same_site(SameSite::Strict)
+                    .build(),
+            );
+            token
+        });
+
+        Outcome::Success(Self(token.clone()))
+    }
+}
+
+/// Guard for fetch calls, the token has to be sent in the `X-CSRF-Token` header.
+/// Requests with a bearer token are let through, as the browser never adds one by itself,
+/// the route's user guard checks the token.
+pub struct CsrfHeader;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfHeader {
+    type Error = CsrfError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if req
+            .headers()
+            .get_one("Authorization")
+            .and_then(bearer_token)
+            .is_some()
+        {
+            return Outcome::Success(Self);
+        }
+
+        match check_token(req, req.headers().get_one(HEADER_NAME)) {
+            Ok(()) => Outcome::Success(Self),
+            Err(error) => Outcome::Error(reject(req, error)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CsrfFormError {
+    #[error(transparent)]
+    Csrf(#[from] CsrfError),
+    #[error("Form error: {0}")]
+    Form(String),
+}
+
+/// Reads an url-encoded body, checks its `csrf_token` field and returns the other fields decoded.
+async fn read_form<'r>(
+    req: &'r Request<'_>,
+    data: Data<'r>,
+) -> DataOutcome<'r, Vec<(String, String)>, CsrfFormError> {
+    if req.content_type() != Some(&ContentType::Form) {
+        return DataOutcome::Error((
+            Status::UnsupportedMediaType,
+            CsrfFormError::Form("Expected an url-encoded form".to_string()),
+        ));
+    }
+
+    let limit = req.limits().get("form").unwrap_or(32.kibibytes());
+    let body = match data.open(limit).into_string().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => {
+            return DataOutcome::Error((
+                Status::PayloadTooLarge,
+                CsrfFormError::Form("Form is too large".to_string()),
+            ));
+        }
+        Err(err) => {
+            return DataOutcome::Error((Status::BadRequest, CsrfFormError::Form(err.to_string())));
+        }
+    };
+
+    let mut sent = None;
+    let mut fields = vec![];
+    for pair in body.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = RawStr::new(key).url_decode_lossy().into_owned();
+        let value = RawStr::new(value).url_decode_lossy().into_owned();
+        if key == FIELD_NAME {
+            sent = Some(value);
+        } else {
+            fields.push((key, value));
+        }
+    }
+
+    match check_token(req, sent.as_deref()) {
+        Ok(()) => DataOutcome::Success(fields),
+        Err(error) => DataOutcome::Error(reject(req, error)),
+    }
+}
+
+/// Multipart forms are parsed by Rocket, so the form itself has to carry the token.
+pub trait CsrfField {
+    fn csrf_token(&self) -> &str;
+}
+
+/// `CsrfForm` for `multipart/form-data`, e.g. forms with a file upload.
+pub struct CsrfMultipartForm<T>(pub T);
+
+impl<T> Deref for CsrfMultipartForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T> FromData<'r> for CsrfMultipartForm<T>
+where
+    T: rocket::form::FromForm<'r> + CsrfField + Send,
+{
+    type Error = CsrfFormError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> DataOutcome<'r, Self> {
+        if req
+            .content_type()
+            .is_none_or(|content_type| !content_type.is_form_data())
+        {
+            return DataOutcome::Error((
+                Status::UnsupportedMediaType,
+                CsrfFormError::Form("Expected a multipart form".to_string()),
+            ));
+        }
+
+        let form = match Form::<T>::from_data(req, data).await {
+            DataOutcome::Success(form) => form.into_inner(),
+            DataOutcome::Error((status, errors)) => {
+                return DataOutcome::Error((status, CsrfFormError::Form(errors.to_string())));
+            }
+            DataOutcome::Forward(forward) => return DataOutcome::Forward(forward),
+        };
+
+        let sent = Some(form.csrf_token()).filter(|token| !token.is_empty());
+        match check_token(req, sent) {
+            Ok(()) => DataOutcome::Success(Self(form)),
+            Err(error) => DataOutcome::Error(reject(req, error)),
+        }
+    }
+}
+
+/// Drop-in for `Form<T>` that also checks the hidden `csrf_token` field.
+pub struct CsrfForm<T>(pub T);
+
+impl<T> Deref for CsrfForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T> FromData<'r> for CsrfForm<T>
+where
+    T: for<'a> rocket::form::FromForm<'a> + Send,
+{
+    type Error = CsrfFormError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> DataOutcome<'r, Self> {
+        let fields = match read_form(req, data).await {
+            DataOutcome::Success(fields) => fields,
+            DataOutcome::Error(error) => return DataOutcome::Error(error),
+            DataOutcome::Forward(forward) => return DataOutcome::Forward(forward),
+        };
+
+        let fields = fields
+            .iter()
+            .map(|(key, value)| ValueField::from((key.as_str(), value.as_str())));
+        match Form::<T>::parse_iter(fields) {
+            Ok(form) => DataOutcome::Success(Self(form)),
+            Err(errors) => DataOutcome::Error((
+                Status::UnprocessableEntity,
+                CsrfFormError::Form(errors.to_string()),
+            )),
+        }
+    }
+}
+
+/// For forms that post nothing but the `csrf_token` field, e.g. a logout button.
+pub struct CsrfPost;
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for CsrfPost {
+    type Error = CsrfFormError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> DataOutcome<'r, Self> {
+        read_form(req, data).await.map(|_| Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd"));
+        assert!(!constant_time_eq("abc", "abcd"));
+    }
+}
@@ -1,2 +0,0 @@
-INSERT INTO users (username, password)
-VALUES (:username, :password)
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT id, username
+FROM users
+ORDER BY id;
\ No newline at end of file
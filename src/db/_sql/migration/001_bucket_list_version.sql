@@ -0,0 +1,2 @@
+ALTER TABLE bucket_list
+    ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,10 @@
+ALTER TABLE bucket_list
+    ADD COLUMN completed_at TEXT;
+
+CREATE TABLE bucket_list_tags
+(
+    bucket_list_id INTEGER NOT NULL,
+    tag            TEXT    NOT NULL,
+    PRIMARY KEY (bucket_list_id, tag),
+    FOREIGN KEY (bucket_list_id) REFERENCES bucket_list (id) ON DELETE CASCADE
+);
@@ -0,0 +1,36 @@
+CREATE VIRTUAL TABLE bucket_list_fts USING fts5
+(
+    name,
+    description,
+    content = 'bucket_list',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER bucket_list_fts_insert
+    AFTER INSERT
+    ON bucket_list
+BEGIN
+    INSERT INTO bucket_list_fts (rowid, name, description)
+    VALUES (new.id, new.name, new.description);
+END;
+
+CREATE TRIGGER bucket_list_fts_delete
+    AFTER DELETE
+    ON bucket_list
+BEGIN
+    INSERT INTO bucket_list_fts (bucket_list_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+END;
+
+CREATE TRIGGER bucket_list_fts_update
+    AFTER UPDATE OF name, description
+    ON bucket_list
+BEGIN
+    INSERT INTO bucket_list_fts (bucket_list_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+    INSERT INTO bucket_list_fts (rowid, name, description)
+    VALUES (new.id, new.name, new.description);
+END;
+
+INSERT INTO bucket_list_fts (bucket_list_fts)
+VALUES ('rebuild');
@@ -0,0 +1,10 @@
+ALTER TABLE users
+    ADD COLUMN email TEXT;
+
+CREATE TABLE user_password_reset_tokens
+(
+    user_id      INTEGER     NOT NULL,
+    token_hash   TEXT UNIQUE NOT NULL,
+    expire_after TEXT        NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,21 @@
+CREATE TABLE user_login_tokens_new
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id      INTEGER                           NOT NULL,
+    token        TEXT UNIQUE                       NOT NULL,
+    expire_after TEXT                              NOT NULL,
+    created_at   TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen    TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    user_agent   TEXT                              NOT NULL DEFAULT '',
+    ip           TEXT                              NOT NULL DEFAULT '',
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+INSERT INTO user_login_tokens_new (user_id, token, expire_after)
+SELECT user_id, token, expire_after
+FROM user_login_tokens;
+
+DROP TABLE user_login_tokens;
+
+ALTER TABLE user_login_tokens_new
+    RENAME TO user_login_tokens;
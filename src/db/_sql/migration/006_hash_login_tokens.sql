@@ -0,0 +1,9 @@
+-- Raw tokens cannot be turned into keyed hashes, so every session and reset link is invalidated.
+DELETE
+FROM user_login_tokens;
+
+DELETE
+FROM user_password_reset_tokens;
+
+ALTER TABLE user_login_tokens
+    RENAME COLUMN token TO token_hash;
@@ -0,0 +1,16 @@
+CREATE TABLE login_attempts
+(
+    key          TEXT PRIMARY KEY NOT NULL,
+    failures     INTEGER          NOT NULL,
+    last_failure TEXT             NOT NULL,
+    locked_until TEXT
+);
+
+CREATE TABLE login_lockouts
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    key          TEXT                              NOT NULL,
+    failures     INTEGER                           NOT NULL,
+    locked_at    TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until TEXT                              NOT NULL
+);
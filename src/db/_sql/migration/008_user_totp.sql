@@ -0,0 +1,24 @@
+ALTER TABLE users
+    ADD COLUMN totp_secret TEXT;
+
+ALTER TABLE users
+    ADD COLUMN totp_pending_secret TEXT;
+
+ALTER TABLE users
+    ADD COLUMN totp_last_step INTEGER;
+
+CREATE TABLE user_recovery_codes
+(
+    user_id   INTEGER NOT NULL,
+    code_hash TEXT    NOT NULL,
+    UNIQUE (user_id, code_hash),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_login_challenges
+(
+    user_id      INTEGER     NOT NULL,
+    token_hash   TEXT UNIQUE NOT NULL,
+    expire_after TEXT        NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+CREATE TABLE user_api_tokens
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id      INTEGER                           NOT NULL,
+    name         TEXT                              NOT NULL,
+    token_hash   TEXT UNIQUE                       NOT NULL,
+    scopes       TEXT                              NOT NULL,
+    expire_after TEXT,
+    created_at   TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used    TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,21 @@
+CREATE TABLE user_oidc_logins
+(
+    state_hash    TEXT UNIQUE NOT NULL,
+    nonce         TEXT        NOT NULL,
+    code_verifier TEXT        NOT NULL,
+    link_user_id  INTEGER,
+    expire_after  TEXT        NOT NULL,
+    FOREIGN KEY (link_user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_external_identities
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id    INTEGER                           NOT NULL,
+    issuer     TEXT                              NOT NULL,
+    subject    TEXT                              NOT NULL,
+    email      TEXT,
+    created_at TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (issuer, subject),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,24 @@
+ALTER TABLE users
+    ADD COLUMN is_admin INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE users
+    ADD COLUMN disabled_at TEXT;
+
+ALTER TABLE users
+    ADD COLUMN must_reset_password INTEGER NOT NULL DEFAULT 0;
+
+UPDATE users
+SET is_admin = 1
+WHERE username = 'default';
+
+CREATE TABLE admin_audit_log
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    admin_id        INTEGER                           NOT NULL,
+    admin_username  TEXT                              NOT NULL,
+    action          TEXT                              NOT NULL,
+    target_user_id  INTEGER                           NOT NULL,
+    target_username TEXT                              NOT NULL,
+    detail          TEXT                              NOT NULL DEFAULT '',
+    created_at      TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,9 @@
+ALTER TABLE users
+    ADD COLUMN email_verified_at TEXT;
+
+-- Addresses from before verification existed were entered by hand and are trusted.
+UPDATE users
+SET email_verified_at = CURRENT_TIMESTAMP
+WHERE email IS NOT NULL;
+
+CREATE UNIQUE INDEX users_email_unique ON users (email COLLATE NOCASE);
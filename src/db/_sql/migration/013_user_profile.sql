@@ -0,0 +1,12 @@
+CREATE TABLE user_profiles
+(
+    user_id             INTEGER PRIMARY KEY NOT NULL,
+    display_name        TEXT,
+    time_zone           TEXT                NOT NULL DEFAULT 'UTC',
+    locale              TEXT                NOT NULL DEFAULT 'en-US',
+    theme               TEXT                NOT NULL DEFAULT 'system',
+    avatar              BLOB,
+    avatar_content_type TEXT,
+    avatar_updated_at   TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
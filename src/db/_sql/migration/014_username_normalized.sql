@@ -0,0 +1,3 @@
+-- Filled from Rust once SQL is done with the table, see `fill_username_normalized`.
+ALTER TABLE users
+    ADD COLUMN username_normalized TEXT;
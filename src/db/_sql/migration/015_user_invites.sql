@@ -0,0 +1,12 @@
+CREATE TABLE user_invites
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    code_hash    TEXT UNIQUE                       NOT NULL,
+    note         TEXT                              NOT NULL DEFAULT '',
+    max_uses     INTEGER                           NOT NULL,
+    uses         INTEGER                           NOT NULL DEFAULT 0,
+    expire_after TEXT,
+    created_by   INTEGER,
+    created_at   TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,29 @@
+CREATE TABLE auth_events
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    event      TEXT                              NOT NULL,
+    outcome    TEXT                              NOT NULL,
+    -- No foreign key, the history outlives deleted users like the admin audit log does.
+    user_id    INTEGER,
+    username   TEXT                              NOT NULL DEFAULT '',
+    ip         TEXT                              NOT NULL DEFAULT '',
+    user_agent TEXT                              NOT NULL DEFAULT '',
+    detail     TEXT                              NOT NULL DEFAULT '',
+    created_at TEXT                              NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX auth_events_user_id ON auth_events (user_id, id);
+
+CREATE TRIGGER auth_events_no_update
+    BEFORE UPDATE
+    ON auth_events
+BEGIN
+    SELECT RAISE(ABORT, 'auth_events is append-only');
+END;
+
+CREATE TRIGGER auth_events_no_delete
+    BEFORE DELETE
+    ON auth_events
+BEGIN
+    SELECT RAISE(ABORT, 'auth_events is append-only');
+END;
@@ -0,0 +1,42 @@
+-- SQLite can not make a column NOT NULL in place, so the table is rebuilt. Foreign keys are off
+-- while migrating, the other tables point at the new table once it has the old name.
+-- The keys are filled from Rust right after, the unique index follows in the next migration.
+CREATE TABLE users_new
+(
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    username            TEXT UNIQUE                       NOT NULL,
+    username_normalized TEXT                              NOT NULL,
+    password            BLOB                              NOT NULL,
+    email               TEXT,
+    totp_secret         TEXT,
+    totp_pending_secret TEXT,
+    totp_last_step      INTEGER,
+    is_admin            INTEGER                           NOT NULL DEFAULT 0,
+    disabled_at         TEXT,
+    must_reset_password INTEGER                           NOT NULL DEFAULT 0,
+    email_verified_at   TEXT
+);
+
+INSERT INTO users_new (id, username, username_normalized, password, email, totp_secret,
+                       totp_pending_secret, totp_last_step, is_admin, disabled_at,
+                       must_reset_password, email_verified_at)
+SELECT id,
+       username,
+       COALESCE(username_normalized, lower(username)),
+       password,
+       email,
+       totp_secret,
+       totp_pending_secret,
+       totp_last_step,
+       is_admin,
+       disabled_at,
+       must_reset_password,
+       email_verified_at
+FROM users;
+
+DROP TABLE users;
+
+ALTER TABLE users_new
+    RENAME TO users;
+
+CREATE UNIQUE INDEX users_email_unique ON users (email COLLATE NOCASE);
@@ -0,0 +1 @@
+CREATE UNIQUE INDEX users_username_normalized_unique ON users (username_normalized);
@@ -0,0 +1,6 @@
+-- Databases from before the setup page were seeded with `default`/`banana` as an admin.
+-- Its login is refused until the password is reset, startup points operators at `create-admin`.
+UPDATE users
+SET must_reset_password = 1
+WHERE username = 'default'
+  AND is_admin = 1;
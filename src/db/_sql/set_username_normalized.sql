@@ -0,0 +1,3 @@
+UPDATE users
+SET username_normalized = :username_normalized
+WHERE id = :id;
\ No newline at end of file
@@ -1,8 +1,9 @@
 use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
 use crate::error::{ExtraResultExt, FromIntoStackError};
-use crate::user::password::Password;
+use crate::user::validate::username::username_key;
 use error_stack::{Report, ResultExt};
-use rusqlite::{Connection, named_params};
+use rusqlite::{Connection, Transaction, named_params};
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
@@ -22,10 +23,122 @@ pub enum SqliteClientError {
     Connection,
     #[error("Init failed")]
     InitFailed,
+    #[error("Migration failed")]
+    MigrationFailed,
 }
 
 impl FromIntoStackError for SqliteClientError {}
 
+const MIGRATIONS: [&str; 19] = [
+    include_str!("_sql/migration/001_bucket_list_version.sql"),
+    include_str!("_sql/migration/002_bucket_list_completion_and_tags.sql"),
+    include_str!("_sql/migration/003_bucket_list_fts.sql"),
+    include_str!("_sql/migration/004_user_email_and_password_reset.sql"),
+    include_str!("_sql/migration/005_user_login_token_sessions.sql"),
+    include_str!("_sql/migration/006_hash_login_tokens.sql"),
+    include_str!("_sql/migration/007_login_throttle.sql"),
+    include_str!("_sql/migration/008_user_totp.sql"),
+    include_str!("_sql/migration/009_user_api_tokens.sql"),
+    include_str!("_sql/migration/010_user_oidc.sql"),
+    include_str!("_sql/migration/011_user_admin.sql"),
+    include_str!("_sql/migration/012_user_email_verification.sql"),
+    include_str!("_sql/migration/013_user_profile.sql"),
+    include_str!("_sql/migration/014_username_normalized.sql"),
+    include_str!("_sql/migration/015_user_invites.sql"),
+    include_str!("_sql/migration/016_auth_events.sql"),
+    include_str!("_sql/migration/017_username_normalized_not_null.sql"),
+    include_str!("_sql/migration/018_username_normalized_unique.sql"),
+    include_str!("_sql/migration/019_lock_default_admin.sql"),
+];
+
+/// Steps SQL can not do, run after the migration numbered `version` in its transaction.
+fn post_migration(version: usize, tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    match version {
+        17 => fill_username_normalized(tx),
+        _ => Ok(()),
+    }
+}
+
+/// SQLite's `lower()` only folds ASCII, so the keys are computed with `username_key`.
+/// Names that end up with the same key keep working through the exact username match, all but
+/// the oldest get their id appended to stay out of the unique index.
+fn fill_username_normalized(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    let users = tx
+        .prepare(include_str!("_sql/get_usernames.sql"))?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>("id")?, row.get::<_, String>("username")?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut taken = HashSet::new();
+    for (id, username) in users {
+        let key = username_key(&username);
+        let key = if taken.insert(key.clone()) {
+            key
+        } else {
+            format!("{key}#{id}")
+        };
+        tx.execute(
+            include_str!("_sql/set_username_normalized.sql"),
+            named_params! {
+                ":id": id,
+                ":username_normalized": key,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), Report<SqliteClientError>> {
+    let user_version: usize = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .change_context(SqliteClientError::MigrationFailed)
+        .attach_critical("Failed to read schema version".to_string())?;
+
+    if user_version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    // Rebuilding a table drops it, which would cascade to the rows pointing at it. The pragma
+    // has no effect inside a transaction, each migration checks the keys before its commit.
+    conn.pragma_update(None, "foreign_keys", false)
+        .change_context(SqliteClientError::MigrationFailed)
+        .attach_critical("Failed to disable foreign keys".to_string())?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(user_version) {
+        let tx = conn
+            .transaction()
+            .change_context(SqliteClientError::MigrationFailed)
+            .attach_critical("Failed to start migration".to_string())?;
+        tx.execute_batch(migration)
+            .and_then(|_| post_migration(index + 1, &tx))
+            .change_context(SqliteClientError::MigrationFailed)
+            .attach_critical_lazy(|| format!("Migration {} failed", index + 1))?;
+        let foreign_key_violations = tx
+            .prepare("PRAGMA foreign_key_check")
+            .and_then(|mut stmt| stmt.query([])?.next().map(|row| row.is_some()))
+            .change_context(SqliteClientError::MigrationFailed)
+            .attach_critical("Failed to check foreign keys".to_string())?;
+        if foreign_key_violations {
+            return Err(SqliteClientError::MigrationFailed
+                .into_stack_error_critical(format!("Migration {} broke foreign keys", index + 1)));
+        }
+        tx.pragma_update(None, "user_version", index + 1)
+            .change_context(SqliteClientError::MigrationFailed)
+            .attach_critical("Failed to update schema version".to_string())?;
+        tx.commit()
+            .change_context(SqliteClientError::MigrationFailed)
+            .attach_critical("Failed to commit migration".to_string())?;
+    }
+
+    conn.pragma_update(None, "foreign_keys", true)
+        .change_context(SqliteClientError::MigrationFailed)
+        .attach_critical("Failed to enable foreign keys".to_string())?;
+
+    Ok(())
+}
+
 pub struct SqliteClient<T = DefaultConnection>(Arc<Mutex<Connection>>, PhantomData<T>)
 where
     T: ConnectionMarker;
@@ -38,32 +151,21 @@ impl<T: ConnectionMarker> SqliteClient<T> {
         }
         let file_exist = std::fs::metadata(&sqlite_path).is_ok();
 
-        let conn = Connection::open(sqlite_path)
+        let mut conn = Connection::open(sqlite_path)
             .change_context(SqliteClientError::Connection)
             .attach_critical("Sqlite Connection failed".to_string())?;
+        // Per connection, `init.sql` only covered the very first run.
+        conn.pragma_update(None, "foreign_keys", true)
+            .change_context(SqliteClientError::Connection)
+            .attach_critical("Failed to enable foreign keys".to_string())?;
         if !file_exist {
             conn.execute_batch(include_str!("_sql/init.sql"))
                 .change_context(SqliteClientError::InitFailed)
                 .attach_critical("Init failed".to_string())?;
-
-            let password = Password::hash_password("banana".to_string())
-                .change_context(SqliteClientError::InitFailed)
-                .attach_critical("Failed to hash password".to_string())?
-                .encode_to_msg_pack()
-                .change_context(SqliteClientError::InitFailed)
-                .attach_critical("Failed to encode password".to_string())?;
-
-            conn.execute(
-                include_str!("_sql/add_user.sql",),
-                named_params! {
-                    ":username": "default",
-                    ":password": password.to_vec(),
-                },
-            )
-            .change_context(SqliteClientError::InitFailed)
-            .attach_critical("Failed to create default user".to_string())?;
         }
 
+        migrate(&mut conn)?;
+
         Ok(SqliteClient(Arc::new(Mutex::new(conn)), PhantomData))
     }
 
@@ -100,3 +202,79 @@ impl FromGlobalContext for SqliteClient {
         Ok(sqlite_client?.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database as it was before the usernames were normalized from Rust.
+    fn connection_at_016() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("_sql/init.sql")).unwrap();
+        for migration in &MIGRATIONS[..16] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 16).unwrap();
+        conn
+    }
+
+    #[test]
+    fn usernames_are_normalized_from_rust() {
+        let mut conn = connection_at_016();
+        conn.execute_batch(
+            "INSERT INTO users (username, password) VALUES ('alice', x''), ('ALICE', x''), ('Ｂob', x'');
+             INSERT INTO user_profiles (user_id) VALUES (3);",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+
+        let keys = conn
+            .prepare("SELECT username_normalized FROM users ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(keys, ["alice", "alice#2", "bob"]);
+
+        let profiles: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM user_profiles WHERE user_id = 3",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(profiles, 1);
+        let foreign_keys: bool = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+    }
+
+    #[test]
+    fn username_normalized_is_required_and_unique() {
+        let mut conn = connection_at_016();
+        migrate(&mut conn).unwrap();
+
+        assert!(
+            conn.execute(
+                "INSERT INTO users (username, password) VALUES ('carol', x'')",
+                []
+            )
+            .is_err()
+        );
+        conn.execute(
+            "INSERT INTO users (username, username_normalized, password) VALUES ('carol', 'carol', x'')",
+            [],
+        )
+        .unwrap();
+        assert!(
+            conn.execute(
+                "INSERT INTO users (username, username_normalized, password) VALUES ('Carol', 'carol', x'')",
+                [],
+            )
+            .is_err()
+        );
+    }
+}
@@ -46,23 +46,54 @@ impl GlobalContext {
     }
 }
 
+/// What a personal access token may do, checked against the `API_SCOPE` of the route's flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    Read,
+    Write,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 2] = [ApiScope::Read, ApiScope::Write];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Write => "write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        ApiScope::ALL
+            .into_iter()
+            .find(|api_scope| api_scope.as_str() == scope.trim())
+    }
+}
+
 #[derive(Clone)]
 pub struct DependencyFlagData {
     pub use_forward: bool,
     pub allow_user: bool,
     pub allow_visitor: bool,
+    pub api_scope: Option<ApiScope>,
+    pub require_admin: bool,
 }
 
 pub trait DependencyFlag {
     const USE_FORWARD: bool = false;
     const ALLOW_USER: bool = true;
     const ALLOW_VISITOR: bool = true;
+    /// Scope a personal access token needs, routes without one do not accept tokens.
+    const API_SCOPE: Option<ApiScope> = None;
+    const REQUIRE_ADMIN: bool = false;
 
     fn build_flag_data() -> DependencyFlagData {
         DependencyFlagData {
             use_forward: Self::USE_FORWARD,
             allow_user: Self::ALLOW_USER,
             allow_visitor: Self::ALLOW_VISITOR,
+            api_scope: Self::API_SCOPE,
+            require_admin: Self::REQUIRE_ADMIN,
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::csrf::{CsrfError, CsrfRejection};
 use crate::html_base::HtmlBuilder;
 use error_stack::{Context, Report, ResultExt};
 use maud::{PreEscaped, html};
@@ -202,3 +203,32 @@ where
         }
     }
 }
+
+#[derive(Error, Debug)]
+pub enum ForbiddenError {
+    #[error("You are not allowed to do this.")]
+    Forbidden,
+    #[error(transparent)]
+    Csrf(#[from] CsrfError),
+}
+
+impl ErrorStatus for ForbiddenError {
+    fn error_status(&self) -> Status {
+        Status::Forbidden
+    }
+}
+
+#[catch(403)]
+pub fn forbidden_catcher(request: &Request<'_>) -> ErrorReportResponse<ForbiddenError> {
+    let error = match request.local_cache(|| CsrfRejection(None)).0 {
+        Some(csrf_error) => ForbiddenError::Csrf(csrf_error),
+        None => ForbiddenError::Forbidden,
+    };
+    let output = if request.content_type().is_some_and(|c| c.is_json()) {
+        ErrorOutput::Json
+    } else {
+        ErrorOutput::Html
+    };
+
+    ErrorReportResponse(Report::new(error).attach(output))
+}
@@ -1,6 +1,8 @@
+use crate::csrf::{CsrfToken, FIELD_NAME};
 use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
 use crate::user::dependency::{DependencyUserContext, FromUserContext};
 use crate::user::model::UserContext;
+use crate::user::profile::{Locale, Theme};
 use error_stack::Report;
 use maud::{DOCTYPE, Markup, PreEscaped, html};
 use rocket::request::FlashMessage;
@@ -17,10 +19,25 @@ fn html_import_map() -> Markup {
     }
 }
 
-fn html_doc(title: &str, content: Markup, head: Markup, footer: Markup) -> Markup {
+/// Language and theme of the document, pages for visitors use the defaults.
+#[derive(Default)]
+struct DocumentPreferences {
+    locale: Locale,
+    theme: Theme,
+}
+
+fn html_doc(
+    title: &str,
+    content: Markup,
+    head: Markup,
+    footer: Markup,
+    preferences: &DocumentPreferences,
+) -> Markup {
+    // Without `data-theme` the browser's color scheme decides.
+    let theme = (preferences.theme != Theme::System).then_some(preferences.theme.as_str());
     html! {
         (DOCTYPE)
-        html {
+        html lang=(preferences.locale.as_str()) data-theme=[theme] {
             head {
                 meta charset="utf-8";
                 meta name="viewport" content="width=device-width, initial-scale=1";
@@ -42,6 +59,7 @@ pub struct HtmlBuilder {
     content: Markup,
     head: Option<Markup>,
     footer: Option<Markup>,
+    preferences: DocumentPreferences,
 }
 
 impl HtmlBuilder {
@@ -51,9 +69,15 @@ impl HtmlBuilder {
             content,
             head: None,
             footer: None,
+            preferences: DocumentPreferences::default(),
         }
     }
 
+    pub fn attach_preferences(mut self, locale: Locale, theme: Theme) -> Self {
+        self.preferences = DocumentPreferences { locale, theme };
+        self
+    }
+
     #[allow(dead_code)]
     pub fn attach_head(mut self, head: Markup) -> Self {
         self.head = Some(head);
@@ -71,6 +95,7 @@ impl HtmlBuilder {
             self.content,
             self.head.unwrap_or(html! {}),
             self.footer.unwrap_or(html! {}),
+            &self.preferences,
         )
     }
 }
@@ -94,6 +119,11 @@ impl NavigationItem {
                 url: "/bucket-list/".to_string(),
                 tag: "bucket-list".to_string(),
             },
+            Self {
+                name: "Statistics".to_string(),
+                url: "/bucket-list/stats".to_string(),
+                tag: "bucket-list-stats".to_string(),
+            },
             Self {
                 name: "User".to_string(),
                 url: "/user/".to_string(),
@@ -114,14 +144,16 @@ pub struct HtmlCell {
 
 pub struct ContextHtmlBuilder {
     flash_message: Option<(String, String)>,
+    csrf_token: String,
     user_context: Option<Arc<UserContext>>,
     data: RefCell<HtmlCell>,
 }
 
 impl ContextHtmlBuilder {
-    pub fn new(flash_message: Option<(String, String)>) -> Self {
+    pub fn new(flash_message: Option<(String, String)>, csrf_token: String) -> Self {
         Self {
             flash_message,
+            csrf_token,
             user_context: None,
             data: RefCell::new(HtmlCell {
                 title: None,
@@ -158,12 +190,23 @@ impl ContextHtmlBuilder {
         self
     }
 
+    /// Hidden `csrf_token` input, every post form renders one as its first child. A test in
+    /// `main.rs` renders each page and fails on a post form without it.
+    pub fn csrf_input(&self) -> Markup {
+        html! {
+            input type="hidden" name=(FIELD_NAME) value=(self.csrf_token);
+        }
+    }
+
     pub fn build(&self) -> Markup {
         let parse_flash = self.parse_flash();
         let data = self.data.borrow();
         let title = data.title.clone().unwrap_or_else(|| "Untitled".to_string());
         let content = data.content.clone().unwrap_or_else(|| html! {});
-        let head = data.head.clone().unwrap_or_else(|| html! {});
+        let head = html! {
+            meta name="csrf-token" content=(self.csrf_token);
+            (data.head.clone().unwrap_or_else(|| html! {}))
+        };
         let footer = data.footer.clone().unwrap_or_else(|| html! {});
 
         let new_content = html! {
@@ -176,10 +219,15 @@ impl ContextHtmlBuilder {
             }
         };
 
-        HtmlBuilder::new(title, new_content)
+        let html_builder = HtmlBuilder::new(title, new_content)
             .attach_head(head)
-            .attach_footer(footer)
-            .build()
+            .attach_footer(footer);
+        match &self.user_context {
+            Some(user_context) => html_builder
+                .attach_preferences(user_context.profile.locale, user_context.profile.theme),
+            None => html_builder,
+        }
+        .build()
     }
 
     fn parse_flash(&self) -> Markup {
@@ -225,7 +273,7 @@ impl ContextHtmlBuilder {
                 @if let Some(user_context) = user_context {
                     span .nav-user {
                         @if user_context.is_user {
-                            a href="/user/" { "Hello, " (user_context.username) }
+                            a href="/user/" { "Hello, " (user_context.display_name()) }
                         } @else {
                             a href="/user/login" { "You're a visitor, click here to login" }
                         }
@@ -283,7 +331,13 @@ impl FromGlobalContext for ContextHtmlBuilder {
                 None
             };
 
-        Ok(Self::new(flash_message))
+        let csrf_token = request
+            .guard::<CsrfToken>()
+            .await
+            .succeeded()
+            .ok_or(DependencyError::Other("No CSRF token".to_string()))?;
+
+        Ok(Self::new(flash_message, csrf_token.0))
     }
 }
 
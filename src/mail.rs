@@ -0,0 +1,239 @@
+use crate::config::{MailConfig, MailTransportKind, SmtpTls};
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::error::{ExtraResultExt, FromIntoStackError};
+use error_stack::{Report, ResultExt};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+#[derive(Error, Debug)]
+pub enum MailerError {
+    #[error("Config error")]
+    ConfigError,
+    #[error("Invalid address")]
+    InvalidAddress,
+    #[error("Message error")]
+    MessageError,
+    #[error("Transport error")]
+    TransportError,
+}
+
+impl FromIntoStackError for MailerError {}
+
+#[derive(Clone)]
+enum MailTransport {
+    File(AsyncFileTransport<Tokio1Executor>),
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: MailTransport,
+}
+
+impl Mailer {
+    pub fn new(config: &MailConfig) -> Result<Self, Report<MailerError>> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .change_context(MailerError::ConfigError)
+            .attach_critical("Mail from address is invalid".to_string())?;
+
+        let transport = match config.transport {
+            MailTransportKind::File => {
+                std::fs::create_dir_all(&config.file_path)
+                    .change_context(MailerError::ConfigError)
+                    .attach_critical("Failed to create mail directory".to_string())?;
+                MailTransport::File(AsyncFileTransport::new(&config.file_path))
+            }
+            MailTransportKind::Smtp => {
+                let builder = match config.smtp_tls {
+                    SmtpTls::None => {
+                        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+                    }
+                    SmtpTls::StartTls => {
+                        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+                            .change_context(MailerError::ConfigError)
+                            .attach_critical("Invalid SMTP host".to_string())?
+                    }
+                    SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)
+                        .change_context(MailerError::ConfigError)
+                        .attach_critical("Invalid SMTP host".to_string())?,
+                }
+                .port(config.smtp_port);
+
+                let builder = if config.smtp_username.is_empty() {
+                    builder
+                } else {
+                    builder.credentials(Credentials::new(
+                        config.smtp_username.clone(),
+                        config.smtp_password.clone(),
+                    ))
+                };
+
+                MailTransport::Smtp(builder.build())
+            }
+        };
+
+        Ok(Self { from, transport })
+    }
+
+    pub async fn send(
+        &self,
+        to: &str,
+        subject: &str,
+        body: String,
+    ) -> Result<(), Report<MailerError>> {
+        let to = to
+            .parse::<Mailbox>()
+            .change_context(MailerError::InvalidAddress)?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .body(body)
+            .change_context(MailerError::MessageError)?;
+
+        match &self.transport {
+            MailTransport::File(transport) => transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .change_context(MailerError::TransportError),
+            MailTransport::Smtp(transport) => transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .change_context(MailerError::TransportError),
+        }
+    }
+}
+
+static MAILER: OnceCell<Mailer> = OnceCell::const_new();
+
+impl FromGlobalContext for Mailer {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let mailer: Result<&Self, Report<DependencyError>> = MAILER
+            .get_or_try_init(|| async {
+                Self::new(&dependency_global_context.global_context.config.mail)
+                    .change_context(DependencyError::Other("Could not start mailer".to_string()))
+            })
+            .await;
+
+        Ok(mailer?.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Just enough SMTP to accept one message, returns the DATA section.
+    async fn fake_smtp_server(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"220 localhost\r\n").await.unwrap();
+
+        let mut data = String::new();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                return data;
+            }
+            if in_data {
+                if line == ".\r\n" {
+                    writer.write_all(b"250 OK\r\n").await.unwrap();
+                    return data;
+                }
+                data.push_str(&line);
+                continue;
+            }
+            let reply: &[u8] = match line.get(..4).unwrap_or("").to_uppercase().as_str() {
+                "EHLO" | "HELO" => b"250 localhost\r\n",
+                "DATA" => {
+                    in_data = true;
+                    b"354 Start mail input\r\n"
+                }
+                "QUIT" => b"221 Bye\r\n",
+                _ => b"250 OK\r\n",
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn send_over_smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(fake_smtp_server(listener));
+
+        let mailer = Mailer::new(&MailConfig {
+            transport: MailTransportKind::Smtp,
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: port,
+            smtp_tls: SmtpTls::None,
+            ..MailConfig::default()
+        })
+        .unwrap();
+
+        mailer
+            .send(
+                "user@example.com",
+                "Hello",
+                "Hello from the test".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let data = server.await.unwrap();
+        assert!(data.contains("Subject: Hello"));
+        assert!(data.contains("Hello from the test"));
+    }
+
+    #[tokio::test]
+    async fn send_to_file() {
+        let file_path = std::env::temp_dir().join(format!("mail-{}", uuid::Uuid::new_v4()));
+        let mailer = Mailer::new(&MailConfig {
+            transport: MailTransportKind::File,
+            file_path: file_path.to_string_lossy().to_string(),
+            ..MailConfig::default()
+        })
+        .unwrap();
+
+        mailer
+            .send(
+                "user@example.com",
+                "Hello",
+                "Hello from the test".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let written = std::fs::read_dir(&file_path).unwrap().count();
+        std::fs::remove_dir_all(&file_path).unwrap();
+        assert_eq!(written, 1);
+    }
+
+    #[tokio::test]
+    async fn send_error_invalid_address() {
+        let mailer = Mailer::new(&MailConfig {
+            transport: MailTransportKind::Smtp,
+            ..MailConfig::default()
+        })
+        .unwrap();
+        let result = mailer
+            .send("not an address", "Hello", "Hello".to_string())
+            .await;
+        assert!(result.is_err());
+    }
+}
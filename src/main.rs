@@ -1,11 +1,18 @@
+pub mod admin;
 pub mod bucket_list;
 pub mod config;
 pub mod content_type;
+pub mod csrf;
 pub mod db;
 pub mod dependency;
 pub mod error;
 pub mod html_base;
 pub mod icon;
+pub mod mail;
+pub mod markdown;
+pub mod oidc;
+pub mod setup;
+pub mod startup;
 pub mod user;
 pub mod utils;
 pub mod validation;
@@ -14,11 +21,16 @@ pub mod validation;
 extern crate rocket;
 extern crate core;
 
+use crate::admin::route::AdminRoute;
 use crate::bucket_list::route::BucketListRoute;
 use crate::config::get_figment_for_rocket;
 use crate::dependency::GlobalContext;
+use crate::error::forbidden_catcher;
 use crate::html_base::ContextHtmlBuilder;
 use crate::icon::plus_icon;
+use crate::setup::cli;
+use crate::setup::route::SetupRoute;
+use crate::startup::StartupCheck;
 use crate::user::dependency::UserDep;
 use crate::user::route::UserRoute;
 use crate::utils::{EmbedEtag, EtagCheck};
@@ -27,6 +39,8 @@ use maud::{Markup, PreEscaped, html};
 use rocket::response::content::RawCss;
 use rocket::serde::json::Value;
 use rocket::serde::json::serde_json::json;
+use rocket::{Build, Rocket};
+use std::process::ExitCode;
 
 #[get("/")]
 async fn root(context_html_builder: UserDep<ContextHtmlBuilder>) -> Markup {
@@ -87,11 +101,178 @@ async fn main_css(_etag: EtagCheck) -> EmbedEtag<RawCss<Box<[u8]>>> {
     EmbedEtag::new(RawCss(css.into()))
 }
 
-#[launch]
-async fn rocket() -> _ {
-    rocket::custom(get_figment_for_rocket())
-        .attach(GlobalContext::adhoc())
+fn rocket() -> Rocket<Build> {
+    mount(rocket::custom(get_figment_for_rocket()).attach(GlobalContext::adhoc()))
+}
+
+/// Everything but the config, tests manage their own `GlobalContext`.
+fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket
+        .attach(StartupCheck::adhoc())
         .mount("/", routes![root, js_array, favicon, main_css])
+        .register("/", catchers![forbidden_catcher])
         .attach(BucketListRoute::adhoc())
         .attach(UserRoute::adhoc())
+        .attach(AdminRoute::adhoc())
+        .attach(SetupRoute::adhoc())
+}
+
+/// Without arguments the server starts, otherwise it is a subcommand.
+#[rocket::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args).await;
+    }
+
+    match rocket().launch().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, SessionConfig};
+    use crate::db::SqliteClient;
+    use crate::user::password::{Password, PasswordHashParams};
+    use crate::user::repository::UserRepository;
+    use crate::user::token::generate_token;
+    use rocket::http::{ContentType, Cookie, Method};
+    use rocket::local::asynchronous::Client;
+    use std::sync::Arc;
+
+    /// Start of each post form in `html` that lacks the `csrf_token` field.
+    fn forms_without_csrf(html: &str) -> Vec<String> {
+        html.split("<form")
+            .skip(1)
+            .map(|form| form.split("</form>").next().unwrap_or_default())
+            .filter(|form| {
+                form.split('>')
+                    .next()
+                    .is_some_and(|tag| tag.contains("method=\"post\""))
+            })
+            .filter(|form| !form.contains("name=\"csrf_token\""))
+            .map(|form| format!("<form{}", form.chars().take(80).collect::<String>()))
+            .collect()
+    }
+
+    /// Renders every page without query, path parameters are `1`.
+    async fn pages_without_csrf(client: &Client, login_token: Option<&str>) -> Vec<String> {
+        let paths: Vec<String> = client
+            .rocket()
+            .routes()
+            .filter(|route| route.method == Method::Get)
+            .map(|route| {
+                route
+                    .uri
+                    .path()
+                    .split('/')
+                    .map(|segment| {
+                        if segment.starts_with('<') {
+                            "1"
+                        } else {
+                            segment
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+
+        let mut missing = vec![];
+        for path in paths {
+            let mut request = client.get(path.clone());
+            if let Some(login_token) = login_token {
+                request = request.cookie(Cookie::new("login-token", login_token.to_string()));
+            }
+            let response = request.dispatch().await;
+            if response.content_type() != Some(ContentType::HTML) {
+                continue;
+            }
+            let html = response.into_string().await.unwrap_or_default();
+            missing.extend(
+                forms_without_csrf(&html)
+                    .into_iter()
+                    .map(|form| format!("{path}: {form}")),
+            );
+        }
+        missing
+    }
+
+    #[test]
+    fn test_forms_without_csrf() {
+        let html = "<form method=\"get\"></form>\
+            <form method=\"post\"><input type=\"hidden\" name=\"csrf_token\" value=\"a\"></form>\
+            <form class=\"x\" method=\"post\"><input name=\"q\"></form>";
+        assert_eq!(
+            forms_without_csrf(html),
+            ["<form class=\"x\" method=\"post\"><input name=\"q\">"]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn every_post_form_has_csrf_token() {
+        let config = Config {
+            sqlite_path: ":memory:".to_string(),
+            token_secret: "test".to_string(),
+            session: SessionConfig {
+                private: false,
+                ..SessionConfig::default()
+            },
+            ..Config::default()
+        };
+        let global_context = GlobalContext {
+            config: Arc::new(config),
+        };
+        let sqlite_client = global_context.inject::<SqliteClient>().await.unwrap();
+        let user_repository = global_context.inject::<UserRepository>().await.unwrap();
+        let client = Client::tracked(mount(
+            rocket::custom(get_figment_for_rocket()).manage(global_context),
+        ))
+        .await
+        .unwrap();
+
+        // The setup page only renders before the first user.
+        assert_eq!(
+            pages_without_csrf(&client, None).await,
+            Vec::<String>::new()
+        );
+
+        let password = Password::hash_password("test".to_string(), &PasswordHashParams::default())
+            .unwrap()
+            .encode_to_msg_pack()
+            .unwrap();
+        let user_id = user_repository
+            .add_admin("admin".to_string(), password, true)
+            .unwrap();
+        let login_token = generate_token();
+        user_repository
+            .add_token(
+                login_token.clone(),
+                user_id,
+                String::new(),
+                String::new(),
+                1,
+            )
+            .unwrap();
+        sqlite_client
+            .get_conn()
+            .lock()
+            .unwrap()
+            .execute(
+                include_str!("bucket_list/_sql/add_to_bucket_list.sql"),
+                ["Test", ""],
+            )
+            .unwrap();
+
+        assert_eq!(
+            pages_without_csrf(&client, Some(&login_token)).await,
+            Vec::<String>::new()
+        );
+    }
 }
@@ -0,0 +1,57 @@
+use ammonia::Builder;
+use pulldown_cmark::{Options, Parser, html};
+use std::collections::HashSet;
+
+const ALLOWED_URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+pub fn render_markdown(source: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(source, options));
+
+    Builder::default()
+        .url_schemes(HashSet::from(ALLOWED_URL_SCHEMES))
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        let html = render_markdown("Hello **World**");
+        assert_eq!(html.trim(), "<p>Hello <strong>World</strong></p>");
+    }
+
+    #[test]
+    fn test_render_markdown_strip_script() {
+        let html = render_markdown("Hello <script>alert('x')</script>");
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("alert"));
+    }
+
+    #[test]
+    fn test_render_markdown_strip_event_handler() {
+        let html = render_markdown("<img src=\"https://example.com/a.png\" onerror=\"alert(1)\">");
+        assert!(!html.contains("onerror"));
+    }
+
+    #[test]
+    fn test_render_markdown_allowed_link() {
+        let html = render_markdown("[Japan](https://example.com/japan)");
+        assert!(html.contains("href=\"https://example.com/japan\""));
+        assert!(html.contains("rel=\"noopener noreferrer nofollow\""));
+    }
+
+    #[test]
+    fn test_render_markdown_disallowed_link() {
+        let html = render_markdown("[Click](javascript:alert(1))");
+        assert!(!html.contains("javascript:"));
+    }
+}
@@ -0,0 +1,936 @@
+use crate::config::OidcConfig;
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::error::{ErrorStatus, FromIntoStackError};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::alphabet::URL_SAFE;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use error_stack::{Report, ResultExt};
+use reqwest::Method;
+use ring::signature::{
+    ECDSA_P256_SHA256_FIXED, RSA_PKCS1_2048_8192_SHA256, RsaPublicKeyComponents, UnparsedPublicKey,
+};
+use rocket::http::Status;
+use rocket::serde::json::serde_json;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{Mutex, OnceCell};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use url::Url;
+
+/// Clock difference tolerated between us and the provider.
+const LEEWAY_SECONDS: i64 = 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_BYTES: usize = 256 * 1024;
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// JWTs must not be padded, but some providers pad the key parameters of their JWKS.
+const BASE64_URL_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("Single sign-on is not enabled")]
+    NotEnabled,
+    #[error("Config error")]
+    ConfigError,
+    #[error("Request to the identity provider failed")]
+    RequestError,
+    #[error("The identity provider sent an invalid response")]
+    InvalidResponse,
+    #[error("The ID token is invalid")]
+    InvalidIdToken,
+}
+
+impl FromIntoStackError for OidcError {}
+
+impl ErrorStatus for OidcError {
+    fn error_status(&self) -> Status {
+        match self {
+            OidcError::NotEnabled => Status::NotFound,
+            OidcError::ConfigError => Status::InternalServerError,
+            OidcError::RequestError | OidcError::InvalidResponse => Status::BadGateway,
+            OidcError::InvalidIdToken => Status::BadRequest,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    aud: Audience,
+    azp: Option<String>,
+    exp: i64,
+    nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Everything the callback needs to finish the login, kept server side until then.
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+fn random_url_safe() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// PKCE `S256` challenge of a verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn decode_segment(segment: &str) -> Result<Vec<u8>, Report<OidcError>> {
+    BASE64_URL_LENIENT
+        .decode(segment)
+        .change_context(OidcError::InvalidIdToken)
+}
+
+fn key_matches(header: &JwtHeader, key: &Jwk) -> bool {
+    let kty = match header.alg.as_str() {
+        "RS256" => "RSA",
+        "ES256" => "EC",
+        _ => return false,
+    };
+    key.kty == kty
+        && key
+            .key_use
+            .as_deref()
+            .is_none_or(|key_use| key_use == "sig")
+        && (header.kid.is_none() || header.kid == key.kid)
+}
+
+fn verify_signature(
+    alg: &str,
+    key: &Jwk,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), Report<OidcError>> {
+    let param = |value: &Option<String>| match value {
+        Some(value) => decode_segment(value),
+        None => Err(Report::new(OidcError::InvalidIdToken).attach_printable("Incomplete JWK")),
+    };
+
+    let verified = match alg {
+        "RS256" => RsaPublicKeyComponents {
+            n: param(&key.n)?,
+            e: param(&key.e)?,
+        }
+        .verify(&RSA_PKCS1_2048_8192_SHA256, message, signature),
+        "ES256" => {
+            if key.crv.as_deref() != Some("P-256") {
+                return Err(
+                    Report::new(OidcError::InvalidIdToken).attach_printable("Unsupported curve")
+                );
+            }
+            let mut point = vec![0x04];
+            point.extend(param(&key.x)?);
+            point.extend(param(&key.y)?);
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point).verify(message, signature)
+        }
+        _ => {
+            return Err(Report::new(OidcError::InvalidIdToken)
+                .attach_printable(format!("Unsupported algorithm {}", alg)));
+        }
+    };
+
+    verified.map_err(|_| {
+        Report::new(OidcError::InvalidIdToken).attach_printable("Signature does not match")
+    })
+}
+
+struct Jwt<'a> {
+    header: JwtHeader,
+    /// Signed part, `header.payload`.
+    message: &'a str,
+    payload: &'a str,
+    signature: &'a str,
+}
+
+impl<'a> Jwt<'a> {
+    fn parse(token: &'a str) -> Result<Self, Report<OidcError>> {
+        let not_jwt = || Report::new(OidcError::InvalidIdToken).attach_printable("Not a JWT");
+        let (message, signature) = token.rsplit_once('.').ok_or_else(not_jwt)?;
+        let (header, payload) = message.split_once('.').ok_or_else(not_jwt)?;
+        if payload.contains('.') {
+            return Err(not_jwt());
+        }
+        let header = serde_json::from_slice(&decode_segment(header)?)
+            .change_context(OidcError::InvalidIdToken)?;
+
+        Ok(Self {
+            header,
+            message,
+            payload,
+            signature,
+        })
+    }
+
+    fn find_key<'k>(&self, keys: &'k [Jwk]) -> Option<&'k Jwk> {
+        keys.iter().find(|key| key_matches(&self.header, key))
+    }
+
+    fn verify(&self, key: &Jwk) -> Result<IdTokenClaims, Report<OidcError>> {
+        verify_signature(
+            &self.header.alg,
+            key,
+            self.message.as_bytes(),
+            &decode_segment(self.signature)?,
+        )?;
+        serde_json::from_slice(&decode_segment(self.payload)?)
+            .change_context(OidcError::InvalidIdToken)
+    }
+}
+
+fn validate_claims(
+    claims: &IdTokenClaims,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<(), Report<OidcError>> {
+    let invalid =
+        |reason: &'static str| Err(Report::new(OidcError::InvalidIdToken).attach_printable(reason));
+
+    if claims.iss != issuer {
+        return invalid("Issuer does not match");
+    }
+    let audience_ok = match &claims.aud {
+        Audience::One(aud) => aud == client_id,
+        Audience::Many(auds) => {
+            auds.iter().any(|aud| aud == client_id)
+                && (auds.len() == 1 || claims.azp.as_deref() == Some(client_id))
+        }
+    };
+    if !audience_ok {
+        return invalid("Audience does not match");
+    }
+    if claims.exp + LEEWAY_SECONDS < now {
+        return invalid("Token has expired");
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return invalid("Nonce does not match");
+    }
+    Ok(())
+}
+
+static TLS_CONFIG: LazyLock<Arc<ClientConfig>> = LazyLock::new(|| {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    Arc::new(
+        ClientConfig::builder_with_provider(Arc::new(
+            tokio_rustls::rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth(),
+    )
+});
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .use_preconfigured_tls(ClientConfig::clone(&TLS_CONFIG))
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent("rust_vue_exercise")
+        .build()
+        .expect("the client config is valid")
+});
+
+/// Reads the body chunk by chunk, a response is dropped as soon as it gets too large.
+async fn read_body(mut response: reqwest::Response) -> Result<Vec<u8>, Report<OidcError>> {
+    let too_large =
+        || Report::new(OidcError::InvalidResponse).attach_printable("Response too large");
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_RESPONSE_BYTES as u64)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .change_context(OidcError::RequestError)?
+    {
+        if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Plain http is allowed for local providers.
+async fn fetch_json<T: DeserializeOwned>(
+    method: Method,
+    url: &str,
+    basic_auth: Option<(&str, &str)>,
+    form: Option<String>,
+) -> Result<T, Report<OidcError>> {
+    let url = Url::parse(url)
+        .change_context(OidcError::ConfigError)
+        .attach_printable_lazy(|| url.to_string())?;
+    if !matches!(url.scheme(), "https" | "http") {
+        return Err(Report::new(OidcError::ConfigError)
+            .attach_printable(format!("Unsupported scheme {}", url.scheme())));
+    }
+
+    let mut request = HTTP_CLIENT
+        .request(method, url)
+        .header("Accept", "application/json");
+    if let Some((username, password)) = basic_auth {
+        let encode = |value: &str| -> String {
+            url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+        };
+        request = request.basic_auth(encode(username), Some(encode(password)));
+    }
+    if let Some(form) = form {
+        request = request
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(form);
+    }
+
+    let response = request
+        .send()
+        .await
+        .change_context(OidcError::RequestError)?;
+    let status = response.status();
+    let body = read_body(response).await?;
+    if !status.is_success() {
+        return Err(
+            Report::new(OidcError::RequestError).attach_printable(format!(
+                "{}: {}",
+                status,
+                String::from_utf8_lossy(&body[..body.len().min(512)])
+            )),
+        );
+    }
+
+    serde_json::from_slice(&body).change_context(OidcError::InvalidResponse)
+}
+
+/// Keys of the provider, with the time they were last fetched.
+#[derive(Default)]
+struct JwkCache {
+    keys: Arc<[Jwk]>,
+    fetched_at: Option<Instant>,
+}
+
+/// Relying party for one provider, discovery and keys are fetched on first use and cached.
+#[derive(Clone)]
+pub struct OidcClient {
+    config: Arc<OidcConfig>,
+    redirect_url: String,
+    metadata: Arc<OnceCell<ProviderMetadata>>,
+    keys: Arc<Mutex<JwkCache>>,
+    /// Unknown key ids trigger a refetch at most this often.
+    jwks_refetch_interval: Duration,
+}
+
+impl OidcClient {
+    pub fn new(config: &OidcConfig, public_url: &str) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            redirect_url: format!("{}/user/oidc/callback", public_url.trim_end_matches('/')),
+            metadata: Arc::new(OnceCell::new()),
+            keys: Arc::new(Mutex::new(JwkCache::default())),
+            jwks_refetch_interval: JWKS_REFETCH_INTERVAL,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.issuer_url.is_empty()
+    }
+
+    pub fn button_label(&self) -> &str {
+        &self.config.button_label
+    }
+
+    pub fn auto_register(&self) -> bool {
+        self.config.auto_register
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, Report<OidcError>> {
+        if !self.is_enabled() {
+            return Err(Report::new(OidcError::NotEnabled));
+        }
+
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer_url.trim_end_matches('/');
+                let metadata: ProviderMetadata = fetch_json(
+                    Method::GET,
+                    &format!("{}/.well-known/openid-configuration", issuer),
+                    None,
+                    None,
+                )
+                .await?;
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(
+                        OidcError::InvalidResponse.into_stack_error_critical(format!(
+                            "Provider reports issuer {}, configured is {}",
+                            metadata.issuer, issuer
+                        )),
+                    );
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    pub async fn authorization_request(&self) -> Result<AuthorizationRequest, Report<OidcError>> {
+        let metadata = self.metadata().await?;
+        let state = random_url_safe();
+        let nonce = random_url_safe();
+        let code_verifier = random_url_safe();
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .change_context(OidcError::InvalidResponse)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(AuthorizationRequest {
+            url: url.into(),
+            state,
+            nonce,
+            code_verifier,
+        })
+    }
+
+    /// Redeems the code from the callback and returns the verified claims of its ID token.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, Report<OidcError>> {
+        let metadata = self.metadata().await?;
+        let form = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("code_verifier", code_verifier)
+            .finish();
+        let basic_auth = (!self.config.client_secret.is_empty()).then_some((
+            self.config.client_id.as_str(),
+            self.config.client_secret.as_str(),
+        ));
+
+        let response: TokenResponse = fetch_json(
+            Method::POST,
+            &metadata.token_endpoint,
+            basic_auth,
+            Some(form),
+        )
+        .await?;
+
+        let claims = self.verify(metadata, &response.id_token).await?;
+        validate_claims(
+            &claims,
+            &metadata.issuer,
+            &self.config.client_id,
+            nonce,
+            chrono::Utc::now().timestamp(),
+        )?;
+        Ok(claims)
+    }
+
+    /// Refetches the key set when no cached key fits, providers rotate their keys.
+    /// Tokens with made up key ids can not make us hammer the provider, the refetch is
+    /// throttled and concurrent logins wait for the one in flight.
+    async fn verify(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, Report<OidcError>> {
+        let jwt = Jwt::parse(id_token)?;
+        let mut cache = self.keys.lock().await;
+        if let Some(key) = jwt.find_key(&cache.keys) {
+            return jwt.verify(key);
+        }
+        let no_key = || Report::new(OidcError::InvalidIdToken).attach_printable("No matching key");
+        if cache
+            .fetched_at
+            .is_some_and(|fetched_at| fetched_at.elapsed() < self.jwks_refetch_interval)
+        {
+            return Err(no_key());
+        }
+
+        cache.fetched_at = Some(Instant::now());
+        let jwks: JwkSet = fetch_json(Method::GET, &metadata.jwks_uri, None, None).await?;
+        cache.keys = Arc::from(jwks.keys);
+
+        let key = jwt.find_key(&cache.keys).ok_or_else(no_key)?;
+        jwt.verify(key)
+    }
+}
+
+static OIDC_CLIENT: OnceCell<OidcClient> = OnceCell::const_new();
+
+impl FromGlobalContext for OidcClient {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let config = &dependency_global_context.global_context.config;
+        let client = OIDC_CLIENT
+            .get_or_init(|| async { Self::new(&config.oidc, &config.public_url) })
+            .await;
+
+        Ok(client.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+    use rocket::serde::json::serde_json::json;
+    use std::sync::RwLock;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    const CLIENT_ID: &str = "exercise";
+    const CLIENT_SECRET: &str = "secret";
+
+    struct MockProvider {
+        key_pair: EcdsaKeyPair,
+        kid: String,
+        issuer: String,
+        nonce: String,
+        code_challenge: String,
+        exp: i64,
+    }
+
+    impl MockProvider {
+        fn jwk(&self) -> serde_json::Value {
+            let point = self.key_pair.public_key().as_ref();
+            json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "kid": self.kid,
+                "use": "sig",
+                "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+                "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+            })
+        }
+
+        fn id_token(&self) -> String {
+            let header =
+                URL_SAFE_NO_PAD.encode(json!({"alg": "ES256", "kid": self.kid}).to_string());
+            let payload = URL_SAFE_NO_PAD.encode(
+                json!({
+                    "iss": self.issuer,
+                    "sub": "user-42",
+                    "aud": CLIENT_ID,
+                    "exp": self.exp,
+                    "iat": self.exp - 300,
+                    "nonce": self.nonce,
+                    "email": "jane@example.com",
+                    "preferred_username": "jane",
+                })
+                .to_string(),
+            );
+            let message = format!("{}.{}", header, payload);
+            let signature = self
+                .key_pair
+                .sign(&SystemRandom::new(), message.as_bytes())
+                .unwrap();
+            format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature.as_ref()))
+        }
+
+        /// Checks what a provider checks on the token request: client credentials and PKCE.
+        fn token_response(&self, head: &str, body: &str) -> (&'static str, String) {
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET));
+            if header(head, "authorization") != Some(format!("Basic {}", credentials)) {
+                return (
+                    "401 Unauthorized",
+                    json!({"error": "invalid_client"}).to_string(),
+                );
+            }
+            let verifier = url::form_urlencoded::parse(body.as_bytes())
+                .find(|(key, _)| key == "code_verifier")
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            if code_challenge(&verifier) != self.code_challenge {
+                return (
+                    "400 Bad Request",
+                    json!({"error": "invalid_grant"}).to_string(),
+                );
+            }
+            (
+                "200 OK",
+                json!({"access_token": "at", "token_type": "Bearer", "id_token": self.id_token()})
+                    .to_string(),
+            )
+        }
+    }
+
+    fn header(head: &str, name: &str) -> Option<String> {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    }
+
+    /// Serves discovery, keys and the token endpoint, one request per connection.
+    async fn serve(listener: TcpListener, provider: Arc<RwLock<MockProvider>>) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let provider = Arc::clone(&provider);
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let content_length = header(&head, "content-length")
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0u8; content_length];
+                reader.read_exact(&mut body).await.unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let (status, response) = {
+                    let provider = provider.read().unwrap();
+                    let path = head.split(' ').nth(1).unwrap_or_default();
+                    match path {
+                        "/.well-known/openid-configuration" => (
+                            "200 OK",
+                            json!({
+                                "issuer": provider.issuer,
+                                "authorization_endpoint": format!("{}/authorize", provider.issuer),
+                                "token_endpoint": format!("{}/token", provider.issuer),
+                                "jwks_uri": format!("{}/jwks", provider.issuer),
+                            })
+                            .to_string(),
+                        ),
+                        "/jwks" => ("200 OK", json!({"keys": [provider.jwk()]}).to_string()),
+                        "/token" => provider.token_response(&head, &body),
+                        _ => ("404 Not Found", "{}".to_string()),
+                    }
+                };
+                let mut stream = reader.into_inner();
+                let _ = stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            status,
+                            response.len(),
+                            response
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            });
+        }
+    }
+
+    async fn start_provider() -> (OidcClient, Arc<RwLock<MockProvider>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        let provider = Arc::new(RwLock::new(MockProvider {
+            key_pair,
+            kid: "key-1".to_string(),
+            issuer: issuer.clone(),
+            nonce: String::new(),
+            code_challenge: String::new(),
+            exp: chrono::Utc::now().timestamp() + 300,
+        }));
+        tokio::spawn(serve(listener, Arc::clone(&provider)));
+
+        let config = OidcConfig {
+            issuer_url: issuer,
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            ..OidcConfig::default()
+        };
+        (OidcClient::new(&config, "http://127.0.0.1:8000"), provider)
+    }
+
+    /// Plays the browser: takes nonce and challenge from the authorization url.
+    async fn authorize(
+        client: &OidcClient,
+        provider: &RwLock<MockProvider>,
+    ) -> AuthorizationRequest {
+        let request = client.authorization_request().await.unwrap();
+        let url = Url::parse(&request.url).unwrap();
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+        assert_eq!(param("state"), request.state);
+        assert_eq!(param("code_challenge_method"), "S256");
+        assert_eq!(
+            param("redirect_uri"),
+            "http://127.0.0.1:8000/user/oidc/callback"
+        );
+
+        let mut provider = provider.write().unwrap();
+        provider.nonce = param("nonce");
+        provider.code_challenge = param("code_challenge");
+        request
+    }
+
+    #[tokio::test]
+    async fn test_login_flow() {
+        let (client, provider) = start_provider().await;
+        let request = authorize(&client, &provider).await;
+
+        let claims = client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "user-42");
+        assert_eq!(claims.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(claims.preferred_username.as_deref(), Some("jane"));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_code_verifier() {
+        let (client, provider) = start_provider().await;
+        let request = authorize(&client, &provider).await;
+
+        let err = client
+            .exchange_code("code", "not-the-verifier", &request.nonce)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), OidcError::RequestError));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_nonce() {
+        let (client, provider) = start_provider().await;
+        let request = authorize(&client, &provider).await;
+
+        let err = client
+            .exchange_code("code", &request.code_verifier, "other-nonce")
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), OidcError::InvalidIdToken));
+    }
+
+    #[tokio::test]
+    async fn test_expired_id_token() {
+        let (client, provider) = start_provider().await;
+        let request = authorize(&client, &provider).await;
+        provider.write().unwrap().exp = chrono::Utc::now().timestamp() - 2 * LEEWAY_SECONDS;
+
+        let err = client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), OidcError::InvalidIdToken));
+    }
+
+    fn rotate_key(provider: &RwLock<MockProvider>) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let mut provider = provider.write().unwrap();
+        provider.key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        provider.kid = format!("{}-rotated", provider.kid);
+    }
+
+    #[tokio::test]
+    async fn test_rotated_key() {
+        let (mut client, provider) = start_provider().await;
+        client.jwks_refetch_interval = Duration::ZERO;
+        let request = authorize(&client, &provider).await;
+        client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap();
+
+        rotate_key(&provider);
+
+        let request = authorize(&client, &provider).await;
+        let claims = client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "user-42");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_key_refetch_is_throttled() {
+        let (client, provider) = start_provider().await;
+        let request = authorize(&client, &provider).await;
+        client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap();
+
+        rotate_key(&provider);
+
+        let request = authorize(&client, &provider).await;
+        let err = client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), OidcError::InvalidIdToken));
+    }
+
+    #[tokio::test]
+    async fn test_response_too_large() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n")
+                .await;
+            let chunk = [b' '; 16 * 1024];
+            for _ in 0..MAX_RESPONSE_BYTES / chunk.len() + 1 {
+                if stream.write_all(&chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let err = fetch_json::<serde_json::Value>(Method::GET, &url, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), OidcError::InvalidResponse));
+        assert!(format!("{err:?}").contains("Response too large"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let client = OidcClient::new(&OidcConfig::default(), "http://127.0.0.1:8000");
+        assert!(!client.is_enabled());
+        let err = client.authorization_request().await.err().unwrap();
+        assert!(matches!(err.current_context(), OidcError::NotEnabled));
+    }
+
+    fn claims(aud: Audience, azp: Option<&str>) -> IdTokenClaims {
+        IdTokenClaims {
+            iss: "https://idp.example.com".to_string(),
+            sub: "1".to_string(),
+            aud,
+            azp: azp.map(|azp| azp.to_string()),
+            exp: 1000,
+            nonce: Some("n".to_string()),
+            email: None,
+            email_verified: None,
+            preferred_username: None,
+            name: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_claims() {
+        let issuer = "https://idp.example.com";
+        let ok = claims(Audience::One(CLIENT_ID.to_string()), None);
+        assert!(validate_claims(&ok, issuer, CLIENT_ID, "n", 1000).is_ok());
+        assert!(validate_claims(&ok, issuer, CLIENT_ID, "n", 1000 + LEEWAY_SECONDS + 1).is_err());
+        assert!(validate_claims(&ok, "https://other.example.com", CLIENT_ID, "n", 1000).is_err());
+        assert!(validate_claims(&ok, issuer, "other", "n", 1000).is_err());
+        assert!(validate_claims(&ok, issuer, CLIENT_ID, "m", 1000).is_err());
+
+        let many = vec![CLIENT_ID.to_string(), "other".to_string()];
+        let without_azp = claims(Audience::Many(many.clone()), None);
+        assert!(validate_claims(&without_azp, issuer, CLIENT_ID, "n", 1000).is_err());
+        let with_azp = claims(Audience::Many(many), Some(CLIENT_ID));
+        assert!(validate_claims(&with_azp, issuer, CLIENT_ID, "n", 1000).is_ok());
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // Example from RFC 7636, appendix B.
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::{Config, get_figment_for_other};
+use crate::dependency::GlobalContext;
+use crate::setup::form::SetupAdminForm;
+use crate::setup::service::SetupService;
+use std::io::{BufRead, Write, stderr, stdin};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: rust_vue_exercise [create-admin <username>]
+  create-admin <username>  Add an admin, the password is read from standard input";
+
+/// Runs a subcommand instead of the server, for the first admin or one locked out.
+pub async fn run(args: &[String]) -> ExitCode {
+    match args {
+        [command, username] if command == "create-admin" => create_admin(username).await,
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn create_admin(username: &str) -> ExitCode {
+    let config = match get_figment_for_other().extract::<Arc<Config>>() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to read config: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let global_context = GlobalContext { config };
+    let setup_service = match global_context.inject::<SetupService>().await {
+        Ok(setup_service) => setup_service.without_setup_token(),
+        Err(report) => {
+            eprintln!("{report:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    eprint!("Password: ");
+    let _ = stderr().flush();
+    let mut password = String::new();
+    if stdin().lock().read_line(&mut password).is_err() {
+        eprintln!("Failed to read the password");
+        return ExitCode::FAILURE;
+    }
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+
+    let form = SetupAdminForm {
+        setup_token: String::new(),
+        username: username.to_string(),
+        password: password.clone(),
+        password_confirm: password,
+    };
+    let data = match form
+        .as_validated(&setup_service, setup_service.username_config())
+        .await
+    {
+        Ok(data) => data,
+        Err(err) => {
+            eprint!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match setup_service
+        .create_admin(
+            data.username.as_str().to_string(),
+            data.password.as_str().to_string(),
+        )
+        .await
+    {
+        Ok(true) => {
+            println!("Admin {} created.", data.username.as_str());
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            eprintln!("Admin could not be created.");
+            ExitCode::FAILURE
+        }
+        Err(report) => {
+            eprintln!("{report:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::config::UsernameConfig;
+use crate::html_base::ContextHtmlBuilder;
+use crate::setup::model::SetupAdminFormValidated;
+use crate::setup::service::IsSetupTokenValid;
+use crate::user::validate::password::{IsPasswordBreached, Password, PasswordCheckResult};
+use crate::user::validate::username::{IsUsernameTaken, Username, UsernameCheckResult};
+use crate::validation::{
+    ValidateErrorItem, ValidationErrorResponse, ValidationErrorsBuilder, ValidationOptionMarkup,
+};
+use maud::{Markup, html};
+use std::collections::HashMap;
+
+#[derive(FromForm, Default, Clone)]
+pub struct SetupAdminForm {
+    /// Printed at startup while no user exists.
+    pub setup_token: String,
+    pub username: String,
+    pub password: String,
+    pub password_confirm: String,
+}
+
+impl SetupAdminForm {
+    pub async fn as_validated<T: IsSetupTokenValid + IsUsernameTaken + IsPasswordBreached>(
+        &self,
+        setup_check: &T,
+        username_config: &UsernameConfig,
+    ) -> Result<SetupAdminFormValidated, ValidationErrorResponse> {
+        let mut builder = ValidationErrorsBuilder::new();
+
+        if !setup_check.is_setup_token_valid(&self.setup_token) {
+            builder.add(
+                "setup_token".to_string(),
+                vec!["setup token is wrong, use the one printed at startup".to_string()],
+            );
+        }
+        let username = builder
+            .add_item_from_trait(
+                Username::parse(self.username.clone(), username_config, None)
+                    .check_username_result(setup_check, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let password = builder
+            .add_item_from_trait(
+                Password::parse(self.password.clone(), None)
+                    .check_breached_result(setup_check, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let password_confirm = builder
+            .add_item_from_trait(password.parse_confirm(self.password_confirm.clone(), None))
+            .unwrap_or_default();
+
+        builder.build_result()?;
+
+        Ok(SetupAdminFormValidated {
+            username,
+            password,
+            password_confirm,
+        })
+    }
+
+    pub fn html_form(
+        title: String,
+        context_html_builder: &ContextHtmlBuilder,
+        setup_admin_form: Option<SetupAdminForm>,
+        errors: Option<HashMap<String, ValidateErrorItem>>,
+    ) -> Markup {
+        let setup_admin_form = setup_admin_form.unwrap_or_default();
+        let errors = errors.unwrap_or_default();
+        context_html_builder
+            .attach_title(title.clone())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                p { "No user exists yet. Create the first admin with the setup token printed at startup." }
+                form method="post" .form {
+                    (context_html_builder.csrf_input())
+                    input .form-item type="text" name="setup_token" placeholder="Setup token" value=(setup_admin_form.setup_token);
+                    (errors.get("setup_token").as_html())
+                    input .form-item type="text" name="username" placeholder="Username" value=(setup_admin_form.username);
+                    (errors.get("username").as_html())
+                    input .form-item type="password" name="password" placeholder="Password";
+                    (errors.get("password").as_html())
+                    input .form-item type="password" name="password_confirm" placeholder="Confirm password";
+                    (errors.get("password_confirm").as_html())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Create admin" };
+                }
+            })
+            .build()
+    }
+}
@@ -0,0 +1,5 @@
+pub mod cli;
+pub mod form;
+pub mod model;
+pub mod route;
+pub mod service;
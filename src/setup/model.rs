@@ -0,0 +1,8 @@
+use crate::user::validate::password::Password;
+use crate::user::validate::username::Username;
+
+pub struct SetupAdminFormValidated {
+    pub username: Username,
+    pub password: Password,
+    pub password_confirm: Password,
+}
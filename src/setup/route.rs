@@ -0,0 +1,119 @@
+use crate::csrf::CsrfForm;
+use crate::dependency::{Dep, GlobalContext};
+use crate::error::ErrorReportResponse;
+use crate::html_base::ContextHtmlBuilder;
+use crate::setup::form::SetupAdminForm;
+use crate::setup::service::SetupService;
+use crate::user::dependency::UserDep;
+use crate::user::password::PasswordHashPoolError;
+use maud::Markup;
+use rocket::fairing::AdHoc;
+use rocket::response::{Flash, Redirect};
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum SetupResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+fn setup_done() -> SetupResponse {
+    SetupResponse::Redirect(Flash::error(
+        Redirect::to(uri!("/user/login")),
+        "Setup is already done.",
+    ))
+}
+
+/// `token` fills in the setup token, for the link printed at startup.
+#[get("/setup?<token>")]
+async fn setup(
+    token: Option<String>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    setup_service: Dep<SetupService>,
+) -> SetupResponse {
+    if setup_service.0.is_setup_done() {
+        return setup_done();
+    }
+
+    let setup_admin_form = SetupAdminForm {
+        setup_token: token.unwrap_or_default(),
+        ..SetupAdminForm::default()
+    };
+    SetupResponse::Markup(SetupAdminForm::html_form(
+        "Setup".to_string(),
+        &context_html_builder.0,
+        Some(setup_admin_form),
+        None,
+    ))
+}
+
+#[post("/setup", data = "<data>")]
+async fn setup_post(
+    data: CsrfForm<SetupAdminForm>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    setup_service: Dep<SetupService>,
+) -> Result<SetupResponse, ErrorReportResponse<PasswordHashPoolError>> {
+    if setup_service.0.is_setup_done() {
+        return Ok(setup_done());
+    }
+
+    let validated_data = data
+        .as_validated(&setup_service.0, setup_service.0.username_config())
+        .await;
+    Ok(match validated_data {
+        Ok(data) => {
+            if setup_service
+                .0
+                .create_admin(
+                    data.username.as_str().to_string(),
+                    data.password.as_str().to_string(),
+                )
+                .await
+                .map_err(ErrorReportResponse)?
+            {
+                SetupResponse::Redirect(Flash::success(
+                    Redirect::to(uri!("/user/login")),
+                    "Admin created, you can log in now.",
+                ))
+            } else {
+                setup_done()
+            }
+        }
+        Err(err) => SetupResponse::Markup(SetupAdminForm::html_form(
+            "Setup".to_string(),
+            &context_html_builder.0,
+            Some(data.clone()),
+            Some(err.as_map()),
+        )),
+    })
+}
+
+pub struct SetupRoute;
+
+impl SetupRoute {
+    /// Prints the setup token while no user exists, it is only valid until the next restart.
+    /// Without a usable admin it points to `create-admin` instead.
+    pub fn adhoc() -> AdHoc {
+        AdHoc::on_ignite("SetupRoute", |r| async {
+            if let Some(global_context) = r.state::<GlobalContext>() {
+                match global_context.inject::<SetupService>().await {
+                    Ok(setup_service) => {
+                        if let Some(setup_token) = setup_service.issue_setup_token() {
+                            println!(
+                                "No user exists yet, create the first admin at {}/setup?token={}",
+                                global_context.config.public_url, setup_token
+                            );
+                        } else if setup_service.is_admin_missing() {
+                            println!(
+                                "No admin can log in, add one with `rust_vue_exercise create-admin <username>`"
+                            );
+                        }
+                    }
+                    Err(report) => eprintln!("Setup token could not be issued: {report:?}"),
+                }
+            }
+
+            r.mount("/", routes![setup, setup_post])
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::UsernameConfig;
+use crate::csrf::constant_time_eq;
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::user::breached_password::BreachedPasswordList;
+use crate::user::password::{PasswordHashPool, PasswordHashPoolError};
+use crate::user::repository::UserRepository;
+use crate::user::token::generate_token;
+use crate::user::validate::password::IsPasswordBreached;
+use crate::user::validate::username::IsUsernameTaken;
+use error_stack::Report;
+use std::sync::Mutex;
+
+/// Only held in memory, a restart before the setup is done prints a new one.
+static SETUP_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+pub trait IsSetupTokenValid {
+    fn is_setup_token_valid(&self, setup_token: &str) -> bool;
+}
+
+pub struct SetupService {
+    user_repository: UserRepository,
+    password_hash_pool: PasswordHashPool,
+    breached_password_list: BreachedPasswordList,
+    username_config: UsernameConfig,
+    /// Off for the command line, whoever runs it already has the database.
+    require_setup_token: bool,
+}
+
+impl SetupService {
+    fn new(
+        user_repository: UserRepository,
+        password_hash_pool: PasswordHashPool,
+        breached_password_list: BreachedPasswordList,
+        username_config: UsernameConfig,
+    ) -> Self {
+        Self {
+            user_repository,
+            password_hash_pool,
+            breached_password_list,
+            username_config,
+            require_setup_token: true,
+        }
+    }
+
+    pub fn without_setup_token(mut self) -> Self {
+        self.require_setup_token = false;
+        self
+    }
+
+    pub fn username_config(&self) -> &UsernameConfig {
+        &self.username_config
+    }
+
+    /// Unreadable counts as done, the setup page stays shut on errors.
+    pub fn is_setup_done(&self) -> bool {
+        self.user_repository.has_users().unwrap_or(true)
+    }
+
+    /// Users exist but no admin can log in, e.g. only the old seeded `default` account.
+    pub fn is_admin_missing(&self) -> bool {
+        self.is_setup_done() && !self.user_repository.has_usable_admin().unwrap_or(true)
+    }
+
+    /// Called at startup, `None` once a user exists.
+    pub fn issue_setup_token(&self) -> Option<String> {
+        if self.is_setup_done() {
+            return None;
+        }
+        let setup_token = generate_token();
+        *SETUP_TOKEN.lock().ok()? = Some(setup_token.clone());
+        Some(setup_token)
+    }
+
+    /// From the setup page only the first admin can be added, the command line can add more.
+    /// `Ok(false)` when the username is taken, or someone else finished the setup first.
+    pub async fn create_admin(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<bool, Report<PasswordHashPoolError>> {
+        let password = self.password_hash_pool.hash_password(password).await?;
+        let created = self
+            .user_repository
+            .add_admin(username, password, self.require_setup_token)
+            .is_ok();
+
+        if created && let Ok(mut setup_token) = SETUP_TOKEN.lock() {
+            *setup_token = None;
+        }
+        Ok(created)
+    }
+}
+
+impl IsSetupTokenValid for SetupService {
+    fn is_setup_token_valid(&self, setup_token: &str) -> bool {
+        if !self.require_setup_token {
+            return true;
+        }
+        match SETUP_TOKEN.lock() {
+            Ok(expected) => expected
+                .as_deref()
+                .is_some_and(|expected| constant_time_eq(expected, setup_token)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl IsUsernameTaken for SetupService {
+    async fn is_username_taken(&self, username: &str) -> bool {
+        self.user_repository
+            .username_taken(username.to_string())
+            .is_ok()
+    }
+}
+
+impl IsPasswordBreached for SetupService {
+    async fn is_password_breached(&self, password: &str) -> bool {
+        self.breached_password_list.contains(password).await
+    }
+}
+
+impl FromGlobalContext for SetupService {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_global_context.inject().await?,
+            dependency_global_context.inject().await?,
+            dependency_global_context.inject().await?,
+            dependency_global_context
+                .global_context
+                .config
+                .username
+                .clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SqliteClient;
+    use crate::user::password::PasswordHashParams;
+    use crate::user::token::TokenHasher;
+    use std::time::Duration;
+
+    #[test]
+    fn invalid_setup_token_is_rejected() {
+        let setup_service = SetupService::new(
+            UserRepository::new(
+                SqliteClient::new(":memory:".to_string()).unwrap(),
+                TokenHasher::new(b"test"),
+            ),
+            PasswordHashPool::new(PasswordHashParams::default(), 1, Duration::from_secs(1)),
+            BreachedPasswordList::new(None),
+            UsernameConfig::default(),
+        );
+        let setup_token = setup_service.issue_setup_token().unwrap();
+
+        assert!(setup_service.is_setup_token_valid(&setup_token));
+        assert!(!setup_service.is_setup_token_valid("wrong"));
+        assert!(!setup_service.is_setup_token_valid(""));
+        assert!(!setup_service.is_setup_token_valid(&setup_token[1..]));
+    }
+}
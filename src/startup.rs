@@ -0,0 +1,36 @@
+use crate::dependency::GlobalContext;
+use crate::user::token::TokenHasher;
+use rocket::config::SecretKey;
+use rocket::fairing::AdHoc;
+
+/// Refuses to launch on settings that would otherwise only fail on the first request.
+pub struct StartupCheck;
+
+impl StartupCheck {
+    pub fn adhoc() -> AdHoc {
+        AdHoc::try_on_ignite("StartupCheck", |r| async {
+            let Some(global_context) = r.state::<GlobalContext>() else {
+                return Ok(r);
+            };
+
+            // Loaded up front, a server that can not keep its token secret does not start.
+            if let Err(report) = global_context.inject::<TokenHasher>().await {
+                error!("Token secret could not be loaded: {report:?}");
+                return Err(r);
+            }
+
+            let has_secret_key = r
+                .figment()
+                .extract_inner::<SecretKey>("secret_key")
+                .is_ok_and(|secret_key| secret_key.is_provided());
+            if global_context.config.session.private && !has_secret_key {
+                error!(
+                    "`session.private` needs Rocket's `secret_key`, set it in exercise_rocket.toml"
+                );
+                return Err(r);
+            }
+
+            Ok(r)
+        })
+    }
+}
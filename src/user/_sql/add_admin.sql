@@ -0,0 +1,4 @@
+INSERT INTO users(username, username_normalized, password, is_admin)
+SELECT :username, :username_normalized, :password, 1
+WHERE NOT :first_only
+   OR NOT EXISTS(SELECT 1 FROM users)
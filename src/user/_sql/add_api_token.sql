@@ -0,0 +1,3 @@
+INSERT INTO user_api_tokens (user_id, name, token_hash, scopes, expire_after)
+VALUES (:user_id, :name, :token_hash, :scopes,
+        CASE WHEN :expires_in_days > 0 THEN datetime('now', '+' || :expires_in_days || ' days') END)
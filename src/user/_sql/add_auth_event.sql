@@ -0,0 +1,8 @@
+INSERT INTO auth_events (event, outcome, user_id, username, ip, user_agent, detail)
+VALUES (:event, :outcome,
+        COALESCE(:user_id, (SELECT id FROM users WHERE username_normalized = :username_normalized)),
+        CASE
+            WHEN :username = '' THEN COALESCE((SELECT username FROM users WHERE id = :user_id), '')
+            ELSE :username
+            END,
+        :ip, :user_agent, :detail)
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT INTO user_external_identities (user_id, issuer, subject, email)
+VALUES (:user_id, :issuer, :subject, :email)
@@ -0,0 +1,4 @@
+INSERT INTO user_invites (code_hash, note, max_uses, expire_after, created_by)
+VALUES (:code_hash, :note, :max_uses,
+        CASE WHEN :valid_days > 0 THEN datetime('now', '+' || :valid_days || ' days') END,
+        :created_by)
\ No newline at end of file
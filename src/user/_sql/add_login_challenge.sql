@@ -0,0 +1,2 @@
+INSERT INTO user_login_challenges (user_id, token_hash, expire_after)
+VALUES (:user_id, :token_hash, datetime('now', '+5 minutes'))
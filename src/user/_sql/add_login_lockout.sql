@@ -0,0 +1,2 @@
+INSERT INTO login_lockouts (key, failures, locked_until)
+VALUES (:key, :failures, :locked_until)
@@ -0,0 +1,2 @@
+INSERT INTO user_oidc_logins (state_hash, nonce, code_verifier, link_user_id, expire_after)
+VALUES (:state_hash, :nonce, :code_verifier, :link_user_id, datetime('now', '+10 minutes'))
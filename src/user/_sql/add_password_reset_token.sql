@@ -0,0 +1,2 @@
+INSERT INTO user_password_reset_tokens(user_id, token_hash, expire_after)
+VALUES (:user_id, :token_hash, datetime('now', '+30 minutes'))
@@ -0,0 +1,2 @@
+INSERT INTO user_recovery_codes (user_id, code_hash)
+VALUES (:user_id, :code_hash)
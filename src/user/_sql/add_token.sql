@@ -1,2 +1,2 @@
-INSERT INTO user_login_tokens(user_id, token, expire_after)
-VALUES (:user_id, :token, datetime('now', '+30 day'))
\ No newline at end of file
+INSERT INTO user_login_tokens(user_id, token_hash, expire_after, user_agent, ip)
+VALUES (:user_id, :token_hash, datetime('now', '+' || :lifetime_days || ' days'), :user_agent, :ip)
@@ -0,0 +1,5 @@
+DELETE
+FROM user_oidc_logins
+WHERE state_hash = :state_hash
+  AND expire_after > datetime('now')
+RETURNING nonce, code_verifier, link_user_id;
@@ -0,0 +1,4 @@
+DELETE
+FROM user_recovery_codes
+WHERE user_id = :user_id
+  AND code_hash = :code_hash
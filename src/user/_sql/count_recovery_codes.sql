@@ -0,0 +1,3 @@
+SELECT COUNT(*) AS total
+FROM user_recovery_codes
+WHERE user_id = :user_id;
@@ -0,0 +1,4 @@
+DELETE
+FROM user_api_tokens
+WHERE id = :id
+  AND user_id = :user_id
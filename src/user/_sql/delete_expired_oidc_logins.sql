@@ -0,0 +1,3 @@
+DELETE
+FROM user_oidc_logins
+WHERE expire_after <= datetime('now')
@@ -0,0 +1,4 @@
+DELETE
+FROM user_external_identities
+WHERE id = :id
+  AND user_id = :user_id
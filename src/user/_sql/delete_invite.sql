@@ -0,0 +1,3 @@
+DELETE
+FROM user_invites
+WHERE id = :id
\ No newline at end of file
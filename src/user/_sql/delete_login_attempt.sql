@@ -0,0 +1,3 @@
+DELETE
+FROM login_attempts
+WHERE key = :key
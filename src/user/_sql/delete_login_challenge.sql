@@ -0,0 +1,4 @@
+DELETE
+FROM user_login_challenges
+WHERE token_hash = :token_hash
+   OR expire_after <= datetime('now')
@@ -0,0 +1,4 @@
+DELETE
+from user_login_tokens
+WHERE user_id = :user_id
+  AND token_hash != :token_hash;
\ No newline at end of file
@@ -0,0 +1,4 @@
+DELETE
+FROM user_password_reset_tokens
+WHERE user_id = :user_id
+   OR expire_after <= datetime('now')
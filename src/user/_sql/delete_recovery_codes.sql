@@ -0,0 +1,3 @@
+DELETE
+FROM user_recovery_codes
+WHERE user_id = :user_id
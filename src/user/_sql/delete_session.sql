@@ -0,0 +1,5 @@
+DELETE
+FROM user_login_tokens
+WHERE id = :id
+  AND user_id = :user_id
+RETURNING token_hash = :token_hash AS current;
@@ -1,3 +1,3 @@
 DELETE
 from user_login_tokens
-WHERE token = :token;
\ No newline at end of file
+WHERE token_hash = :token_hash;
\ No newline at end of file
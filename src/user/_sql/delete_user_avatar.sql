@@ -0,0 +1,5 @@
+UPDATE user_profiles
+SET avatar              = NULL,
+    avatar_content_type = NULL,
+    avatar_updated_at   = NULL
+WHERE user_id = :user_id
\ No newline at end of file
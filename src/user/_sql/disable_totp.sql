@@ -0,0 +1,5 @@
+UPDATE users
+SET totp_secret         = NULL,
+    totp_pending_secret = NULL,
+    totp_last_step      = NULL
+WHERE id = :id
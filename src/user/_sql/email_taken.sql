@@ -0,0 +1,3 @@
+SELECT 1 AS taken
+FROM users
+WHERE email = :email COLLATE NOCASE
\ No newline at end of file
@@ -0,0 +1,6 @@
+UPDATE users
+SET totp_secret         = totp_pending_secret,
+    totp_pending_secret = NULL,
+    totp_last_step      = :step
+WHERE id = :id
+  AND totp_pending_secret = :secret
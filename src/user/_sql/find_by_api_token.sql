@@ -0,0 +1,7 @@
+SELECT u.id, u.username, uat.scopes
+FROM users AS u
+         INNER JOIN user_api_tokens uat on u.id = uat.user_id
+WHERE uat.token_hash = :token_hash
+  AND (uat.expire_after IS NULL OR uat.expire_after > datetime('now'))
+  AND u.disabled_at IS NULL
+LIMIT 1;
@@ -0,0 +1,7 @@
+SELECT u.id, u.username
+FROM users AS u
+         INNER JOIN user_external_identities uei on u.id = uei.user_id
+WHERE uei.issuer = :issuer
+  AND uei.subject = :subject
+  AND u.disabled_at IS NULL
+LIMIT 1;
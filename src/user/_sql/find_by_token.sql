@@ -1,6 +1,7 @@
-SELECT u.id, u.username
+SELECT u.id, u.username, u.is_admin
 FROM users AS u
          INNER JOIN user_login_tokens ult on u.id = ult.user_id
-WHERE ult.token = :token
+WHERE ult.token_hash = :token_hash
   AND ult.expire_after > datetime('now')
-LIMIT 1;
\ No newline at end of file
+  AND u.disabled_at IS NULL
+LIMIT 1;
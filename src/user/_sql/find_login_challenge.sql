@@ -0,0 +1,7 @@
+SELECT u.id, u.username, u.totp_secret
+FROM users AS u
+         INNER JOIN user_login_challenges ulc on u.id = ulc.user_id
+WHERE ulc.token_hash = :token_hash
+  AND ulc.expire_after > datetime('now')
+  AND u.disabled_at IS NULL
+LIMIT 1;
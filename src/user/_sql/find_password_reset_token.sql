@@ -0,0 +1,5 @@
+SELECT user_id
+FROM user_password_reset_tokens
+WHERE token_hash = :token_hash
+  AND expire_after > datetime('now')
+LIMIT 1;
@@ -0,0 +1,5 @@
+SELECT id, name, scopes, expire_after, created_at, last_used
+FROM user_api_tokens
+WHERE user_id = :user_id
+  AND (expire_after IS NULL OR expire_after > datetime('now'))
+ORDER BY created_at DESC, id DESC;
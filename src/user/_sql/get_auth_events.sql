@@ -0,0 +1,5 @@
+SELECT id, event, outcome, user_id, username, ip, user_agent, detail, created_at
+FROM auth_events
+WHERE user_id = :user_id
+ORDER BY id DESC
+LIMIT :limit
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT id, issuer, email, created_at
+FROM user_external_identities
+WHERE user_id = :user_id
+ORDER BY created_at DESC, id DESC;
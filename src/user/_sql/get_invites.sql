@@ -0,0 +1,4 @@
+SELECT ui.id, ui.note, ui.max_uses, ui.uses, ui.expire_after, ui.created_at, u.username AS created_by
+FROM user_invites ui
+         LEFT JOIN users u ON u.id = ui.created_by
+ORDER BY ui.created_at DESC, ui.id DESC
\ No newline at end of file
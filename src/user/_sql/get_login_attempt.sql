@@ -0,0 +1,4 @@
+SELECT failures, last_failure, locked_until
+FROM login_attempts
+WHERE key = :key
+LIMIT 1;
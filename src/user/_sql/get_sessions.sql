@@ -0,0 +1,5 @@
+SELECT id, created_at, last_seen, user_agent, ip, token_hash = :token_hash AS current
+FROM user_login_tokens
+WHERE user_id = :user_id
+  AND expire_after > datetime('now')
+ORDER BY last_seen DESC;
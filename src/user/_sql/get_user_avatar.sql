@@ -0,0 +1,5 @@
+SELECT avatar, avatar_content_type
+FROM user_profiles
+WHERE user_id = :user_id
+  AND avatar IS NOT NULL
+LIMIT 1;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT id, email
+FROM users
+WHERE (username = :username OR username_normalized = :username_normalized)
+  AND disabled_at IS NULL
+ORDER BY username = :username DESC
+LIMIT 1;
@@ -0,0 +1,5 @@
+SELECT id, username, email, email_verified_at
+FROM users
+WHERE id = :id
+  AND disabled_at IS NULL
+LIMIT 1;
\ No newline at end of file
@@ -1,4 +1,9 @@
-SELECT id, password
+SELECT id,
+       password,
+       must_reset_password,
+       email IS NOT NULL AND email_verified_at IS NULL AS email_unverified
 FROM users
-WHERE username = :username
+WHERE (username = :username OR username_normalized = :username_normalized)
+  AND disabled_at IS NULL
+ORDER BY username = :username DESC
 LIMIT 1;
\ No newline at end of file
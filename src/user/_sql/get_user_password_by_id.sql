@@ -0,0 +1,7 @@
+SELECT id,
+       password,
+       must_reset_password,
+       email IS NOT NULL AND email_verified_at IS NULL AS email_unverified
+FROM users
+WHERE id = :id
+LIMIT 1;
\ No newline at end of file
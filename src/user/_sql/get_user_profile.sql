@@ -0,0 +1,4 @@
+SELECT display_name, time_zone, locale, theme, avatar_updated_at
+FROM user_profiles
+WHERE user_id = :user_id
+LIMIT 1;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT totp_secret, totp_pending_secret
+FROM users
+WHERE id = :id
+LIMIT 1;
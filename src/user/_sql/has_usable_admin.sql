@@ -0,0 +1,5 @@
+SELECT EXISTS(SELECT 1
+              FROM users
+              WHERE is_admin = 1
+                AND disabled_at IS NULL
+                AND must_reset_password = 0) AS has_usable_admin
\ No newline at end of file
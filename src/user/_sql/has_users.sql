@@ -0,0 +1 @@
+SELECT EXISTS(SELECT 1 FROM users) AS has_users
@@ -0,0 +1,5 @@
+SELECT 1 AS usable
+FROM user_invites
+WHERE code_hash = :code_hash
+  AND uses < max_uses
+  AND (expire_after IS NULL OR expire_after > datetime('now'))
\ No newline at end of file
@@ -1,2 +1,2 @@
-INSERT INTO users(username, password)
-VALUES (:username, :password)
\ No newline at end of file
+INSERT INTO users(username, username_normalized, password, email)
+VALUES (:username, :username_normalized, :password, :email)
\ No newline at end of file
@@ -0,0 +1,3 @@
+UPDATE users
+SET password = :password
+WHERE id = :id;
\ No newline at end of file
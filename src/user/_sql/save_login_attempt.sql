@@ -0,0 +1,5 @@
+INSERT INTO login_attempts (key, failures, last_failure, locked_until)
+VALUES (:key, :failures, :last_failure, :locked_until)
+ON CONFLICT (key) DO UPDATE SET failures     = excluded.failures,
+                                last_failure = excluded.last_failure,
+                                locked_until = excluded.locked_until
@@ -0,0 +1,6 @@
+INSERT INTO user_profiles (user_id, display_name, time_zone, locale, theme)
+VALUES (:user_id, :display_name, :time_zone, :locale, :theme)
+ON CONFLICT (user_id) DO UPDATE SET display_name = excluded.display_name,
+                                    time_zone    = excluded.time_zone,
+                                    locale       = excluded.locale,
+                                    theme        = excluded.theme
\ No newline at end of file
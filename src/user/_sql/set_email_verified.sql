@@ -0,0 +1,5 @@
+UPDATE users
+SET email_verified_at = CURRENT_TIMESTAMP
+WHERE id = :id
+  AND email = :email
+  AND email_verified_at IS NULL
\ No newline at end of file
@@ -0,0 +1,3 @@
+UPDATE users
+SET totp_pending_secret = :secret
+WHERE id = :id
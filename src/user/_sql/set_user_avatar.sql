@@ -0,0 +1,5 @@
+INSERT INTO user_profiles (user_id, avatar, avatar_content_type, avatar_updated_at)
+VALUES (:user_id, :avatar, :content_type, CURRENT_TIMESTAMP)
+ON CONFLICT (user_id) DO UPDATE SET avatar              = excluded.avatar,
+                                    avatar_content_type = excluded.avatar_content_type,
+                                    avatar_updated_at   = excluded.avatar_updated_at
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE user_api_tokens
+SET last_used = CURRENT_TIMESTAMP
+WHERE token_hash = :token_hash
+  AND (last_used IS NULL OR last_used < datetime('now', '-1 minute'))
@@ -0,0 +1,7 @@
+UPDATE user_login_tokens
+SET last_seen    = CURRENT_TIMESTAMP,
+    expire_after = CASE
+                       WHEN :sliding THEN datetime('now', '+' || :lifetime_days || ' days')
+                       ELSE expire_after END
+WHERE token_hash = :token_hash
+  AND last_seen < datetime('now', '-1 minute')
@@ -0,0 +1,4 @@
+UPDATE users
+SET totp_last_step = :step
+WHERE id = :id
+  AND (totp_last_step IS NULL OR totp_last_step < :step)
@@ -0,0 +1,6 @@
+UPDATE users
+SET totp_secret         = :secret,
+    totp_pending_secret = :pending_secret
+WHERE id = :id
+  AND totp_secret IS :old_secret
+  AND totp_pending_secret IS :old_pending_secret
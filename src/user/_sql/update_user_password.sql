@@ -0,0 +1,4 @@
+UPDATE users
+SET password            = :password,
+    must_reset_password = 0
+WHERE id = :id;
\ No newline at end of file
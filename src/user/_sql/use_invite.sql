@@ -0,0 +1,5 @@
+UPDATE user_invites
+SET uses = uses + 1
+WHERE code_hash = :code_hash
+  AND uses < max_uses
+  AND (expire_after IS NULL OR expire_after > datetime('now'))
\ No newline at end of file
@@ -1,3 +1,3 @@
 SELECT 1 AS taken
 FROM users
-WHERE username = :username
\ No newline at end of file
+WHERE username_normalized = :username_normalized
\ No newline at end of file
@@ -0,0 +1,63 @@
+use crate::dependency::ApiScope;
+
+/// Scopes are stored comma separated, unknown ones are dropped.
+pub fn parse_scopes(scopes: &str) -> Box<[ApiScope]> {
+    ApiScope::ALL
+        .into_iter()
+        .filter(|api_scope| {
+            scopes
+                .split(',')
+                .any(|s| ApiScope::parse(s) == Some(*api_scope))
+        })
+        .collect()
+}
+
+pub fn format_scopes(scopes: &[ApiScope]) -> String {
+    ApiScope::ALL
+        .into_iter()
+        .filter(|api_scope| scopes.contains(api_scope))
+        .map(|api_scope| api_scope.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Token from an `Authorization: Bearer <token>` header value.
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(
+            parse_scopes("write,read").as_ref(),
+            [ApiScope::Read, ApiScope::Write]
+        );
+        assert_eq!(parse_scopes("read, admin").as_ref(), [ApiScope::Read]);
+        assert!(parse_scopes("").is_empty());
+    }
+
+    #[test]
+    fn test_format_scopes() {
+        assert_eq!(
+            format_scopes(&[ApiScope::Write, ApiScope::Read]),
+            "read,write"
+        );
+        assert_eq!(format_scopes(&[ApiScope::Write]), "write");
+        assert_eq!(format_scopes(&[]), "");
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer  abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Bearer"), None);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use error_stack::Report;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tokio::task::spawn_blocking;
+
+/// Line that starts at or after `pos`, with its start offset. Lines are `HEX[:count]`.
+fn line_at<R: BufRead + Seek>(reader: &mut R, pos: u64) -> std::io::Result<Option<(u64, Vec<u8>)>> {
+    let mut start = pos;
+    if pos > 0 {
+        reader.seek(SeekFrom::Start(pos - 1))?;
+        let mut partial = vec![];
+        start = pos - 1 + reader.read_until(b'\n', &mut partial)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut line = vec![];
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some((start, line)))
+}
+
+/// Binary search over a file sorted by hash, only a few dozen lines are read per lookup.
+fn contains_hash<R: BufRead + Seek>(reader: &mut R, hash: &str) -> std::io::Result<bool> {
+    let mut low = 0;
+    let mut high = reader.seek(SeekFrom::End(0))?;
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let Some((start, line)) = line_at(reader, mid)? else {
+            high = mid;
+            continue;
+        };
+        if start >= high {
+            high = mid;
+            continue;
+        }
+
+        let line_hash = line
+            .split(|b| matches!(b, b':' | b'\r' | b'\n'))
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        match line_hash.as_slice().cmp(hash.as_bytes()) {
+            Ordering::Equal => return Ok(true),
+            Ordering::Less => low = start + line.len() as u64,
+            Ordering::Greater => high = mid,
+        }
+    }
+
+    Ok(false)
+}
+
+/// Offline list of breached passwords, e.g. the "ordered by hash" SHA-1 download of
+/// Have I Been Pwned. Without a configured file every password passes.
+#[derive(Clone)]
+pub struct BreachedPasswordList {
+    path: Option<Arc<PathBuf>>,
+}
+
+impl BreachedPasswordList {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path: path.map(Arc::new),
+        }
+    }
+
+    /// A file that became unreadable lets the password pass, as refusing every password
+    /// would lock out registration altogether.
+    pub async fn contains(&self, password: &str) -> bool {
+        let Some(path) = self.path.clone() else {
+            return false;
+        };
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+
+        spawn_blocking(move || {
+            let mut reader = BufReader::new(File::open(path.as_path())?);
+            contains_hash(&mut reader, &hash)
+        })
+        .await
+        .is_ok_and(|found| found.unwrap_or(false))
+    }
+}
+
+static BREACHED_PASSWORD_LIST: OnceCell<BreachedPasswordList> = OnceCell::const_new();
+
+impl FromGlobalContext for BreachedPasswordList {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let breached_password_list = BREACHED_PASSWORD_LIST
+            .get_or_try_init(|| async {
+                let path = &dependency_global_context
+                    .global_context
+                    .config
+                    .breached_password_path;
+                if path.is_empty() {
+                    return Ok(Self::new(None));
+                }
+
+                // Fails early on a wrong path instead of silently letting every password pass.
+                let mut probe = [0; 1];
+                File::open(path)
+                    .and_then(|mut file| file.read(&mut probe))
+                    .map_err(|_| {
+                        Report::new(DependencyError::Other(
+                            "Breached password list can not be read".to_string(),
+                        ))
+                    })?;
+
+                Ok::<_, Report<DependencyError>>(Self::new(Some(PathBuf::from(path))))
+            })
+            .await?;
+
+        Ok(breached_password_list.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sha1_upper(password: &str) -> String {
+        hex::encode_upper(Sha1::digest(password.as_bytes()))
+    }
+
+    fn list(passwords: &[&str]) -> Cursor<Vec<u8>> {
+        let mut hashes: Vec<String> = passwords.iter().map(|p| sha1_upper(p)).collect();
+        hashes.sort();
+        let lines: Vec<String> = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| format!("{}:{}\r\n", hash, i * 1000 + 1))
+            .collect();
+        Cursor::new(lines.concat().into_bytes())
+    }
+
+    #[test]
+    fn test_contains_hash() {
+        let passwords = [
+            "Password1!",
+            "123456",
+            "qwerty",
+            "Summer2024!",
+            "letmein",
+            "dragon",
+            "P@ssw0rd",
+        ];
+        let mut reader = list(&passwords);
+        for password in passwords {
+            assert!(
+                contains_hash(&mut reader, &sha1_upper(password)).unwrap(),
+                "{}",
+                password
+            );
+        }
+        assert!(!contains_hash(&mut reader, &sha1_upper("Hello@Wor1d2")).unwrap());
+        assert!(!contains_hash(&mut reader, &"0".repeat(40)).unwrap());
+        assert!(!contains_hash(&mut reader, &"F".repeat(40)).unwrap());
+    }
+
+    #[test]
+    fn test_contains_hash_single_line_and_empty() {
+        let mut reader = list(&["Password1!"]);
+        assert!(contains_hash(&mut reader, &sha1_upper("Password1!")).unwrap());
+        assert!(!contains_hash(&mut reader, &sha1_upper("qwerty")).unwrap());
+
+        let mut reader = Cursor::new(vec![]);
+        assert!(!contains_hash(&mut reader, &sha1_upper("qwerty")).unwrap());
+    }
+
+    #[test]
+    fn test_contains_hash_lowercase_without_count() {
+        let hash = sha1_upper("Password1!");
+        let mut reader = Cursor::new(format!("{}\n", hash.to_lowercase()).into_bytes());
+        assert!(contains_hash(&mut reader, &hash).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_without_file() {
+        assert!(!BreachedPasswordList::new(None).contains("Password1!").await);
+    }
+}
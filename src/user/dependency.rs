@@ -1,8 +1,8 @@
 use crate::dependency::{
-    DefaultFlag, Dep, DependencyError, DependencyFlag, DependencyGlobalContext, FromGlobalContext,
-    GlobalContext,
+    DefaultFlag, Dep, DependencyError, DependencyFlag, DependencyFlagData, DependencyGlobalContext,
+    FromGlobalContext, GlobalContext,
 };
-use crate::user::model::UserContext;
+use crate::user::model::{UserAuth, UserContext};
 use crate::user::service::UserCheckService;
 use error_stack::Report;
 use rocket::Request;
@@ -34,6 +34,29 @@ pub trait FromUserContext: Sized {
     ) -> impl Future<Output = Result<Self, Report<DependencyError>>> + Send;
 }
 
+/// Why the route with `flag` is refused to the request of `user_context`, `None` if it is not.
+fn refused_status(user_context: &UserContext, flag: &DependencyFlagData) -> Option<Status> {
+    match &user_context.auth {
+        UserAuth::InvalidApiToken => return Some(Status::Unauthorized),
+        UserAuth::ApiToken { scopes }
+            if !flag.api_scope.is_some_and(|scope| scopes.contains(&scope)) =>
+        {
+            return Some(Status::Forbidden);
+        }
+        _ => {}
+    }
+
+    if user_context.is_user && !flag.allow_user {
+        Some(Status::Unauthorized)
+    } else if (!user_context.is_user && !flag.allow_visitor)
+        || (flag.require_admin && !user_context.is_admin)
+    {
+        Some(Status::Forbidden)
+    } else {
+        None
+    }
+}
+
 pub struct UserDependencyGuard<T, F = DefaultFlag>(pub T, pub Arc<UserContext>, PhantomData<F>)
 where
     T: FromUserContext,
@@ -55,7 +78,7 @@ where
             .local_cache_async(async {
                 let user_service = req.guard::<Dep<UserCheckService>>().await.succeeded()?;
 
-                Some(Arc::new(user_service.get_user_context()))
+                Some(Arc::new(user_service.get_user_context(req.cookies())))
             })
             .await;
 
@@ -70,17 +93,11 @@ where
             Some(user_context) => Arc::clone(user_context),
         };
 
-        if user_context.is_user && !flag.allow_user {
+        if let Some(status) = refused_status(&user_context, &flag) {
             return if flag.use_forward {
-                Outcome::Forward(Status::Unauthorized)
+                Outcome::Forward(status)
             } else {
-                Outcome::Error((Status::Unauthorized, ()))
-            };
-        } else if !user_context.is_user && !flag.allow_visitor {
-            return if flag.use_forward {
-                Outcome::Forward(Status::Forbidden)
-            } else {
-                Outcome::Error((Status::Forbidden, ()))
+                Outcome::Error((status, ()))
             };
         }
 
@@ -116,3 +133,67 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependency::ApiScope;
+    use crate::user::flag::{AdminFlag, ApiReadFlag};
+    use crate::user::profile::UserProfile;
+
+    fn user_context(is_admin: bool, auth: UserAuth) -> UserContext {
+        UserContext {
+            id: 1,
+            is_user: true,
+            username: "alice".to_string(),
+            is_admin,
+            auth,
+            profile: UserProfile::default(),
+        }
+    }
+
+    #[test]
+    fn admin_flag_refuses_non_admin() {
+        let flag = AdminFlag::build_flag_data();
+        assert_eq!(
+            refused_status(&user_context(false, UserAuth::Session), &flag),
+            Some(Status::Forbidden)
+        );
+        assert_eq!(
+            refused_status(&user_context(true, UserAuth::Session), &flag),
+            None
+        );
+    }
+
+    #[test]
+    fn admin_flag_refuses_visitor() {
+        let visitor = UserContext {
+            is_user: false,
+            ..user_context(false, UserAuth::Visitor)
+        };
+        assert_eq!(
+            refused_status(&visitor, &AdminFlag::build_flag_data()),
+            Some(Status::Forbidden)
+        );
+    }
+
+    #[test]
+    fn api_token_needs_scope() {
+        let read_only = || {
+            user_context(
+                false,
+                UserAuth::ApiToken {
+                    scopes: Box::new([ApiScope::Read]),
+                },
+            )
+        };
+        assert_eq!(
+            refused_status(&read_only(), &ApiReadFlag::build_flag_data()),
+            None
+        );
+        assert_eq!(
+            refused_status(&read_only(), &AdminFlag::build_flag_data()),
+            Some(Status::Forbidden)
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::user::token::TokenHasher;
+use chrono::{DateTime, Utc};
+
+/// Link token `<user id>.<expiry>.<signature>`, nothing is stored until it is used.
+/// The signature covers the address the mail went to, so changing it voids older links.
+pub struct EmailVerificationToken {
+    pub user_id: i64,
+    expire_after: i64,
+    signature: String,
+}
+
+fn signed_message(user_id: i64, expire_after: i64, email: &str) -> String {
+    format!(
+        "email-verification:{}:{}:{}",
+        user_id,
+        expire_after,
+        email.to_lowercase()
+    )
+}
+
+impl EmailVerificationToken {
+    pub fn sign(
+        token_hasher: &TokenHasher,
+        user_id: i64,
+        email: &str,
+        expire_after: DateTime<Utc>,
+    ) -> String {
+        let expire_after = expire_after.timestamp();
+        format!(
+            "{}.{}.{}",
+            user_id,
+            expire_after,
+            token_hasher.hash(&signed_message(user_id, expire_after, email))
+        )
+    }
+
+    /// Only splits the token, `verify` has to pass before the user id can be trusted.
+    pub fn parse(token: &str) -> Option<Self> {
+        let mut parts = token.splitn(3, '.');
+        Some(Self {
+            user_id: parts.next()?.parse().ok()?,
+            expire_after: parts.next()?.parse().ok()?,
+            signature: parts.next()?.to_string(),
+        })
+    }
+
+    pub fn verify(&self, token_hasher: &TokenHasher, email: &str, now: DateTime<Utc>) -> bool {
+        now.timestamp() <= self.expire_after
+            && token_hasher.verify(
+                &signed_message(self.user_id, self.expire_after, email),
+                &self.signature,
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn token_hasher() -> TokenHasher {
+        TokenHasher::new(b"secret")
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let now = Utc::now();
+        let token = EmailVerificationToken::sign(
+            &token_hasher(),
+            7,
+            "Jane@example.com",
+            now + Duration::hours(1),
+        );
+        let parsed = EmailVerificationToken::parse(&token).unwrap();
+        assert_eq!(parsed.user_id, 7);
+        assert!(parsed.verify(&token_hasher(), "jane@example.com", now));
+    }
+
+    #[test]
+    fn test_verify_error_expired() {
+        let now = Utc::now();
+        let token = EmailVerificationToken::sign(
+            &token_hasher(),
+            7,
+            "jane@example.com",
+            now - Duration::seconds(1),
+        );
+        let parsed = EmailVerificationToken::parse(&token).unwrap();
+        assert!(!parsed.verify(&token_hasher(), "jane@example.com", now));
+    }
+
+    #[test]
+    fn test_verify_error_other_email() {
+        let now = Utc::now();
+        let token = EmailVerificationToken::sign(
+            &token_hasher(),
+            7,
+            "jane@example.com",
+            now + Duration::hours(1),
+        );
+        let parsed = EmailVerificationToken::parse(&token).unwrap();
+        assert!(!parsed.verify(&token_hasher(), "john@example.com", now));
+    }
+
+    #[test]
+    fn test_verify_error_tampered() {
+        let now = Utc::now();
+        let token = EmailVerificationToken::sign(
+            &token_hasher(),
+            7,
+            "jane@example.com",
+            now + Duration::hours(1),
+        );
+        let tampered = format!("8{}", token.trim_start_matches('7'));
+        let parsed = EmailVerificationToken::parse(&tampered).unwrap();
+        assert!(!parsed.verify(&token_hasher(), "jane@example.com", now));
+        assert!(!EmailVerificationToken::parse(&token).unwrap().verify(
+            &TokenHasher::new(b"other secret"),
+            "jane@example.com",
+            now
+        ));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(EmailVerificationToken::parse("").is_none());
+        assert!(EmailVerificationToken::parse("7.abc.sig").is_none());
+        assert!(EmailVerificationToken::parse("7.123").is_none());
+    }
+}
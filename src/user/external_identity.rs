@@ -0,0 +1,60 @@
+use crate::oidc::IdTokenClaims;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Leaves room for the suffix within the 30 characters a username may have.
+const MAX_BASE_LENGTH: usize = 20;
+
+/// Username for an auto-registered user, taken from the provider's claims.
+/// Later attempts get a suffix, for when the name is taken or too short.
+pub fn username_from_claims(claims: &IdTokenClaims, suffix: Option<&str>) -> String {
+    let base = claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| {
+            claims
+                .email
+                .as_deref()
+                .and_then(|email| email.split('@').next())
+        })
+        .or(claims.name.as_deref())
+        .unwrap_or_default()
+        .trim();
+    let base = if base.is_empty() { "user" } else { base };
+    let base: String = base.graphemes(true).take(MAX_BASE_LENGTH).collect();
+
+    match suffix {
+        Some(suffix) => format!("{}-{}", base, suffix),
+        None => base,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::{from_value, json};
+
+    fn claims(value: rocket::serde::json::Value) -> IdTokenClaims {
+        let mut claims = json!({"iss": "i", "sub": "s", "aud": "a", "exp": 0});
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        from_value(claims).unwrap()
+    }
+
+    #[test]
+    fn test_username_from_claims() {
+        let both = claims(json!({"preferred_username": "jane.doe", "email": "jd@example.com"}));
+        assert_eq!(username_from_claims(&both, None), "jane.doe");
+        assert_eq!(username_from_claims(&both, Some("a1b2")), "jane.doe-a1b2");
+
+        let email = claims(json!({"email": "jd@example.com"}));
+        assert_eq!(username_from_claims(&email, None), "jd");
+
+        let nothing = claims(json!({}));
+        assert_eq!(username_from_claims(&nothing, Some("a1b2")), "user-a1b2");
+
+        let long = claims(json!({"preferred_username": "a".repeat(40)}));
+        assert_eq!(username_from_claims(&long, Some("a1b2")).len(), 25);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::dependency::DependencyFlag;
+use crate::dependency::{ApiScope, DependencyFlag};
 
 pub struct LoginFlag;
 
@@ -13,3 +13,25 @@ impl DependencyFlag for LogoutFlag {
     const ALLOW_USER: bool = true;
     const ALLOW_VISITOR: bool = false;
 }
+
+/// The admin area, only sessions of admin users get in.
+pub struct AdminFlag;
+
+impl DependencyFlag for AdminFlag {
+    const ALLOW_VISITOR: bool = false;
+    const REQUIRE_ADMIN: bool = true;
+}
+
+/// JSON routes that also take a personal access token with the read scope.
+pub struct ApiReadFlag;
+
+impl DependencyFlag for ApiReadFlag {
+    const API_SCOPE: Option<ApiScope> = Some(ApiScope::Read);
+}
+
+/// JSON routes that also take a personal access token with the write scope.
+pub struct ApiWriteFlag;
+
+impl DependencyFlag for ApiWriteFlag {
+    const API_SCOPE: Option<ApiScope> = Some(ApiScope::Write);
+}
@@ -1,47 +1,89 @@
+use crate::config::UsernameConfig;
+use crate::csrf::CsrfField;
 use crate::html_base::ContextHtmlBuilder;
-use crate::user::model::UserRegisterFormValidated;
-use crate::user::validate::password::Password;
+use crate::user::model::{
+    UserChangePasswordFormValidated, UserContext, UserPasswordResetFormValidated,
+    UserProfileFormValidated, UserRegisterFormValidated,
+};
+use crate::user::profile::{Locale, Theme, UserProfile};
+use crate::user::validate::email::{Email, EmailCheckResult, IsEmailTaken};
+use crate::user::validate::invite::{InviteCode, InviteCodeCheckResult, IsInviteValid};
+use crate::user::validate::password::{
+    IsCurrentPassword, IsPasswordBreached, Password, PasswordCheckResult,
+};
+use crate::user::validate::profile::{DisplayName, TimeZone, parse_locale, parse_theme};
 use crate::user::validate::username::{IsUsernameTaken, Username, UsernameCheckResult};
 use crate::validation::{
     ValidateErrorItem, ValidationErrorResponse, ValidationErrorsBuilder, ValidationOptionMarkup,
 };
 use maud::{Markup, html};
+use rocket::fs::TempFile;
 use std::collections::HashMap;
 
 #[derive(FromForm, Default, Clone)]
 pub struct UserRegisterForm {
     pub username: String,
+    pub email: String,
     pub password: String,
     pub password_confirm: String,
+    /// Only on the form with invite-only registration.
+    #[field(default = String::new())]
+    pub invite_code: String,
 }
 
 impl UserRegisterForm {
-    pub async fn as_validated<T: IsUsernameTaken>(
+    pub async fn as_validated<
+        T: IsUsernameTaken + IsEmailTaken + IsPasswordBreached + IsInviteValid,
+    >(
         &self,
-        is_username_taken: &T,
+        register_check: &T,
+        username_config: &UsernameConfig,
     ) -> Result<UserRegisterFormValidated, ValidationErrorResponse> {
         let mut builder = ValidationErrorsBuilder::new();
 
         let username = builder
             .add_item_from_trait(
-                Username::parse(self.username.clone(), None)
-                    .check_username_result(is_username_taken, None)
+                Username::parse(self.username.clone(), username_config, None)
+                    .check_username_result(register_check, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let email = builder
+            .add_item_from_trait(
+                Email::parse(self.email.clone(), None)
+                    .check_email_result(register_check, None)
                     .await,
             )
             .unwrap_or_default();
         let password = builder
-            .add_item_from_trait(Password::parse(self.password.clone(), None))
+            .add_item_from_trait(
+                Password::parse(self.password.clone(), None)
+                    .check_breached_result(register_check, None)
+                    .await,
+            )
             .unwrap_or_default();
         let password_confirm = builder
             .add_item_from_trait(password.parse_confirm(self.password_confirm.clone(), None))
             .unwrap_or_default();
+        let invite_code = match register_check.is_invite_required() {
+            true => builder
+                .add_item_from_trait(
+                    InviteCode::parse(self.invite_code.clone(), None)
+                        .check_invite_code_result(register_check, None)
+                        .await,
+                )
+                .ok(),
+            false => None,
+        };
 
         builder.build_result()?;
 
         Ok(UserRegisterFormValidated {
             username,
+            email,
             password,
             password_confirm,
+            invite_code,
         })
     }
 
@@ -50,6 +92,7 @@ impl UserRegisterForm {
         context_html_builder: &ContextHtmlBuilder,
         user_register_form: Option<UserRegisterForm>,
         errors: Option<HashMap<String, ValidateErrorItem>>,
+        invite_required: bool,
     ) -> Markup {
         let user_register_form = user_register_form.unwrap_or_default();
         let errors = errors.unwrap_or_default();
@@ -58,15 +101,265 @@ impl UserRegisterForm {
             .attach_content(html! {
                 h1 .mt-3 { (title) }
                 form method="post" .form {
+                    (context_html_builder.csrf_input())
                     input .form-item type="text" name="username" placeholder="Username" value=(user_register_form.username);
                     (errors.get("username").as_html())
+                    input .form-item type="email" name="email" placeholder="Email" value=(user_register_form.email);
+                    (errors.get("email").as_html())
                     input .form-item type="password" name="password" placeholder="Password";
                     (errors.get("password").as_html())
                     input .form-item type="password" name="password_confirm" placeholder="Confirm password";
                     (errors.get("password_confirm").as_html())
+                    @if invite_required {
+                        input .form-item type="text" name="invite_code" placeholder="Invite code" value=(user_register_form.invite_code);
+                        (errors.get("invite_code").as_html())
+                    }
                     button .btn .btn-sky-blue .mt-3 type="submit" { "Register" };
                 }
             })
             .build()
     }
 }
+
+#[derive(FromForm, Default, Clone)]
+pub struct UserChangePasswordForm {
+    pub current_password: String,
+    pub password: String,
+    pub password_confirm: String,
+}
+
+impl UserChangePasswordForm {
+    pub async fn as_validated<T: IsCurrentPassword + IsPasswordBreached>(
+        &self,
+        is_current_password: &T,
+    ) -> Result<UserChangePasswordFormValidated, ValidationErrorResponse> {
+        let mut builder = ValidationErrorsBuilder::new();
+
+        let current_password = builder
+            .add_item_from_trait(
+                Password::parse_current(self.current_password.clone(), is_current_password, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let password = builder
+            .add_item_from_trait(
+                Password::parse(self.password.clone(), None)
+                    .check_breached_result(is_current_password, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let password_confirm = builder
+            .add_item_from_trait(password.parse_confirm(self.password_confirm.clone(), None))
+            .unwrap_or_default();
+
+        builder.build_result()?;
+
+        Ok(UserChangePasswordFormValidated {
+            current_password,
+            password,
+            password_confirm,
+        })
+    }
+
+    pub fn html_form(
+        title: String,
+        context_html_builder: &ContextHtmlBuilder,
+        errors: Option<HashMap<String, ValidateErrorItem>>,
+    ) -> Markup {
+        let errors = errors.unwrap_or_default();
+        context_html_builder
+            .attach_title(title.clone())
+            .set_current_tag("user".to_string())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                form method="post" .form {
+                    (context_html_builder.csrf_input())
+                    input .form-item type="password" name="current_password" placeholder="Current password";
+                    (errors.get("current_password").as_html())
+                    input .form-item type="password" name="password" placeholder="New password";
+                    (errors.get("password").as_html())
+                    input .form-item type="password" name="password_confirm" placeholder="Confirm new password";
+                    (errors.get("password_confirm").as_html())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Change password" };
+                }
+            })
+            .build()
+    }
+}
+
+#[derive(FromForm, Default, Clone)]
+pub struct UserPasswordResetForm {
+    pub password: String,
+    pub password_confirm: String,
+}
+
+impl UserPasswordResetForm {
+    pub async fn as_validated<T: IsPasswordBreached>(
+        &self,
+        breached_check: &T,
+    ) -> Result<UserPasswordResetFormValidated, ValidationErrorResponse> {
+        let mut builder = ValidationErrorsBuilder::new();
+
+        let password = builder
+            .add_item_from_trait(
+                Password::parse(self.password.clone(), None)
+                    .check_breached_result(breached_check, None)
+                    .await,
+            )
+            .unwrap_or_default();
+        let password_confirm = builder
+            .add_item_from_trait(password.parse_confirm(self.password_confirm.clone(), None))
+            .unwrap_or_default();
+
+        builder.build_result()?;
+
+        Ok(UserPasswordResetFormValidated {
+            password,
+            password_confirm,
+        })
+    }
+
+    pub fn html_form(
+        title: String,
+        context_html_builder: &ContextHtmlBuilder,
+        errors: Option<HashMap<String, ValidateErrorItem>>,
+    ) -> Markup {
+        let errors = errors.unwrap_or_default();
+        context_html_builder
+            .attach_title(title.clone())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                form method="post" .form {
+                    (context_html_builder.csrf_input())
+                    input .form-item type="password" name="password" placeholder="New password";
+                    (errors.get("password").as_html())
+                    input .form-item type="password" name="password_confirm" placeholder="Confirm new password";
+                    (errors.get("password_confirm").as_html())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Reset password" };
+                }
+            })
+            .build()
+    }
+}
+
+#[derive(FromForm, Default, Clone)]
+pub struct UserProfileForm {
+    pub display_name: String,
+    pub time_zone: String,
+    pub locale: String,
+    pub theme: String,
+}
+
+impl UserProfileForm {
+    pub fn from_profile(profile: &UserProfile) -> Self {
+        Self {
+            display_name: profile.display_name.clone().unwrap_or_default(),
+            time_zone: profile.time_zone.name().to_string(),
+            locale: profile.locale.as_str().to_string(),
+            theme: profile.theme.as_str().to_string(),
+        }
+    }
+
+    pub fn as_validated(&self) -> Result<UserProfileFormValidated, ValidationErrorResponse> {
+        let mut builder = ValidationErrorsBuilder::new();
+
+        let display_name = builder
+            .add_item_from_trait(DisplayName::parse(self.display_name.clone(), None))
+            .unwrap_or_default();
+        let time_zone = builder
+            .add_item_from_trait(TimeZone::parse(self.time_zone.clone(), None))
+            .unwrap_or_default();
+        let locale = builder
+            .add_item_from_trait(parse_locale(&self.locale, None))
+            .unwrap_or_default();
+        let theme = builder
+            .add_item_from_trait(parse_theme(&self.theme, None))
+            .unwrap_or_default();
+
+        builder.build_result()?;
+
+        Ok(UserProfileFormValidated {
+            display_name,
+            time_zone,
+            locale,
+            theme,
+        })
+    }
+
+    pub fn html_form(
+        title: String,
+        context_html_builder: &ContextHtmlBuilder,
+        user_context: &UserContext,
+        user_profile_form: Option<UserProfileForm>,
+        errors: Option<HashMap<String, ValidateErrorItem>>,
+        avatar_max_kib: usize,
+    ) -> Markup {
+        let user_profile_form =
+            user_profile_form.unwrap_or_else(|| Self::from_profile(&user_context.profile));
+        let errors = errors.unwrap_or_default();
+        context_html_builder
+            .attach_title(title.clone())
+            .set_current_tag("user".to_string())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                form method="post" .form {
+                    (context_html_builder.csrf_input())
+                    label for="display_name" { "Display name" }
+                    input .form-item type="text" id="display_name" name="display_name" placeholder=(user_context.username) value=(user_profile_form.display_name);
+                    (errors.get("display_name").as_html())
+                    label for="time_zone" { "Time zone" }
+                    input .form-item type="text" id="time_zone" name="time_zone" list="time_zones" value=(user_profile_form.time_zone);
+                    datalist id="time_zones" {
+                        @for tz in chrono_tz::TZ_VARIANTS {
+                            option value=(tz.name()) {}
+                        }
+                    }
+                    (errors.get("time_zone").as_html())
+                    label for="locale" { "Date format" }
+                    select .form-item id="locale" name="locale" {
+                        @for locale in Locale::ALL {
+                            option value=(locale.as_str()) selected[locale.as_str() == user_profile_form.locale] { (locale.label()) }
+                        }
+                    }
+                    (errors.get("locale").as_html())
+                    label for="theme" { "Theme" }
+                    select .form-item id="theme" name="theme" {
+                        @for theme in Theme::ALL {
+                            option value=(theme.as_str()) selected[theme.as_str() == user_profile_form.theme] { (theme.label()) }
+                        }
+                    }
+                    (errors.get("theme").as_html())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Save profile" };
+                }
+                h2 .mt-5 { "Avatar" }
+                @if let Some(avatar_url) = user_context.profile.avatar_url(user_context.id) {
+                    img .mt-3 src=(avatar_url) alt="Your avatar" width="96" height="96";
+                    form method="post" action="/user/profile/avatar/delete" {
+                        (context_html_builder.csrf_input())
+                        button .btn .btn-sky-blue .mt-3 type="submit" { "Remove avatar" }
+                    }
+                }
+                p .mt-3 { "PNG, JPEG, GIF or WebP, at most " (avatar_max_kib) " KiB." }
+                form method="post" action="/user/profile/avatar" enctype="multipart/form-data" .form {
+                    (context_html_builder.csrf_input())
+                    input .form-item type="file" name="avatar" accept="image/png,image/jpeg,image/gif,image/webp";
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Upload avatar" };
+                }
+            })
+            .build()
+    }
+}
+
+#[derive(FromForm)]
+pub struct UserAvatarForm<'r> {
+    /// Defaulted, so a missing token is answered like on every other form.
+    #[field(default = String::new())]
+    pub csrf_token: String,
+    pub avatar: TempFile<'r>,
+}
+
+impl CsrfField for UserAvatarForm<'_> {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
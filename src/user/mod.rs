@@ -1,9 +1,18 @@
+pub mod api_token;
+pub mod breached_password;
 pub mod dependency;
+pub mod email_verification;
+pub mod external_identity;
 pub mod flag;
 pub mod form;
 pub mod model;
 pub mod password;
+pub mod profile;
 pub mod repository;
 pub mod route;
 pub mod service;
+pub mod session_cookie;
+pub mod throttle;
+pub mod token;
+pub mod totp;
 pub mod validate;
@@ -1,16 +1,79 @@
+use crate::dependency::ApiScope;
+use crate::oidc::OidcError;
+use crate::user::profile::{Locale, Theme, UserProfile};
+use crate::user::validate::email::Email;
+use crate::user::validate::invite::InviteCode;
 use crate::user::validate::password::Password;
+use crate::user::validate::profile::{DisplayName, TimeZone};
 use crate::user::validate::username::Username;
+use chrono::{DateTime, Utc};
+use error_stack::Report;
+use serde::Serialize;
 
 #[derive(Debug)]
 pub struct UserContext {
     pub id: i64,
     pub is_user: bool,
     pub username: String,
+    /// Only sessions carry admin rights, personal access tokens never do.
+    pub is_admin: bool,
+    pub auth: UserAuth,
+    pub profile: UserProfile,
+}
+
+impl UserContext {
+    /// How pages address the user, the username unless a display name is set.
+    pub fn display_name(&self) -> &str {
+        self.profile
+            .display_name
+            .as_deref()
+            .unwrap_or(&self.username)
+    }
+
+    /// In the time zone and date format the user picked.
+    pub fn format_datetime(&self, datetime: DateTime<Utc>) -> String {
+        self.profile.format_datetime(datetime)
+    }
+}
+
+/// How the request was authenticated.
+#[derive(Debug)]
+pub enum UserAuth {
+    Visitor,
+    /// The `login-token` cookie.
+    Session,
+    /// A personal access token in the `Authorization: Bearer` header.
+    ApiToken {
+        scopes: Box<[ApiScope]>,
+    },
+    /// A bearer token was sent, but it is unknown or expired.
+    InvalidApiToken,
 }
 
 pub struct IdPassword {
     pub id: i64,
     pub password: Box<[u8]>,
+    pub must_reset_password: bool,
+    /// Has an address that was never verified, accounts without one are not affected.
+    pub email_unverified: bool,
+}
+
+pub struct SessionUser {
+    pub id: i64,
+    pub username: String,
+    pub is_admin: bool,
+}
+
+pub struct IdEmail {
+    pub id: i64,
+    pub email: Option<String>,
+}
+
+pub struct UserEmailStatus {
+    pub id: i64,
+    pub username: String,
+    pub email: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 }
 
 pub struct IdUsername {
@@ -18,8 +81,219 @@ pub struct IdUsername {
     pub username: String,
 }
 
+pub enum LoginSuccess {
+    Token(String),
+    /// Password passed, the challenge token is exchanged for a login token with a TOTP code.
+    SecondFactor(String),
+}
+
+pub enum LoginFailure {
+    Invalid,
+    Backoff {
+        retry_after_seconds: i64,
+    },
+    Locked {
+        until: DateTime<Utc>,
+    },
+    /// The password hash pool had no free slot in time.
+    Busy,
+    /// The password was right, but an admin asked for a new one.
+    PasswordResetRequired,
+    /// The password was right, but the address has to be verified first.
+    EmailNotVerified,
+}
+
+impl LoginFailure {
+    /// Reason stored with a failed login in the auth events.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginFailure::Invalid => "invalid_credentials",
+            LoginFailure::Backoff { .. } => "throttled",
+            LoginFailure::Locked { .. } => "locked",
+            LoginFailure::Busy => "busy",
+            LoginFailure::PasswordResetRequired => "password_reset_required",
+            LoginFailure::EmailNotVerified => "email_not_verified",
+        }
+    }
+}
+
+pub struct UserTotp {
+    pub secret: Option<String>,
+    pub pending_secret: Option<String>,
+}
+
+pub struct LoginChallenge {
+    pub user_id: i64,
+    pub username: String,
+    pub totp_secret: Option<String>,
+}
+
+pub struct UserSession {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub user_agent: String,
+    pub ip: String,
+    pub current: bool,
+}
+
+pub struct ApiTokenUser {
+    pub id: i64,
+    pub username: String,
+    pub scopes: Box<[ApiScope]>,
+}
+
+pub struct UserApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Box<[ApiScope]>,
+    pub expire_after: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthEventKind {
+    Login,
+    Logout,
+    Register,
+    PasswordChange,
+    PasswordReset,
+}
+
+impl AuthEventKind {
+    /// Name stored in the auth events.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventKind::Login => "login",
+            AuthEventKind::Logout => "logout",
+            AuthEventKind::Register => "register",
+            AuthEventKind::PasswordChange => "password_change",
+            AuthEventKind::PasswordReset => "password_reset",
+        }
+    }
+
+    pub fn label(event: &str) -> &str {
+        match event {
+            "login" => "Login",
+            "logout" => "Logout",
+            "register" => "Registration",
+            "password_change" => "Password change",
+            "password_reset" => "Password reset",
+            _ => event,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthOutcome {
+    Success,
+    Failure,
+}
+
+impl AuthOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthOutcome::Success => "success",
+            AuthOutcome::Failure => "failure",
+        }
+    }
+}
+
+/// Row of the append-only `auth_events` table.
+#[derive(Serialize)]
+pub struct AuthEvent {
+    pub id: i64,
+    pub event: String,
+    pub outcome: String,
+    /// Empty for failed logins with an unknown username.
+    pub user_id: Option<i64>,
+    /// As entered, for failed logins.
+    pub username: String,
+    pub ip: String,
+    pub user_agent: String,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct UserInvite {
+    pub id: i64,
+    pub note: String,
+    pub max_uses: i64,
+    pub uses: i64,
+    pub expire_after: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Empty once the admin who created it was deleted.
+    pub created_by: Option<String>,
+}
+
+/// Pending single sign-on, from the redirect to the provider until its callback.
+pub struct OidcLogin {
+    pub nonce: String,
+    pub code_verifier: String,
+    /// Set when a logged in user links an identity instead of logging in.
+    pub link_user_id: Option<i64>,
+}
+
+pub struct ExternalIdentity {
+    pub id: i64,
+    pub issuer: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub enum OidcLoginSuccess {
+    Token(String),
+    /// The identity was linked to the logged in user.
+    Linked,
+}
+
+pub enum OidcLoginFailure {
+    /// Unknown, expired or already used state, or it was not started in this browser.
+    InvalidState,
+    /// The identity belongs to no user and auto registration is off or registration not open.
+    NotLinked,
+    LinkedToOtherUser,
+    /// The identity is linked, but an admin asked for a new password.
+    PasswordResetRequired,
+    Provider(Report<OidcError>),
+    Failed,
+}
+
+pub struct UserChangePasswordFormValidated {
+    pub current_password: Password,
+    pub password: Password,
+    pub password_confirm: Password,
+}
+
+pub struct UserPasswordResetFormValidated {
+    pub password: Password,
+    pub password_confirm: Password,
+}
+
+pub struct UserAvatar {
+    pub content_type: String,
+    pub data: Box<[u8]>,
+}
+
+pub enum AvatarFailure {
+    TooLarge,
+    UnsupportedType,
+    Failed,
+}
+
+pub struct UserProfileFormValidated {
+    pub display_name: DisplayName,
+    pub time_zone: TimeZone,
+    pub locale: Locale,
+    pub theme: Theme,
+}
+
 pub struct UserRegisterFormValidated {
     pub username: Username,
+    pub email: Email,
     pub password: Password,
     pub password_confirm: Password,
+    /// Only with invite-only registration.
+    pub invite_code: Option<InviteCode>,
 }
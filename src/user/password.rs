@@ -1,9 +1,18 @@
+use crate::config::PasswordHashConfig;
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::error::ErrorStatus;
 use argon2::password_hash::SaltString;
 use argon2::password_hash::rand_core::OsRng;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use error_stack::Report;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use error_stack::{Report, ResultExt};
+use rocket::http::Status;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
 
 #[derive(Error, Debug)]
 #[error("Password Error: {0}")]
@@ -29,13 +38,148 @@ impl PasswordState {
     }
 
     pub fn is_valid_rehashed(&self) -> bool {
+        matches!(self, PasswordState::ValidRehashed(_))
+    }
+}
+
+/// Argon2id parameters for new hashes, built from the `password_hash` config.
+#[derive(Clone, Default)]
+pub struct PasswordHashParams(Params);
+
+impl PasswordHashParams {
+    pub fn new(config: &PasswordHashConfig) -> Result<Self, Report<PasswordError>> {
+        Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map(Self)
+        .map_err(|e| Report::new(PasswordError(format!("Invalid Argon2 parameters: {}", e))))
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.0.clone())
+    }
+
+    /// Whether a verified hash was made with other settings than the current ones.
+    fn is_outdated(&self, hash: &PasswordHash) -> bool {
+        let Ok(params) = Params::try_from(hash) else {
+            return true;
+        };
+
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.0.m_cost()
+            || params.t_cost() != self.0.t_cost()
+            || params.p_cost() != self.0.p_cost()
+            || params.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN)
+                != self.0.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PasswordHashPoolError {
+    #[error("The server is busy, please try again in a moment.")]
+    Busy,
+    #[error("Password hashing failed")]
+    Failed,
+}
+
+impl ErrorStatus for PasswordHashPoolError {
+    fn error_status(&self) -> Status {
         match self {
-            PasswordState::ValidRehashed(_) => true,
-            _ => false,
+            PasswordHashPoolError::Busy => Status::ServiceUnavailable,
+            PasswordHashPoolError::Failed => Status::InternalServerError,
         }
     }
 }
 
+/// Runs Argon2 on Tokio's blocking threads, so the async workers keep serving requests.
+/// The semaphore bounds the memory and CPU spent on hashing at once.
+#[derive(Clone)]
+pub struct PasswordHashPool {
+    params: PasswordHashParams,
+    permits: Arc<Semaphore>,
+    wait_timeout: Duration,
+}
+
+impl PasswordHashPool {
+    pub fn new(params: PasswordHashParams, max_concurrent: usize, wait_timeout: Duration) -> Self {
+        Self {
+            params,
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            wait_timeout,
+        }
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T, Report<PasswordHashPoolError>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PasswordHashParams) -> Result<T, Report<PasswordError>> + Send + 'static,
+    {
+        let permit = timeout(self.wait_timeout, Arc::clone(&self.permits).acquire_owned())
+            .await
+            .map_err(|_| Report::new(PasswordHashPoolError::Busy))?
+            .change_context(PasswordHashPoolError::Failed)?;
+
+        let params = self.params.clone();
+        spawn_blocking(move || {
+            let _permit = permit;
+            f(&params)
+        })
+        .await
+        .change_context(PasswordHashPoolError::Failed)?
+        .change_context(PasswordHashPoolError::Failed)
+    }
+
+    pub async fn hash_password(
+        &self,
+        password: String,
+    ) -> Result<Box<[u8]>, Report<PasswordHashPoolError>> {
+        self.run(move |params| Password::hash_password(password, params)?.encode_to_msg_pack())
+            .await
+    }
+
+    pub async fn verify_password(
+        &self,
+        password_hash: Box<[u8]>,
+        password: String,
+    ) -> Result<PasswordState, Report<PasswordHashPoolError>> {
+        self.run(move |params| Password::verify_password(password_hash, password, params))
+            .await
+    }
+}
+
+static PASSWORD_HASH_POOL: OnceCell<PasswordHashPool> = OnceCell::const_new();
+
+impl FromGlobalContext for PasswordHashPool {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let password_hash_pool = PASSWORD_HASH_POOL
+            .get_or_try_init(|| async {
+                let config = &dependency_global_context
+                    .global_context
+                    .config
+                    .password_hash;
+                let params = PasswordHashParams::new(config).change_context(
+                    DependencyError::Other("Invalid password_hash config".to_string()),
+                )?;
+
+                Ok::<_, Report<DependencyError>>(Self::new(
+                    params,
+                    config.max_concurrent,
+                    Duration::from_millis(config.wait_timeout_ms),
+                ))
+            })
+            .await?;
+
+        Ok(password_hash_pool.clone())
+    }
+}
+
+/// Bump the version for a new scheme, hashes of older versions are rehashed on login.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "version")]
 pub enum Password {
@@ -44,10 +188,13 @@ pub enum Password {
 }
 
 impl Password {
-    pub fn hash_password(password: String) -> Result<Self, Report<PasswordError>> {
+    pub fn hash_password(
+        password: String,
+        params: &PasswordHashParams,
+    ) -> Result<Self, Report<PasswordError>> {
         let salt = SaltString::generate(&mut OsRng);
 
-        let argon2 = Argon2::default();
+        let argon2 = params.argon2();
 
         let password_hash = argon2
             .hash_password(password.as_bytes(), &salt)
@@ -59,34 +206,150 @@ impl Password {
         })
     }
 
+    /// A valid password with an outdated hash comes back as `ValidRehashed` with a fresh hash,
+    /// the caller should store it.
     pub fn verify_password(
         password_hash: Box<[u8]>,
         password: String,
+        params: &PasswordHashParams,
     ) -> Result<PasswordState, Report<PasswordError>> {
         let password_data = rmp_serde::from_slice::<Password>(&password_hash)
             .map_err(|_| PasswordError("Failed to deserialize password hash".to_string()))?;
 
-        match password_data {
+        let outdated = match password_data {
             Password::Version1 { argon2 } => {
                 let parsed_hash = PasswordHash::new(&argon2)
                     .map_err(|_| PasswordError("Failed to parse password hash".to_string()))?;
 
-                match Argon2::default().verify_password(password.as_bytes(), &parsed_hash) {
-                    Ok(_) => Ok(PasswordState::Valid),
-                    Err(_) => Ok(PasswordState::Invalid),
+                // The hash carries its own parameters, so old hashes still verify.
+                if Argon2::default()
+                    .verify_password(password.as_bytes(), &parsed_hash)
+                    .is_err()
+                {
+                    return Ok(PasswordState::Invalid);
                 }
+
+                params.is_outdated(&parsed_hash)
             }
+        };
+
+        if outdated {
+            Ok(PasswordState::ValidRehashed(Self::hash_password(
+                password, params,
+            )?))
+        } else {
+            Ok(PasswordState::Valid)
         }
     }
 
     pub fn encode_to_msg_pack(&self) -> Result<Box<[u8]>, Report<PasswordError>> {
         Ok(rmp_serde::to_vec_named(self)
-            .map_err(|e| {
-                PasswordError(format!(
-                    "Failed to serialize password hash: {}",
-                    e.to_string()
-                ))
-            })?
+            .map_err(|e| PasswordError(format!("Failed to serialize password hash: {}", e)))?
             .into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(memory_kib: u32, iterations: u32) -> PasswordHashParams {
+        PasswordHashParams::new(&PasswordHashConfig {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+            ..PasswordHashConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn hash(password: &str, params: &PasswordHashParams) -> Box<[u8]> {
+        Password::hash_password(password.to_string(), params)
+            .unwrap()
+            .encode_to_msg_pack()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let params = params(1024, 1);
+        let password_hash = hash("Hello@Wor1d", &params);
+
+        let state =
+            Password::verify_password(password_hash.clone(), "Hello@Wor1d".to_string(), &params)
+                .unwrap();
+        assert!(matches!(state, PasswordState::Valid));
+
+        let state =
+            Password::verify_password(password_hash, "hello@wor1d".to_string(), &params).unwrap();
+        assert!(state.is_invalid());
+    }
+
+    #[test]
+    fn test_verify_password_rehash_outdated_params() {
+        let old_params = params(1024, 1);
+        let new_params = params(2048, 2);
+        let password_hash = hash("Hello@Wor1d", &old_params);
+
+        let state =
+            Password::verify_password(password_hash, "Hello@Wor1d".to_string(), &new_params)
+                .unwrap();
+        let PasswordState::ValidRehashed(rehashed) = state else {
+            panic!("Expected the password to be rehashed");
+        };
+
+        let state = Password::verify_password(
+            rehashed.encode_to_msg_pack().unwrap(),
+            "Hello@Wor1d".to_string(),
+            &new_params,
+        )
+        .unwrap();
+        assert!(matches!(state, PasswordState::Valid));
+    }
+
+    #[test]
+    fn test_verify_password_no_rehash_when_invalid() {
+        let password_hash = hash("Hello@Wor1d", &params(1024, 1));
+
+        let state = Password::verify_password(password_hash, "wrong".to_string(), &params(2048, 2))
+            .unwrap();
+        assert!(state.is_invalid());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert!(
+            PasswordHashParams::new(&PasswordHashConfig {
+                memory_kib: 1,
+                iterations: 0,
+                parallelism: 1,
+                ..PasswordHashConfig::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_hash_and_verify() {
+        let pool = PasswordHashPool::new(params(1024, 1), 1, Duration::from_secs(5));
+        let password_hash = pool.hash_password("Hello@Wor1d".to_string()).await.unwrap();
+
+        let state = pool
+            .verify_password(password_hash, "Hello@Wor1d".to_string())
+            .await
+            .unwrap();
+        assert!(matches!(state, PasswordState::Valid));
+    }
+
+    #[tokio::test]
+    async fn test_pool_busy() {
+        let pool = PasswordHashPool::new(params(1024, 1), 1, Duration::from_millis(10));
+        let _permit = Arc::clone(&pool.permits).acquire_owned().await.unwrap();
+
+        let err = pool
+            .hash_password("Hello@Wor1d".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), PasswordHashPoolError::Busy));
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Follows the light or dark setting of the browser.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::System => "Same as the browser",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|theme| theme.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    EnUs,
+    EnGb,
+    DeDe,
+    FrFr,
+    JaJp,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 5] = [
+        Locale::EnUs,
+        Locale::EnGb,
+        Locale::DeDe,
+        Locale::FrFr,
+        Locale::JaJp,
+    ];
+
+    /// BCP 47 tag, also used for the `lang` attribute of pages.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::EnUs => "en-US",
+            Locale::EnGb => "en-GB",
+            Locale::DeDe => "de-DE",
+            Locale::FrFr => "fr-FR",
+            Locale::JaJp => "ja-JP",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Locale::EnUs => "English (United States)",
+            Locale::EnGb => "English (United Kingdom)",
+            Locale::DeDe => "Deutsch (Deutschland)",
+            Locale::FrFr => "Français (France)",
+            Locale::JaJp => "日本語 (日本)",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.as_str().eq_ignore_ascii_case(value))
+    }
+
+    fn datetime_format(&self) -> &'static str {
+        match self {
+            Locale::EnUs => "%m/%d/%Y %I:%M %p",
+            Locale::EnGb | Locale::FrFr => "%d/%m/%Y %H:%M",
+            Locale::DeDe => "%d.%m.%Y %H:%M",
+            Locale::JaJp => "%Y/%m/%d %H:%M",
+        }
+    }
+}
+
+/// Settings of a user, visitors and users without a saved profile get the defaults.
+#[derive(Debug, Clone)]
+pub struct UserProfile {
+    pub display_name: Option<String>,
+    pub time_zone: Tz,
+    pub locale: Locale,
+    pub theme: Theme,
+    /// Changes with every upload, so the avatar url does too.
+    pub avatar_updated_at: Option<DateTime<Utc>>,
+}
+
+impl Default for UserProfile {
+    fn default() -> Self {
+        Self {
+            display_name: None,
+            time_zone: Tz::UTC,
+            locale: Locale::default(),
+            theme: Theme::default(),
+            avatar_updated_at: None,
+        }
+    }
+}
+
+impl UserProfile {
+    pub fn format_datetime(&self, datetime: DateTime<Utc>) -> String {
+        datetime
+            .with_timezone(&self.time_zone)
+            .format(&format!("{} %Z", self.locale.datetime_format()))
+            .to_string()
+    }
+
+    pub fn avatar_url(&self, user_id: i64) -> Option<String> {
+        self.avatar_updated_at
+            .map(|updated_at| format!("/user/avatar/{}?v={}", user_id, updated_at.timestamp()))
+    }
+}
+
+/// Content type of an uploaded avatar, judged by its first bytes and not by what the browser
+/// claims. Anything but PNG, JPEG, GIF and WebP is refused.
+pub fn sniff_avatar(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_theme_parse() {
+        assert_eq!(Theme::parse("dark"), Some(Theme::Dark));
+        assert_eq!(Theme::parse("blue"), None);
+    }
+
+    #[test]
+    fn test_locale_parse() {
+        assert_eq!(Locale::parse("de-DE"), Some(Locale::DeDe));
+        assert_eq!(Locale::parse("en-gb"), Some(Locale::EnGb));
+        assert_eq!(Locale::parse("xx-XX"), None);
+    }
+
+    #[test]
+    fn test_format_datetime() {
+        let datetime = Utc.with_ymd_and_hms(2025, 1, 31, 18, 5, 0).unwrap();
+        assert_eq!(
+            UserProfile::default().format_datetime(datetime),
+            "01/31/2025 06:05 PM UTC"
+        );
+
+        let profile = UserProfile {
+            time_zone: "Europe/Berlin".parse().unwrap(),
+            locale: Locale::DeDe,
+            ..UserProfile::default()
+        };
+        assert_eq!(profile.format_datetime(datetime), "31.01.2025 19:05 CET");
+
+        let profile = UserProfile {
+            time_zone: "Asia/Tokyo".parse().unwrap(),
+            locale: Locale::JaJp,
+            ..UserProfile::default()
+        };
+        assert_eq!(profile.format_datetime(datetime), "2025/02/01 03:05 JST");
+    }
+
+    #[test]
+    fn test_avatar_url() {
+        assert_eq!(UserProfile::default().avatar_url(3), None);
+        let profile = UserProfile {
+            avatar_updated_at: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            ..UserProfile::default()
+        };
+        assert_eq!(
+            profile.avatar_url(3).unwrap(),
+            "/user/avatar/3?v=1735689600"
+        );
+    }
+
+    #[test]
+    fn test_sniff_avatar() {
+        assert_eq!(
+            sniff_avatar(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some("image/png")
+        );
+        assert_eq!(sniff_avatar(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(sniff_avatar(b"GIF89a\x01\0"), Some("image/gif"));
+        assert_eq!(sniff_avatar(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_avatar(b"<svg xmlns="), None);
+        assert_eq!(sniff_avatar(b""), None);
+    }
+}
@@ -1,8 +1,19 @@
 use crate::db::SqliteClient;
-use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
-use crate::user::model::{IdPassword, IdUsername};
+use crate::dependency::{ApiScope, DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::error::ErrorStatus;
+use crate::user::api_token::{format_scopes, parse_scopes};
+use crate::user::model::{
+    ApiTokenUser, AuthEvent, AuthEventKind, AuthOutcome, ExternalIdentity, IdEmail, IdPassword,
+    IdUsername, LoginChallenge, OidcLogin, SessionUser, UserApiToken, UserAvatar, UserEmailStatus,
+    UserInvite, UserSession, UserTotp,
+};
+use crate::user::profile::{Locale, Theme, UserProfile};
+use crate::user::throttle::LoginAttempt;
+use crate::user::token::{SecretCipher, TokenHasher};
+use crate::user::validate::username::username_key;
 use error_stack::{Report, ResultExt};
-use rusqlite::named_params;
+use rocket::http::Status;
+use rusqlite::{Row, named_params};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,19 +28,71 @@ pub enum UserRepositoryError {
     NotFoundError,
 }
 
+impl ErrorStatus for UserRepositoryError {
+    fn error_status(&self) -> Status {
+        match self {
+            UserRepositoryError::NotFoundError => Status::NotFound,
+            _ => Status::InternalServerError,
+        }
+    }
+}
+
+/// Shared with the admin search over all events.
+pub fn map_auth_event(row: &Row) -> rusqlite::Result<AuthEvent> {
+    Ok(AuthEvent {
+        id: row.get("id")?,
+        event: row.get("event")?,
+        outcome: row.get("outcome")?,
+        user_id: row.get("user_id")?,
+        username: row.get("username")?,
+        ip: row.get("ip")?,
+        user_agent: row.get("user_agent")?,
+        detail: row.get("detail")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
 pub struct UserRepository {
     sqlite_client: SqliteClient,
+    token_hasher: TokenHasher,
+    secret_cipher: SecretCipher,
+}
+
+/// Additional data of the TOTP secrets of a user, a secret only decrypts for its own row.
+fn totp_aad(id: i64) -> Vec<u8> {
+    format!("totp:{id}").into_bytes()
 }
 
 impl UserRepository {
-    pub fn new(sqlite_client: SqliteClient) -> Self {
-        Self { sqlite_client }
+    pub fn new(sqlite_client: SqliteClient, token_hasher: TokenHasher) -> Self {
+        Self {
+            sqlite_client,
+            secret_cipher: token_hasher.secret_cipher(),
+            token_hasher,
+        }
+    }
+
+    fn decrypt_totp_secret(
+        &self,
+        id: i64,
+        stored: Option<String>,
+    ) -> Result<Option<String>, Report<UserRepositoryError>> {
+        stored
+            .map(|stored| {
+                self.secret_cipher
+                    .decrypt(&stored, &totp_aad(id))
+                    .ok_or_else(|| Report::new(UserRepositoryError::RowValueError))
+            })
+            .transpose()
     }
 
     pub fn add_token(
         &self,
         token: String,
         user_id: i64,
+        user_agent: String,
+        ip: String,
+        lifetime_days: u32,
     ) -> Result<(), Report<UserRepositoryError>> {
         let conn = self
             .sqlite_client
@@ -40,8 +103,11 @@ impl UserRepository {
         conn.execute(
             include_str!("_sql/add_token.sql"),
             named_params! {
-                ":token": token,
+                ":token_hash": self.token_hasher.hash(&token),
                 ":user_id": user_id,
+                ":user_agent": user_agent,
+                ":ip": ip,
+                ":lifetime_days": lifetime_days,
             },
         )
         .change_context(UserRepositoryError::QueryError)?;
@@ -59,7 +125,7 @@ impl UserRepository {
         conn.execute(
             include_str!("_sql/delete_token.sql"),
             named_params! {
-                ":token": token,
+                ":token_hash": self.token_hasher.hash(&token),
             },
         )
         .change_context(UserRepositoryError::QueryError)?;
@@ -67,7 +133,7 @@ impl UserRepository {
         Ok(())
     }
 
-    pub fn find_by_token(&self, token: String) -> Result<IdUsername, Report<UserRepositoryError>> {
+    pub fn find_by_token(&self, token: String) -> Result<SessionUser, Report<UserRepositoryError>> {
         let conn = self
             .sqlite_client
             .get_conn()
@@ -81,12 +147,13 @@ impl UserRepository {
         let mut item_iter = stmt
             .query_map(
                 named_params! {
-                    ":token": token,
+                    ":token_hash": self.token_hasher.hash(&token),
                 },
                 |row| {
-                    Ok(IdUsername {
+                    Ok(SessionUser {
                         id: row.get("id")?,
                         username: row.get("username")?,
+                        is_admin: row.get("is_admin")?,
                     })
                 },
             )
@@ -99,10 +166,39 @@ impl UserRepository {
         item.change_context(UserRepositoryError::RowValueError)
     }
 
-    pub fn get_user_password(
+    /// Bumps `last_seen`, at most once a minute to spare the writes. A sliding session also
+    /// gets a new expiry, `true` when the row was written.
+    pub fn touch_token(
         &self,
-        username: String,
-    ) -> Result<IdPassword, Report<UserRepositoryError>> {
+        token: String,
+        sliding: bool,
+        lifetime_days: u32,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let changed = conn
+            .execute(
+                include_str!("_sql/touch_token.sql"),
+                named_params! {
+                    ":token_hash": self.token_hasher.hash(&token),
+                    ":sliding": sliding,
+                    ":lifetime_days": lifetime_days,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(changed > 0)
+    }
+
+    pub fn get_sessions(
+        &self,
+        user_id: i64,
+        token: String,
+    ) -> Result<Box<[UserSession]>, Report<UserRepositoryError>> {
         let conn = self
             .sqlite_client
             .get_conn()
@@ -110,23 +206,64 @@ impl UserRepository {
             .map_err(|_| Report::new(UserRepositoryError::LockError))?;
 
         let mut stmt = conn
-            .prepare(include_str!("_sql/get_user_password.sql"))
+            .prepare(include_str!("_sql/get_sessions.sql"))
             .change_context(UserRepositoryError::QueryError)?;
 
-        let mut item_iter = stmt
+        let item_iter = stmt
             .query_map(
                 named_params! {
-                    ":username": username,
+                    ":user_id": user_id,
+                    ":token_hash": self.token_hasher.hash(&token),
                 },
                 |row| {
-                    Ok(IdPassword {
+                    Ok(UserSession {
                         id: row.get("id")?,
-                        password: row.get("password")?,
+                        created_at: row.get("created_at")?,
+                        last_seen: row.get("last_seen")?,
+                        user_agent: row.get("user_agent")?,
+                        ip: row.get("ip")?,
+                        current: row.get("current")?,
                     })
                 },
             )
             .change_context(UserRepositoryError::QueryError)?;
 
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(UserRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    /// Returns whether the deleted session was the one belonging to `token`.
+    pub fn delete_session(
+        &self,
+        id: i64,
+        user_id: i64,
+        token: String,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/delete_session.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":id": id,
+                    ":user_id": user_id,
+                    ":token_hash": self.token_hasher.hash(&token),
+                },
+                |row| row.get("current"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
         let item = item_iter
             .next()
             .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
@@ -134,10 +271,13 @@ impl UserRepository {
         item.change_context(UserRepositoryError::RowValueError)
     }
 
-    pub fn register_user(
+    pub fn add_api_token(
         &self,
-        username: String,
-        password: Box<[u8]>,
+        user_id: i64,
+        name: String,
+        token: String,
+        scopes: &[ApiScope],
+        expires_in_days: u32,
     ) -> Result<(), Report<UserRepositoryError>> {
         let conn = self
             .sqlite_client
@@ -146,10 +286,13 @@ impl UserRepository {
             .map_err(|_| Report::new(UserRepositoryError::LockError))?;
 
         conn.execute(
-            include_str!("_sql/register_user.sql"),
+            include_str!("_sql/add_api_token.sql"),
             named_params! {
-                ":username": username,
-                ":password": password,
+                ":user_id": user_id,
+                ":name": name,
+                ":token_hash": self.token_hasher.hash(&token),
+                ":scopes": format_scopes(scopes),
+                ":expires_in_days": expires_in_days,
             },
         )
         .change_context(UserRepositoryError::QueryError)?;
@@ -157,7 +300,10 @@ impl UserRepository {
         Ok(())
     }
 
-    pub fn username_taken(&self, username: String) -> Result<bool, Report<UserRepositoryError>> {
+    pub fn get_api_tokens(
+        &self,
+        user_id: i64,
+    ) -> Result<Box<[UserApiToken]>, Report<UserRepositoryError>> {
         let conn = self
             .sqlite_client
             .get_conn()
@@ -165,15 +311,145 @@ impl UserRepository {
             .map_err(|_| Report::new(UserRepositoryError::LockError))?;
 
         let mut stmt = conn
-            .prepare(include_str!("_sql/username_taken.sql"))
+            .prepare(include_str!("_sql/get_api_tokens.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                },
+                |row| {
+                    Ok(UserApiToken {
+                        id: row.get("id")?,
+                        name: row.get("name")?,
+                        scopes: parse_scopes(&row.get::<_, String>("scopes")?),
+                        expire_after: row.get("expire_after")?,
+                        created_at: row.get("created_at")?,
+                        last_used: row.get("last_used")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(UserRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    /// Returns `false` when the token does not exist or belongs to another user.
+    pub fn delete_api_token(
+        &self,
+        id: i64,
+        user_id: i64,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let deleted = conn
+            .execute(
+                include_str!("_sql/delete_api_token.sql"),
+                named_params! {
+                    ":id": id,
+                    ":user_id": user_id,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn find_by_api_token(
+        &self,
+        token: String,
+    ) -> Result<ApiTokenUser, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/find_by_api_token.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":token_hash": self.token_hasher.hash(&token),
+                },
+                |row| {
+                    Ok(ApiTokenUser {
+                        id: row.get("id")?,
+                        username: row.get("username")?,
+                        scopes: parse_scopes(&row.get::<_, String>("scopes")?),
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Bumps `last_used`, at most once a minute like `touch_token`.
+    pub fn touch_api_token(&self, token: String) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/touch_api_token.sql"),
+            named_params! {
+                ":token_hash": self.token_hasher.hash(&token),
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Matches the username case-insensitively, an exact match wins for older names that
+    /// only differ in case.
+    pub fn get_user_password(
+        &self,
+        username: String,
+    ) -> Result<IdPassword, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_password.sql"))
             .change_context(UserRepositoryError::QueryError)?;
 
         let mut item_iter = stmt
             .query_map(
                 named_params! {
+                    ":username_normalized": username_key(&username),
                     ":username": username,
                 },
-                |row| Ok(row.get("taken")?),
+                |row| {
+                    Ok(IdPassword {
+                        id: row.get("id")?,
+                        password: row.get("password")?,
+                        must_reset_password: row.get("must_reset_password")?,
+                        email_unverified: row.get("email_unverified")?,
+                    })
+                },
             )
             .change_context(UserRepositoryError::QueryError)?;
 
@@ -183,12 +459,1817 @@ impl UserRepository {
 
         item.change_context(UserRepositoryError::RowValueError)
     }
-}
 
-impl FromGlobalContext for UserRepository {
-    async fn from_global_context(
-        dependency_global_context: &DependencyGlobalContext<'_, '_>,
-    ) -> Result<Self, Report<DependencyError>> {
-        Ok(Self::new(dependency_global_context.inject().await?))
+    pub fn get_user_password_by_id(
+        &self,
+        id: i64,
+    ) -> Result<IdPassword, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_password_by_id.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":id": id,
+                },
+                |row| {
+                    Ok(IdPassword {
+                        id: row.get("id")?,
+                        password: row.get("password")?,
+                        must_reset_password: row.get("must_reset_password")?,
+                        email_unverified: row.get("email_unverified")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn update_user_password(
+        &self,
+        id: i64,
+        password: Box<[u8]>,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/update_user_password.sql"),
+            named_params! {
+                ":id": id,
+                ":password": password,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Only swaps the hash of the same password, unlike `update_user_password` a pending
+    /// forced reset stays.
+    pub fn rehash_user_password(
+        &self,
+        id: i64,
+        password: Box<[u8]>,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/rehash_user_password.sql"),
+            named_params! {
+                ":id": id,
+                ":password": password,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn delete_other_tokens(
+        &self,
+        user_id: i64,
+        token: String,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/delete_other_tokens.sql"),
+            named_params! {
+                ":user_id": user_id,
+                ":token_hash": self.token_hasher.hash(&token),
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn delete_all_tokens(&self, user_id: i64) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/delete_all_tokens.sql"),
+            named_params! {
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn get_user_email(&self, username: String) -> Result<IdEmail, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_email.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":username_normalized": username_key(&username),
+                    ":username": username,
+                },
+                |row| {
+                    Ok(IdEmail {
+                        id: row.get("id")?,
+                        email: row.get("email")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Replaces any earlier reset token of the user, so only the latest mail works.
+    pub fn add_password_reset_token(
+        &self,
+        token: String,
+        user_id: i64,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/delete_password_reset_tokens.sql"),
+            named_params! {
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/add_password_reset_token.sql"),
+            named_params! {
+                ":token_hash": self.token_hasher.hash(&token),
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn find_password_reset_token(
+        &self,
+        token: String,
+    ) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/find_password_reset_token.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":token_hash": self.token_hasher.hash(&token),
+                },
+                |row| row.get("user_id"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Deletes the reset token, stores the new password and logs the user out everywhere in one
+    /// transaction, so a token can only be used once and is not lost when the update fails.
+    /// Returns the user of the token.
+    pub fn reset_password_with_token(
+        &self,
+        token: String,
+        password: Box<[u8]>,
+    ) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let user_id: i64 = {
+            let mut stmt = tx
+                .prepare(include_str!("_sql/consume_password_reset_token.sql"))
+                .change_context(UserRepositoryError::QueryError)?;
+
+            let mut item_iter = stmt
+                .query_map(
+                    named_params! {
+                        ":token_hash": self.token_hasher.hash(&token),
+                    },
+                    |row| row.get("user_id"),
+                )
+                .change_context(UserRepositoryError::QueryError)?;
+
+            item_iter
+                .next()
+                .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?
+                .change_context(UserRepositoryError::RowValueError)?
+        };
+
+        tx.execute(
+            include_str!("_sql/update_user_password.sql"),
+            named_params! {
+                ":id": user_id,
+                ":password": password,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/delete_all_tokens.sql"),
+            named_params! {
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(user_id)
+    }
+
+    /// Reads the attempts of `keys` and stores what `update` returns under the same lock, so two
+    /// logins can not both pass the throttle before either one is counted. `None` from `update`
+    /// leaves everything as it is, a `None` attempt is deleted. Returns the attempts as read.
+    pub fn update_login_attempts(
+        &self,
+        keys: &[String],
+        update: impl FnOnce(&[Option<LoginAttempt>]) -> Option<Vec<Option<LoginAttempt>>>,
+    ) -> Result<Vec<Option<LoginAttempt>>, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut attempts = vec![];
+        {
+            let mut stmt = tx
+                .prepare(include_str!("_sql/get_login_attempt.sql"))
+                .change_context(UserRepositoryError::QueryError)?;
+            for key in keys {
+                let attempt = stmt
+                    .query_map(
+                        named_params! {
+                            ":key": key,
+                        },
+                        |row| {
+                            Ok(LoginAttempt {
+                                failures: row.get("failures")?,
+                                last_failure: row.get("last_failure")?,
+                                locked_until: row.get("locked_until")?,
+                            })
+                        },
+                    )
+                    .change_context(UserRepositoryError::QueryError)?
+                    .next()
+                    .transpose()
+                    .change_context(UserRepositoryError::RowValueError)?;
+                attempts.push(attempt);
+            }
+        }
+
+        let Some(updated) = update(&attempts) else {
+            return Ok(attempts);
+        };
+        for (key, attempt) in keys.iter().zip(updated) {
+            match attempt {
+                Some(attempt) => tx.execute(
+                    include_str!("_sql/save_login_attempt.sql"),
+                    named_params! {
+                        ":key": key,
+                        ":failures": attempt.failures,
+                        ":last_failure": attempt.last_failure,
+                        ":locked_until": attempt.locked_until,
+                    },
+                ),
+                None => tx.execute(
+                    include_str!("_sql/delete_login_attempt.sql"),
+                    named_params! {
+                        ":key": key,
+                    },
+                ),
+            }
+            .change_context(UserRepositoryError::QueryError)?;
+        }
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(attempts)
+    }
+
+    pub fn delete_login_attempt(&self, key: String) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/delete_login_attempt.sql"),
+            named_params! {
+                ":key": key,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn add_login_lockout(
+        &self,
+        key: String,
+        attempt: &LoginAttempt,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_login_lockout.sql"),
+            named_params! {
+                ":key": key,
+                ":failures": attempt.failures,
+                ":locked_until": attempt.locked_until,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Secrets stored before they were encrypted get encrypted on the way.
+    pub fn get_user_totp(&self, id: i64) -> Result<UserTotp, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let (secret, pending_secret) = Self::get_stored_totp(&conn, id)?;
+
+        let is_plaintext = |stored: &Option<String>| {
+            stored
+                .as_deref()
+                .is_some_and(|stored| !SecretCipher::is_encrypted(stored))
+        };
+        if is_plaintext(&secret) || is_plaintext(&pending_secret) {
+            let encrypt = |stored: &Option<String>| {
+                stored
+                    .as_deref()
+                    .map(|stored| match SecretCipher::is_encrypted(stored) {
+                        true => stored.to_string(),
+                        false => self.secret_cipher.encrypt(stored, &totp_aad(id)),
+                    })
+            };
+            conn.execute(
+                include_str!("_sql/update_totp_secrets.sql"),
+                named_params! {
+                    ":id": id,
+                    ":secret": encrypt(&secret),
+                    ":pending_secret": encrypt(&pending_secret),
+                    ":old_secret": secret,
+                    ":old_pending_secret": pending_secret,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+        }
+
+        Ok(UserTotp {
+            secret: self.decrypt_totp_secret(id, secret)?,
+            pending_secret: self.decrypt_totp_secret(id, pending_secret)?,
+        })
+    }
+
+    /// The secret and the pending secret as stored, encrypted or not.
+    fn get_stored_totp(
+        conn: &rusqlite::Connection,
+        id: i64,
+    ) -> Result<(Option<String>, Option<String>), Report<UserRepositoryError>> {
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_totp.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":id": id,
+                },
+                |row| Ok((row.get("totp_secret")?, row.get("totp_pending_secret")?)),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn set_totp_pending_secret(
+        &self,
+        id: i64,
+        secret: String,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/set_totp_pending_secret.sql"),
+            named_params! {
+                ":id": id,
+                ":secret": self.secret_cipher.encrypt(&secret, &totp_aad(id)),
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Promotes the pending secret and replaces the recovery codes, `false` if the pending
+    /// secret changed in the meantime.
+    pub fn enable_totp(
+        &self,
+        id: i64,
+        secret: String,
+        step: i64,
+        recovery_codes: &[String],
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        // Every encryption differs, so the stored value is matched instead of `secret`.
+        let (_, stored_pending_secret) = Self::get_stored_totp(&tx, id)?;
+        let Some(stored_pending_secret) = stored_pending_secret else {
+            return Ok(false);
+        };
+        if self.decrypt_totp_secret(id, Some(stored_pending_secret.clone()))? != Some(secret) {
+            return Ok(false);
+        }
+
+        let updated = tx
+            .execute(
+                include_str!("_sql/enable_totp.sql"),
+                named_params! {
+                    ":id": id,
+                    ":secret": stored_pending_secret,
+                    ":step": step,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        tx.execute(
+            include_str!("_sql/delete_recovery_codes.sql"),
+            named_params! {
+                ":user_id": id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        for code in recovery_codes {
+            tx.execute(
+                include_str!("_sql/add_recovery_code.sql"),
+                named_params! {
+                    ":user_id": id,
+                    ":code_hash": self.token_hasher.hash(code),
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+        }
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(true)
+    }
+
+    pub fn disable_totp(&self, id: i64) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/disable_totp.sql"),
+            named_params! {
+                ":id": id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/delete_recovery_codes.sql"),
+            named_params! {
+                ":user_id": id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Returns `false` if the step, or a later one, was already used, so a code cannot be
+    /// replayed.
+    pub fn update_totp_last_step(
+        &self,
+        id: i64,
+        step: i64,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let updated = conn
+            .execute(
+                include_str!("_sql/update_totp_last_step.sql"),
+                named_params! {
+                    ":id": id,
+                    ":step": step,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(updated > 0)
+    }
+
+    pub fn consume_recovery_code(
+        &self,
+        user_id: i64,
+        code: String,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let deleted = conn
+            .execute(
+                include_str!("_sql/consume_recovery_code.sql"),
+                named_params! {
+                    ":user_id": user_id,
+                    ":code_hash": self.token_hasher.hash(&code),
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn count_recovery_codes(&self, user_id: i64) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/count_recovery_codes.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                },
+                |row| row.get("total"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn add_login_challenge(
+        &self,
+        token: String,
+        user_id: i64,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_login_challenge.sql"),
+            named_params! {
+                ":token_hash": self.token_hasher.hash(&token),
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn find_login_challenge(
+        &self,
+        token: String,
+    ) -> Result<LoginChallenge, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/find_login_challenge.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":token_hash": self.token_hasher.hash(&token),
+                },
+                |row| {
+                    Ok(LoginChallenge {
+                        user_id: row.get("id")?,
+                        username: row.get("username")?,
+                        totp_secret: row.get("totp_secret")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?
+            .change_context(UserRepositoryError::RowValueError)?;
+
+        Ok(LoginChallenge {
+            totp_secret: self.decrypt_totp_secret(item.user_id, item.totp_secret)?,
+            ..item
+        })
+    }
+
+    /// Also clears out expired challenges.
+    pub fn delete_login_challenge(&self, token: String) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/delete_login_challenge.sql"),
+            named_params! {
+                ":token_hash": self.token_hasher.hash(&token),
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Also clears out expired logins.
+    pub fn add_oidc_login(
+        &self,
+        state: String,
+        nonce: String,
+        code_verifier: String,
+        link_user_id: Option<i64>,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(include_str!("_sql/delete_expired_oidc_logins.sql"), [])
+            .change_context(UserRepositoryError::QueryError)?;
+
+        conn.execute(
+            include_str!("_sql/add_oidc_login.sql"),
+            named_params! {
+                ":state_hash": self.token_hasher.hash(&state),
+                ":nonce": nonce,
+                ":code_verifier": code_verifier,
+                ":link_user_id": link_user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn consume_oidc_login(
+        &self,
+        state: String,
+    ) -> Result<OidcLogin, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/consume_oidc_login.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":state_hash": self.token_hasher.hash(&state),
+                },
+                |row| {
+                    Ok(OidcLogin {
+                        nonce: row.get("nonce")?,
+                        code_verifier: row.get("code_verifier")?,
+                        link_user_id: row.get("link_user_id")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn find_by_external_identity(
+        &self,
+        issuer: String,
+        subject: String,
+    ) -> Result<IdUsername, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/find_by_external_identity.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":issuer": issuer,
+                    ":subject": subject,
+                },
+                |row| {
+                    Ok(IdUsername {
+                        id: row.get("id")?,
+                        username: row.get("username")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn add_external_identity(
+        &self,
+        user_id: i64,
+        issuer: String,
+        subject: String,
+        email: Option<String>,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_external_identity.sql"),
+            named_params! {
+                ":user_id": user_id,
+                ":issuer": issuer,
+                ":subject": subject,
+                ":email": email,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    /// Creates the user and links the identity in one transaction, returns the new user id.
+    pub fn register_external_user(
+        &self,
+        username: String,
+        password: Box<[u8]>,
+        issuer: String,
+        subject: String,
+        email: Option<String>,
+    ) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        tx.execute(
+            include_str!("_sql/register_user.sql"),
+            named_params! {
+                ":username_normalized": username_key(&username),
+                ":username": username,
+                ":password": password,
+                ":email": None::<String>,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+        let user_id = tx.last_insert_rowid();
+
+        tx.execute(
+            include_str!("_sql/add_external_identity.sql"),
+            named_params! {
+                ":user_id": user_id,
+                ":issuer": issuer,
+                ":subject": subject,
+                ":email": email,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(user_id)
+    }
+
+    pub fn get_external_identities(
+        &self,
+        user_id: i64,
+    ) -> Result<Box<[ExternalIdentity]>, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_external_identities.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                },
+                |row| {
+                    Ok(ExternalIdentity {
+                        id: row.get("id")?,
+                        issuer: row.get("issuer")?,
+                        email: row.get("email")?,
+                        created_at: row.get("created_at")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(UserRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    /// Returns `false` when the identity does not exist or belongs to another user.
+    pub fn delete_external_identity(
+        &self,
+        id: i64,
+        user_id: i64,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let deleted = conn
+            .execute(
+                include_str!("_sql/delete_external_identity.sql"),
+                named_params! {
+                    ":id": id,
+                    ":user_id": user_id,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Returns the new user id. With an invite code one of its uses is taken in the same
+    /// transaction, the user is not created when none is left.
+    pub fn register_user(
+        &self,
+        username: String,
+        email: String,
+        password: Box<[u8]>,
+        invite_code: Option<String>,
+    ) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let tx = conn
+            .unchecked_transaction()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        if let Some(invite_code) = invite_code {
+            let used = tx
+                .execute(
+                    include_str!("_sql/use_invite.sql"),
+                    named_params! {
+                        ":code_hash": self.token_hasher.hash(&invite_code),
+                    },
+                )
+                .change_context(UserRepositoryError::QueryError)?;
+            if used == 0 {
+                return Err(Report::new(UserRepositoryError::NotFoundError));
+            }
+        }
+
+        tx.execute(
+            include_str!("_sql/register_user.sql"),
+            named_params! {
+                ":username_normalized": username_key(&username),
+                ":username": username,
+                ":password": password,
+                ":email": email,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+        let user_id = tx.last_insert_rowid();
+
+        tx.commit()
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(user_id)
+    }
+
+    /// With `first_only` nothing is inserted once any user exists, the setup page can not be
+    /// replayed.
+    pub fn add_admin(
+        &self,
+        username: String,
+        password: Box<[u8]>,
+        first_only: bool,
+    ) -> Result<i64, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let inserted = conn
+            .execute(
+                include_str!("_sql/add_admin.sql"),
+                named_params! {
+                    ":username_normalized": username_key(&username),
+                    ":username": username,
+                    ":password": password,
+                    ":first_only": first_only,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+        if inserted == 0 {
+            return Err(Report::new(UserRepositoryError::NotFoundError));
+        }
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn has_users(&self) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/has_users.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map([], |row| row.get("has_users"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// An enabled admin that can log in without resetting the password first.
+    pub fn has_usable_admin(&self) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.query_row(include_str!("_sql/has_usable_admin.sql"), [], |row| {
+            row.get("has_usable_admin")
+        })
+        .change_context(UserRepositoryError::QueryError)
+    }
+
+    /// Failures without a user id are matched to a user by the entered username.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_auth_event(
+        &self,
+        event: AuthEventKind,
+        outcome: AuthOutcome,
+        user_id: Option<i64>,
+        username: String,
+        ip: String,
+        user_agent: String,
+        detail: String,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_auth_event.sql"),
+            named_params! {
+                ":event": event.as_str(),
+                ":outcome": outcome.as_str(),
+                ":user_id": user_id,
+                ":username_normalized": username_key(&username),
+                ":username": username,
+                ":ip": ip,
+                ":user_agent": user_agent,
+                ":detail": detail,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn get_auth_events(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Box<[AuthEvent]>, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_auth_events.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                    ":limit": limit,
+                },
+                map_auth_event,
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(UserRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    pub fn invite_usable(&self, invite_code: String) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/invite_usable.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":code_hash": self.token_hasher.hash(&invite_code),
+                },
+                |row| row.get("usable"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Only the hash of the code is stored, it is shown once when created.
+    pub fn add_invite(
+        &self,
+        invite_code: String,
+        note: String,
+        max_uses: i64,
+        valid_days: u32,
+        created_by: i64,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/add_invite.sql"),
+            named_params! {
+                ":code_hash": self.token_hasher.hash(&invite_code),
+                ":note": note,
+                ":max_uses": max_uses,
+                ":valid_days": valid_days,
+                ":created_by": created_by,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn get_invites(&self) -> Result<Box<[UserInvite]>, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_invites.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item_iter = stmt
+            .query_map([], |row| {
+                Ok(UserInvite {
+                    id: row.get("id")?,
+                    note: row.get("note")?,
+                    max_uses: row.get("max_uses")?,
+                    uses: row.get("uses")?,
+                    expire_after: row.get("expire_after")?,
+                    created_at: row.get("created_at")?,
+                    created_by: row.get("created_by")?,
+                })
+            })
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut items = vec![];
+        for item in item_iter {
+            items.push(item.change_context(UserRepositoryError::RowValueError)?);
+        }
+
+        Ok(items.into_boxed_slice())
+    }
+
+    pub fn delete_invite(&self, id: i64) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let deleted = conn
+            .execute(
+                include_str!("_sql/delete_invite.sql"),
+                named_params! {
+                    ":id": id,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn username_taken(&self, username: String) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/username_taken.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":username_normalized": username_key(&username),
+                },
+                |row| row.get("taken"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Case does not matter, `Jane@example.com` and `jane@example.com` are the same address.
+    pub fn email_taken(&self, email: String) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/email_taken.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":email": email,
+                },
+                |row| row.get("taken"),
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    pub fn get_user_email_status(
+        &self,
+        id: i64,
+    ) -> Result<UserEmailStatus, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_email_status.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":id": id,
+                },
+                |row| {
+                    Ok(UserEmailStatus {
+                        id: row.get("id")?,
+                        username: row.get("username")?,
+                        email: row.get("email")?,
+                        email_verified_at: row.get("email_verified_at")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Only marks the address the link was made for, returns false when it was already verified.
+    pub fn set_email_verified(
+        &self,
+        id: i64,
+        email: String,
+    ) -> Result<bool, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let updated = conn
+            .execute(
+                include_str!("_sql/set_email_verified.sql"),
+                named_params! {
+                    ":id": id,
+                    ":email": email,
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Unknown stored values fall back to the defaults instead of failing the request.
+    pub fn get_user_profile(
+        &self,
+        user_id: i64,
+    ) -> Result<UserProfile, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_profile.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                },
+                |row| {
+                    let time_zone: String = row.get("time_zone")?;
+                    let locale: String = row.get("locale")?;
+                    let theme: String = row.get("theme")?;
+                    Ok(UserProfile {
+                        display_name: row.get("display_name")?,
+                        time_zone: time_zone.parse().unwrap_or(chrono_tz::Tz::UTC),
+                        locale: Locale::parse(&locale).unwrap_or_default(),
+                        theme: Theme::parse(&theme).unwrap_or_default(),
+                        avatar_updated_at: row.get("avatar_updated_at")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+
+    /// Leaves the avatar as it is.
+    pub fn save_user_profile(
+        &self,
+        user_id: i64,
+        profile: &UserProfile,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/save_user_profile.sql"),
+            named_params! {
+                ":user_id": user_id,
+                ":display_name": profile.display_name,
+                ":time_zone": profile.time_zone.name(),
+                ":locale": profile.locale.as_str(),
+                ":theme": profile.theme.as_str(),
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn set_user_avatar(
+        &self,
+        user_id: i64,
+        avatar: &UserAvatar,
+    ) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/set_user_avatar.sql"),
+            named_params! {
+                ":user_id": user_id,
+                ":avatar": avatar.data,
+                ":content_type": avatar.content_type,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn delete_user_avatar(&self, user_id: i64) -> Result<(), Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        conn.execute(
+            include_str!("_sql/delete_user_avatar.sql"),
+            named_params! {
+                ":user_id": user_id,
+            },
+        )
+        .change_context(UserRepositoryError::QueryError)?;
+
+        Ok(())
+    }
+
+    pub fn get_user_avatar(&self, user_id: i64) -> Result<UserAvatar, Report<UserRepositoryError>> {
+        let conn = self
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .map_err(|_| Report::new(UserRepositoryError::LockError))?;
+
+        let mut stmt = conn
+            .prepare(include_str!("_sql/get_user_avatar.sql"))
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let mut item_iter = stmt
+            .query_map(
+                named_params! {
+                    ":user_id": user_id,
+                },
+                |row| {
+                    Ok(UserAvatar {
+                        content_type: row.get("avatar_content_type")?,
+                        data: row.get("avatar")?,
+                    })
+                },
+            )
+            .change_context(UserRepositoryError::QueryError)?;
+
+        let item = item_iter
+            .next()
+            .ok_or_else(|| Report::new(UserRepositoryError::NotFoundError))?;
+
+        item.change_context(UserRepositoryError::RowValueError)
+    }
+}
+
+impl FromGlobalContext for UserRepository {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_global_context.inject().await?,
+            dependency_global_context.inject().await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_repository() -> UserRepository {
+        UserRepository::new(
+            SqliteClient::new(":memory:".to_string()).unwrap(),
+            TokenHasher::new(b"test"),
+        )
+    }
+
+    #[test]
+    fn reset_password_with_token_is_single_use() {
+        let repository = user_repository();
+        let id = repository
+            .add_admin("alice".to_string(), Box::new([1]), false)
+            .unwrap();
+        repository
+            .add_password_reset_token("reset".to_string(), id)
+            .unwrap();
+        repository
+            .add_token("login".to_string(), id, String::new(), String::new(), 1)
+            .unwrap();
+
+        assert_eq!(
+            repository
+                .reset_password_with_token("reset".to_string(), Box::new([2]))
+                .unwrap(),
+            id
+        );
+        assert_eq!(
+            repository.get_user_password_by_id(id).unwrap().password,
+            Box::from([2])
+        );
+        assert!(repository.find_by_token("login".to_string()).is_err());
+        assert!(
+            repository
+                .reset_password_with_token("reset".to_string(), Box::new([3]))
+                .is_err()
+        );
+        assert_eq!(
+            repository.get_user_password_by_id(id).unwrap().password,
+            Box::from([2])
+        );
+    }
+
+    #[test]
+    fn update_login_attempts_returns_the_attempts_before() {
+        let repository = user_repository();
+        let keys = ["username:alice".to_string()];
+        let attempt = LoginAttempt {
+            failures: 1,
+            last_failure: "2025-01-01T12:00:00Z".parse().unwrap(),
+            locked_until: None,
+        };
+
+        let before = repository
+            .update_login_attempts(&keys, |_| Some(vec![Some(attempt.clone())]))
+            .unwrap();
+        assert_eq!(before, vec![None]);
+
+        let before = repository.update_login_attempts(&keys, |_| None).unwrap();
+        assert_eq!(before, vec![Some(attempt.clone())]);
+
+        repository
+            .update_login_attempts(&keys, |_| Some(vec![None]))
+            .unwrap();
+        let before = repository.update_login_attempts(&keys, |_| None).unwrap();
+        assert_eq!(before, vec![None]);
+    }
+
+    fn stored_totp(repository: &UserRepository, id: i64) -> (Option<String>, Option<String>) {
+        let conn = repository.sqlite_client.get_conn().lock().unwrap();
+        UserRepository::get_stored_totp(&conn, id).unwrap()
+    }
+
+    #[test]
+    fn totp_secrets_are_encrypted() {
+        let repository = user_repository();
+        let id = repository
+            .add_admin("alice".to_string(), Box::new([1]), false)
+            .unwrap();
+        repository
+            .set_totp_pending_secret(id, "JBSWY3DPEHPK3PXP".to_string())
+            .unwrap();
+
+        let (_, pending_secret) = stored_totp(&repository, id);
+        assert!(SecretCipher::is_encrypted(&pending_secret.unwrap()));
+        assert_eq!(
+            repository
+                .get_user_totp(id)
+                .unwrap()
+                .pending_secret
+                .as_deref(),
+            Some("JBSWY3DPEHPK3PXP")
+        );
+
+        assert!(
+            !repository
+                .enable_totp(id, "OTHERSECRET".to_string(), 1, &[])
+                .unwrap()
+        );
+        assert!(
+            repository
+                .enable_totp(id, "JBSWY3DPEHPK3PXP".to_string(), 1, &[])
+                .unwrap()
+        );
+        let (secret, _) = stored_totp(&repository, id);
+        assert!(SecretCipher::is_encrypted(&secret.unwrap()));
+        assert_eq!(
+            repository.get_user_totp(id).unwrap().secret.as_deref(),
+            Some("JBSWY3DPEHPK3PXP")
+        );
+    }
+
+    #[test]
+    fn plaintext_totp_secret_is_encrypted_on_read() {
+        let repository = user_repository();
+        let id = repository
+            .add_admin("alice".to_string(), Box::new([1]), false)
+            .unwrap();
+        repository
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE users SET totp_secret = 'JBSWY3DPEHPK3PXP' WHERE id = ?1",
+                [id],
+            )
+            .unwrap();
+
+        assert_eq!(
+            repository.get_user_totp(id).unwrap().secret.as_deref(),
+            Some("JBSWY3DPEHPK3PXP")
+        );
+        let (secret, pending_secret) = stored_totp(&repository, id);
+        assert!(SecretCipher::is_encrypted(&secret.unwrap()));
+        assert_eq!(pending_secret, None);
+    }
+
+    #[test]
+    fn rehash_keeps_forced_password_reset() {
+        let repository = user_repository();
+        let id = repository
+            .add_admin("alice".to_string(), Box::new([1]), false)
+            .unwrap();
+        repository
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE users SET must_reset_password = 1 WHERE id = ?1",
+                [id],
+            )
+            .unwrap();
+
+        repository.rehash_user_password(id, Box::new([2])).unwrap();
+        let id_password = repository.get_user_password_by_id(id).unwrap();
+        assert_eq!(id_password.password, Box::from([2]));
+        assert!(id_password.must_reset_password);
+
+        repository.update_user_password(id, Box::new([3])).unwrap();
+        assert!(
+            !repository
+                .get_user_password_by_id(id)
+                .unwrap()
+                .must_reset_password
+        );
+    }
+
+    #[test]
+    fn first_admin_only_while_no_user_exists() {
+        let repository = user_repository();
+        repository
+            .add_admin("alice".to_string(), Box::new([1]), true)
+            .unwrap();
+
+        assert!(
+            repository
+                .add_admin("mallory".to_string(), Box::new([1]), true)
+                .is_err()
+        );
+        assert!(repository.username_taken("mallory".to_string()).is_err());
+        repository
+            .add_admin("bob".to_string(), Box::new([1]), false)
+            .unwrap();
+    }
+
+    #[test]
+    fn forced_reset_admin_is_not_usable() {
+        let repository = user_repository();
+        let id = repository
+            .add_admin("default".to_string(), Box::new([1]), true)
+            .unwrap();
+        assert!(repository.has_usable_admin().unwrap());
+
+        repository
+            .sqlite_client
+            .get_conn()
+            .lock()
+            .unwrap()
+            .execute(
+                include_str!("../db/_sql/migration/019_lock_default_admin.sql"),
+                [],
+            )
+            .unwrap();
+        assert!(
+            repository
+                .get_user_password_by_id(id)
+                .unwrap()
+                .must_reset_password
+        );
+        assert!(!repository.has_usable_admin().unwrap());
+    }
+
+    #[test]
+    fn auth_events_are_append_only() {
+        let repository = user_repository();
+        repository
+            .add_auth_event(
+                AuthEventKind::Login,
+                AuthOutcome::Failure,
+                None,
+                "alice".to_string(),
+                "127.0.0.1".to_string(),
+                String::new(),
+                String::new(),
+            )
+            .unwrap();
+
+        let conn = repository.sqlite_client.get_conn().lock().unwrap();
+        for sql in [
+            "UPDATE auth_events SET outcome = 'success'",
+            "DELETE FROM auth_events",
+        ] {
+            let err = conn.execute(sql, []).unwrap_err();
+            assert!(err.to_string().contains("append-only"), "{sql}: {err}");
+        }
+        let outcome: String = conn
+            .query_row("SELECT outcome FROM auth_events", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(outcome, AuthOutcome::Failure.as_str());
     }
 }
@@ -1,17 +1,43 @@
+use crate::csrf::{CsrfForm, CsrfMultipartForm, CsrfPost};
+use crate::dependency::{ApiScope, Dep};
+use crate::error::ErrorReportResponse;
 use crate::html_base::ContextHtmlBuilder;
+use crate::oidc::OidcError;
 use crate::user::dependency::UserDep;
 use crate::user::flag::{LoginFlag, LogoutFlag};
-use crate::user::form::UserRegisterForm;
-use crate::user::service::{UserLoginService, UserRegisterService};
+use crate::user::form::{
+    UserAvatarForm, UserChangePasswordForm, UserPasswordResetForm, UserProfileForm,
+    UserRegisterForm,
+};
+use crate::user::model::{
+    AuthEventKind, AvatarFailure, LoginFailure, LoginSuccess, OidcLoginFailure, OidcLoginSuccess,
+};
+use crate::user::password::PasswordHashPoolError;
+use crate::user::repository::UserRepositoryError;
+use crate::user::service::{
+    AuthEventService, UserApiTokenService, UserEmailVerificationService, UserLoginService,
+    UserOidcService, UserPasswordResetService, UserPasswordService, UserProfileService,
+    UserRegisterService, UserSessionService, UserTotpService,
+};
+use crate::user::session_cookie::SessionCookie;
+use crate::user::validate::invite::IsInviteValid;
+use error_stack::Report;
 use maud::{Markup, html};
 use rocket::fairing::AdHoc;
-use rocket::form::Form;
-use rocket::http::{Cookie, CookieJar};
+use rocket::http::uri::Origin;
+use rocket::http::{ContentType, Cookie, CookieJar, Header, SameSite};
 use rocket::response::{Flash, Redirect};
 use rocket::time::Duration;
+use tokio::io::AsyncReadExt;
 
 #[get("/")]
-pub async fn display_user(context_html_builder: UserDep<ContextHtmlBuilder>) -> Markup {
+pub async fn display_user(
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    user_oidc: UserDep<UserOidcService>,
+    user_email_verification: UserDep<UserEmailVerificationService>,
+) -> Markup {
+    let email_status = user_email_verification.0.email_status();
+
     let title = if context_html_builder.1.is_user {
         format!("User: {}", context_html_builder.1.username)
     } else {
@@ -26,9 +52,38 @@ pub async fn display_user(context_html_builder: UserDep<ContextHtmlBuilder>) ->
             h1 .mt-3 { (title) }
             p { "Welcome to the user page!" }
             @if context_html_builder.1.is_user {
+                @if let Some(avatar_url) = context_html_builder.1.profile.avatar_url(context_html_builder.1.id) {
+                    img src=(avatar_url) alt="Your avatar" width="96" height="96";
+                }
                 p { "You are logged in as a user '" (context_html_builder.1.username) "'." }
+                @if let Some(email) = email_status.as_ref().and_then(|status| status.email.as_ref()) {
+                    @if email_status.as_ref().is_some_and(|status| status.email_verified_at.is_some()) {
+                        p { "Your email address is " (email) "." }
+                    } @else {
+                        p { "Your email address " (email) " is not verified yet, check your mail for the link." }
+                        form method="post" action="/user/verify-email/resend" {
+                            (context_html_builder.0.csrf_input())
+                            button .btn .btn-sky-blue type="submit" { "Send a new link" }
+                        }
+                    }
+                }
                 p { "You can log out by clicking the button below." }
-                a .btn .btn-sky-blue .mt-3 href="/user/logout" { "Log out" }
+                form method="post" action="/user/logout" {
+                    (context_html_builder.0.csrf_input())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Log out" }
+                }
+                a .btn .btn-sky-blue .mt-3 href="/user/profile" { "Profile" }
+                a .btn .btn-sky-blue .mt-3 href="/user/password" { "Change password" }
+                a .btn .btn-sky-blue .mt-3 href="/user/sessions" { "Sessions" }
+                a .btn .btn-sky-blue .mt-3 href="/user/auth-events" { "Account activity" }
+                a .btn .btn-sky-blue .mt-3 href="/user/totp" { "Two-factor authentication" }
+                a .btn .btn-sky-blue .mt-3 href="/user/api-tokens" { "API tokens" }
+                @if user_oidc.0.is_enabled() {
+                    a .btn .btn-sky-blue .mt-3 href="/user/identities" { "Linked accounts" }
+                }
+                @if context_html_builder.1.is_admin {
+                    a .btn .btn-sky-blue .mt-3 href="/admin/users" { "Manage users" }
+                }
             } @else {
                 p { "You are logged in as a visitor." }
                 p { "You can log in as a user by clicking the button below." }
@@ -39,7 +94,11 @@ pub async fn display_user(context_html_builder: UserDep<ContextHtmlBuilder>) ->
 }
 
 #[get("/login")]
-pub async fn login(context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>) -> Markup {
+pub async fn login(
+    context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>,
+    user_oidc: UserDep<UserOidcService, LoginFlag>,
+    user_register_service: UserDep<UserRegisterService, LoginFlag>,
+) -> Markup {
     let title = "Login".to_string();
     context_html_builder
         .0
@@ -47,12 +106,19 @@ pub async fn login(context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>)
         .attach_content(html! {
             h1 .mt-3 { (title) }
             form method="post" .form {
+                (context_html_builder.0.csrf_input())
                 input .form-item type="text" name="username" placeholder="Username";
                 input .form-item type="password" name="password" placeholder="Password";
                 button .btn .btn-sky-blue .mt-3 type="submit" { "Login" };
             }
-            p { "If you don't have an account, you can register by clicking the button below." }
-            a .btn .btn-sky-blue .mt-3 href="/user/register/" { "Register" }
+            @if user_oidc.0.is_enabled() {
+                a .btn .btn-sky-blue .mt-3 href="/user/oidc/login" { (user_oidc.0.button_label()) }
+            }
+            @if !user_register_service.0.is_registration_closed() {
+                p { "If you don't have an account, you can register by clicking the button below." }
+                a .btn .btn-sky-blue .mt-3 href="/user/register/" { "Register" }
+            }
+            p .mt-3 { a href="/user/password-reset" { "Forgot your password?" } }
         })
         .build()
 }
@@ -64,78 +130,1151 @@ pub struct UserLoginForm {
 }
 
 #[post("/login", data = "<data>")]
-pub async fn login_post<'a>(
-    data: Form<UserLoginForm>,
+pub async fn login_post(
+    data: CsrfForm<UserLoginForm>,
+    user_login: UserDep<UserLoginService, LoginFlag>,
+    session_cookie: Dep<SessionCookie>,
+    jar: &CookieJar<'_>,
+) -> Result<Flash<Redirect>, ErrorReportResponse<PasswordHashPoolError>> {
+    Ok(
+        match user_login
+            .0
+            .validate_login(data.username.clone(), data.password.clone())
+            .await
+        {
+            Ok(LoginSuccess::Token(token)) => {
+                session_cookie.0.add(jar, token);
+                Flash::success(Redirect::to(uri!("/user/")), "Login succeeded.")
+            }
+            Ok(LoginSuccess::SecondFactor(challenge)) => {
+                jar.add(
+                    Cookie::build(("login-challenge", challenge))
+                        .path("/user")
+                        .max_age(Duration::minutes(5))
+                        .build(),
+                );
+                Flash::success(
+                    Redirect::to(uri!("/user/login/totp")),
+                    "Enter the code from your authenticator app.",
+                )
+            }
+            Err(LoginFailure::Busy) => {
+                return Err(ErrorReportResponse(Report::new(
+                    PasswordHashPoolError::Busy,
+                )));
+            }
+            Err(failure) => login_failure_flash(failure, uri!("/user/login/")),
+        },
+    )
+}
+
+fn login_failure_flash(failure: LoginFailure, redirect_to: Origin<'static>) -> Flash<Redirect> {
+    match failure {
+        LoginFailure::Invalid => Flash::error(Redirect::to(redirect_to), "Login failed."),
+        LoginFailure::Backoff {
+            retry_after_seconds,
+        } => Flash::error(
+            Redirect::to(redirect_to),
+            format!(
+                "Too many failed logins, please wait {} second{} before trying again.",
+                retry_after_seconds,
+                if retry_after_seconds == 1 { "" } else { "s" }
+            ),
+        ),
+        LoginFailure::Locked { until } => Flash::error(
+            Redirect::to(redirect_to),
+            format!(
+                "Too many failed logins, logging in is locked until {}.",
+                until.format("%Y-%m-%d %H:%M UTC")
+            ),
+        ),
+        LoginFailure::Busy => Flash::error(
+            Redirect::to(redirect_to),
+            PasswordHashPoolError::Busy.to_string(),
+        ),
+        LoginFailure::PasswordResetRequired => Flash::error(
+            Redirect::to(redirect_to),
+            "Your password has to be changed, use 'Forgot your password?' to choose a new one.",
+        ),
+        LoginFailure::EmailNotVerified => Flash::error(
+            Redirect::to(redirect_to),
+            "Your email address is not verified, a new link was sent to it.",
+        ),
+    }
+}
+
+#[get("/login/totp")]
+pub async fn login_totp(context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>) -> Markup {
+    let title = "Two-factor authentication".to_string();
+    context_html_builder
+        .0
+        .attach_title(title.clone())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "Enter the 6 digit code from your authenticator app, or one of your recovery codes." }
+            form method="post" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="code" placeholder="Code" autocomplete="one-time-code";
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Verify" };
+            }
+        })
+        .build()
+}
+
+#[derive(FromForm)]
+pub struct UserTotpCodeForm {
+    pub code: String,
+}
+
+#[post("/login/totp", data = "<data>")]
+pub async fn login_totp_post(
+    data: CsrfForm<UserTotpCodeForm>,
     user_login: UserDep<UserLoginService, LoginFlag>,
-    jar: &'a CookieJar<'_>,
+    session_cookie: Dep<SessionCookie>,
+    jar: &CookieJar<'_>,
 ) -> Flash<Redirect> {
-    let token = user_login
-        .0
-        .validate_login(data.username.clone(), data.password.clone());
-    if let Some(token) = token {
-        jar.add(
-            Cookie::build(("login-token", token))
-                .path("/")
-                .max_age(Duration::days(30))
-                .build(),
+    let Some(challenge) = jar
+        .get("login-challenge")
+        .map(|cookie| cookie.value().to_string())
+    else {
+        return Flash::error(
+            Redirect::to(uri!("/user/login/")),
+            "The login has expired, please log in again.",
         );
-        return Flash::success(Redirect::to(uri!("/user/")), "Login succeeded.");
-    }
+    };
 
-    Flash::error(Redirect::to(uri!("/user/login/")), "Login failed.")
+    match user_login
+        .0
+        .validate_second_factor(challenge, data.code.clone())
+    {
+        Ok(token) => {
+            jar.remove(Cookie::build("login-challenge").path("/user"));
+            session_cookie.0.add(jar, token);
+            Flash::success(Redirect::to(uri!("/user/")), "Login succeeded.")
+        }
+        Err(failure @ LoginFailure::Locked { .. }) => {
+            jar.remove(Cookie::build("login-challenge").path("/user"));
+            login_failure_flash(failure, uri!("/user/login/"))
+        }
+        Err(failure) => login_failure_flash(failure, uri!("/user/login/totp")),
+    }
 }
 
-#[get("/logout")]
-pub async fn logout<'a>(
+#[post("/logout", data = "<_csrf>")]
+pub async fn logout(
+    _csrf: CsrfPost,
     user_login: UserDep<UserLoginService, LogoutFlag>,
-    jar: &'a CookieJar<'_>,
+    session_cookie: Dep<SessionCookie>,
+    jar: &CookieJar<'_>,
 ) -> Flash<Redirect> {
     user_login.0.logout();
-    jar.remove(Cookie::from("login-token"));
+    session_cookie.0.remove(jar);
     Flash::success(Redirect::to(uri!("/user")), "Logout succeeded.")
 }
 
-#[get("/register")]
-pub async fn register(context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>) -> Markup {
-    UserRegisterForm::html_form("Register".to_string(), &context_html_builder.0, None, None)
-}
-
 #[derive(Responder)]
-enum RegisterPostResponse {
+#[allow(clippy::large_enum_variant)]
+enum RegisterResponse {
     Redirect(Flash<Redirect>),
     Markup(Markup),
 }
 
+fn registration_closed() -> RegisterResponse {
+    RegisterResponse::Redirect(Flash::error(
+        Redirect::to(uri!("/user/login")),
+        "Registration is closed.",
+    ))
+}
+
+/// `invite` fills in the code, for links handed out with an invite.
+#[get("/register?<invite>")]
+async fn register(
+    invite: Option<String>,
+    context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>,
+    user_register_service: UserDep<UserRegisterService, LoginFlag>,
+) -> RegisterResponse {
+    if user_register_service.0.is_registration_closed() {
+        return registration_closed();
+    }
+
+    let user_register_form = UserRegisterForm {
+        invite_code: invite.unwrap_or_default(),
+        ..UserRegisterForm::default()
+    };
+    RegisterResponse::Markup(UserRegisterForm::html_form(
+        "Register".to_string(),
+        &context_html_builder.0,
+        Some(user_register_form),
+        None,
+        user_register_service.0.is_invite_required(),
+    ))
+}
+
 #[post("/register", data = "<data>")]
 async fn register_post(
-    data: Form<UserRegisterForm>,
+    data: CsrfForm<UserRegisterForm>,
     user_register_service: UserDep<UserRegisterService, LoginFlag>,
     context_html_builder: UserDep<ContextHtmlBuilder>,
-) -> RegisterPostResponse {
-    let validated_data = data.as_validated(&user_register_service.0).await;
-    match validated_data {
+) -> Result<RegisterResponse, ErrorReportResponse<PasswordHashPoolError>> {
+    if user_register_service.0.is_registration_closed() {
+        return Ok(registration_closed());
+    }
+
+    let validated_data = data
+        .as_validated(
+            &user_register_service.0,
+            user_register_service.0.username_config(),
+        )
+        .await;
+    Ok(match validated_data {
         Ok(data) => {
-            if user_register_service.0.register_user(
-                data.username.as_str().to_string(),
-                data.password.as_str().to_string(),
-            ) {
-                RegisterPostResponse::Redirect(Flash::success(
+            if user_register_service
+                .0
+                .register_user(
+                    data.username.as_str().to_string(),
+                    data.email.as_str().to_string(),
+                    data.password.as_str().to_string(),
+                    data.invite_code
+                        .map(|invite_code| invite_code.as_str().to_string()),
+                )
+                .await
+                .map_err(ErrorReportResponse)?
+            {
+                RegisterResponse::Redirect(Flash::success(
                     Redirect::to(uri!("/user/login")),
-                    "Register succeeded.",
+                    if user_register_service.0.is_verification_required() {
+                        "Register succeeded, please verify your email address with the link we sent before logging in."
+                    } else {
+                        "Register succeeded, we sent you a link to verify your email address."
+                    },
                 ))
             } else {
-                RegisterPostResponse::Redirect(Flash::error(
+                RegisterResponse::Redirect(Flash::error(
                     Redirect::to(uri!("/user/register")),
                     "Register failed.",
                 ))
             }
         }
-        Err(err) => RegisterPostResponse::Markup(UserRegisterForm::html_form(
+        Err(err) => RegisterResponse::Markup(UserRegisterForm::html_form(
             "Register".to_string(),
             &context_html_builder.0,
             Some(data.clone()),
             Some(err.as_map()),
+            user_register_service.0.is_invite_required(),
         )),
+    })
+}
+
+#[get("/verify-email/<token>")]
+pub async fn verify_email(
+    token: &str,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    user_email_verification: UserDep<UserEmailVerificationService>,
+) -> Flash<Redirect> {
+    let redirect = if context_html_builder.1.is_user {
+        Redirect::to(uri!("/user"))
+    } else {
+        Redirect::to(uri!("/user/login"))
+    };
+    if user_email_verification.0.verify(token) {
+        Flash::success(redirect, "Your email address is verified.")
+    } else {
+        Flash::error(
+            redirect,
+            "The link is invalid, expired or was used already.",
+        )
+    }
+}
+
+#[post("/verify-email/resend", data = "<_csrf>")]
+pub async fn resend_verify_email(
+    _csrf: CsrfPost,
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_email_verification: UserDep<UserEmailVerificationService, LogoutFlag>,
+) -> Flash<Redirect> {
+    if user_email_verification
+        .0
+        .send_verification(context_html_builder.1.id)
+        .await
+    {
+        Flash::success(Redirect::to(uri!("/user")), "A new link was sent.")
+    } else {
+        Flash::error(Redirect::to(uri!("/user")), "The link could not be sent.")
+    }
+}
+
+#[get("/password")]
+pub async fn change_password(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+) -> Markup {
+    UserChangePasswordForm::html_form("Change password".to_string(), &context_html_builder.0, None)
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum ChangePasswordPostResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[post("/password", data = "<data>")]
+async fn change_password_post(
+    data: CsrfForm<UserChangePasswordForm>,
+    user_password_service: UserDep<UserPasswordService, LogoutFlag>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+) -> Result<ChangePasswordPostResponse, ErrorReportResponse<PasswordHashPoolError>> {
+    let validated_data = data.as_validated(&user_password_service.0).await;
+    if let Some(err) = user_password_service.0.take_hash_pool_error() {
+        return Err(ErrorReportResponse(err));
+    }
+    Ok(match validated_data {
+        Ok(data) => {
+            if user_password_service
+                .0
+                .change_password(data.password.as_str().to_string())
+                .await
+                .map_err(ErrorReportResponse)?
+            {
+                ChangePasswordPostResponse::Redirect(Flash::success(
+                    Redirect::to(uri!("/user/")),
+                    "Password changed, other sessions have been logged out.",
+                ))
+            } else {
+                ChangePasswordPostResponse::Redirect(Flash::error(
+                    Redirect::to(uri!("/user/password")),
+                    "Password change failed.",
+                ))
+            }
+        }
+        Err(err) => ChangePasswordPostResponse::Markup(UserChangePasswordForm::html_form(
+            "Change password".to_string(),
+            &context_html_builder.0,
+            Some(err.as_map()),
+        )),
+    })
+}
+
+#[get("/password-reset")]
+pub async fn password_reset(
+    context_html_builder: UserDep<ContextHtmlBuilder, LoginFlag>,
+) -> Markup {
+    let title = "Reset password".to_string();
+    context_html_builder
+        .0
+        .attach_title(title.clone())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "Enter your username, a reset link will be mailed to the address of the account." }
+            form method="post" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="username" placeholder="Username";
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Send reset link" };
+            }
+        })
+        .build()
+}
+
+#[derive(FromForm)]
+pub struct UserPasswordResetRequestForm {
+    pub username: String,
+}
+
+#[post("/password-reset", data = "<data>")]
+pub async fn password_reset_post(
+    data: CsrfForm<UserPasswordResetRequestForm>,
+    user_password_reset_service: UserDep<UserPasswordResetService, LoginFlag>,
+) -> Flash<Redirect> {
+    // Same answer either way, so the form cannot be used to probe for accounts.
+    user_password_reset_service
+        .0
+        .spawn_request_reset(data.username.clone());
+    Flash::success(
+        Redirect::to(uri!("/user/login")),
+        "If the account exists and has an email address, a reset link has been sent.",
+    )
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum PasswordResetTokenResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[get("/password-reset/<token>")]
+async fn password_reset_token(
+    token: &str,
+    user_password_reset_service: UserDep<UserPasswordResetService, LoginFlag>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+) -> PasswordResetTokenResponse {
+    if !user_password_reset_service.0.is_valid_token(token) {
+        return PasswordResetTokenResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/user/password-reset")),
+            "The reset link is invalid or has expired.",
+        ));
+    }
+
+    PasswordResetTokenResponse::Markup(UserPasswordResetForm::html_form(
+        "Reset password".to_string(),
+        &context_html_builder.0,
+        None,
+    ))
+}
+
+#[post("/password-reset/<token>", data = "<data>")]
+async fn password_reset_token_post(
+    token: &str,
+    data: CsrfForm<UserPasswordResetForm>,
+    user_password_reset_service: UserDep<UserPasswordResetService, LoginFlag>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+) -> Result<PasswordResetTokenResponse, ErrorReportResponse<PasswordHashPoolError>> {
+    Ok(
+        match data.as_validated(&user_password_reset_service.0).await {
+            Ok(data) => {
+                if user_password_reset_service
+                    .0
+                    .reset_password(token, data.password.as_str().to_string())
+                    .await
+                    .map_err(ErrorReportResponse)?
+                {
+                    PasswordResetTokenResponse::Redirect(Flash::success(
+                        Redirect::to(uri!("/user/login")),
+                        "Password reset, you can now log in.",
+                    ))
+                } else {
+                    PasswordResetTokenResponse::Redirect(Flash::error(
+                        Redirect::to(uri!("/user/password-reset")),
+                        "The reset link is invalid or has expired.",
+                    ))
+                }
+            }
+            Err(err) => PasswordResetTokenResponse::Markup(UserPasswordResetForm::html_form(
+                "Reset password".to_string(),
+                &context_html_builder.0,
+                Some(err.as_map()),
+            )),
+        },
+    )
+}
+
+#[get("/profile")]
+pub async fn profile(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_profile: UserDep<UserProfileService, LogoutFlag>,
+) -> Markup {
+    UserProfileForm::html_form(
+        "Profile".to_string(),
+        &context_html_builder.0,
+        &context_html_builder.1,
+        None,
+        None,
+        user_profile.0.avatar_max_kib(),
+    )
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum ProfilePostResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[post("/profile", data = "<data>")]
+async fn profile_post(
+    data: CsrfForm<UserProfileForm>,
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_profile: UserDep<UserProfileService, LogoutFlag>,
+) -> ProfilePostResponse {
+    match data.as_validated() {
+        Ok(validated) => {
+            if user_profile.0.save_profile(validated) {
+                ProfilePostResponse::Redirect(Flash::success(
+                    Redirect::to(uri!("/user/profile")),
+                    "Profile saved.",
+                ))
+            } else {
+                ProfilePostResponse::Redirect(Flash::error(
+                    Redirect::to(uri!("/user/profile")),
+                    "Saving the profile failed.",
+                ))
+            }
+        }
+        Err(err) => ProfilePostResponse::Markup(UserProfileForm::html_form(
+            "Profile".to_string(),
+            &context_html_builder.0,
+            &context_html_builder.1,
+            Some(data.clone()),
+            Some(err.as_map()),
+            user_profile.0.avatar_max_kib(),
+        )),
+    }
+}
+
+#[post("/profile/avatar", data = "<data>")]
+pub async fn upload_avatar(
+    data: CsrfMultipartForm<UserAvatarForm<'_>>,
+    user_profile: UserDep<UserProfileService, LogoutFlag>,
+) -> Flash<Redirect> {
+    let mut bytes = vec![];
+    let read = match data.avatar.open().await {
+        Ok(mut reader) => reader.read_to_end(&mut bytes).await.is_ok(),
+        Err(_) => false,
+    };
+    if !read {
+        return Flash::error(Redirect::to(uri!("/user/profile")), "Upload failed.");
+    }
+
+    match user_profile.0.set_avatar(bytes) {
+        Ok(()) => Flash::success(Redirect::to(uri!("/user/profile")), "Avatar saved."),
+        Err(AvatarFailure::TooLarge) => Flash::error(
+            Redirect::to(uri!("/user/profile")),
+            format!(
+                "The avatar must be at most {} KiB.",
+                user_profile.0.avatar_max_kib()
+            ),
+        ),
+        Err(AvatarFailure::UnsupportedType) => Flash::error(
+            Redirect::to(uri!("/user/profile")),
+            "Please choose a PNG, JPEG, GIF or WebP image.",
+        ),
+        Err(AvatarFailure::Failed) => {
+            Flash::error(Redirect::to(uri!("/user/profile")), "Upload failed.")
+        }
+    }
+}
+
+#[post("/profile/avatar/delete", data = "<_csrf>")]
+pub async fn delete_avatar(
+    _csrf: CsrfPost,
+    user_profile: UserDep<UserProfileService, LogoutFlag>,
+) -> Flash<Redirect> {
+    if user_profile.0.delete_avatar() {
+        Flash::success(Redirect::to(uri!("/user/profile")), "Avatar removed.")
+    } else {
+        Flash::error(
+            Redirect::to(uri!("/user/profile")),
+            "Removing the avatar failed.",
+        )
+    }
+}
+
+/// The image type was sniffed on upload, browsers must not guess another one.
+#[derive(Responder)]
+pub struct AvatarResponse(Box<[u8]>, ContentType, Header<'static>, Header<'static>);
+
+#[get("/avatar/<id>")]
+pub async fn avatar(id: i64, user_profile: UserDep<UserProfileService>) -> Option<AvatarResponse> {
+    let avatar = user_profile.0.get_avatar(id)?;
+    Some(AvatarResponse(
+        avatar.data,
+        ContentType::parse_flexible(&avatar.content_type)?,
+        Header::new("X-Content-Type-Options", "nosniff"),
+        // The url changes with every upload.
+        Header::new("Cache-Control", "private, max-age=604800"),
+    ))
+}
+
+#[get("/sessions")]
+pub async fn sessions(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_session_service: UserDep<UserSessionService, LogoutFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let sessions = user_session_service
+        .0
+        .list_sessions()
+        .map_err(ErrorReportResponse)?;
+    let title = "Sessions";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "These devices are logged in to your account." }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Device" }
+                    span .bucket-list-col { "IP" }
+                    span .bucket-list-col { "Logged in" }
+                    span .bucket-list-col { "Last seen" }
+                    span .bucket-list-col { "Action" }
+                }
+                @for session in &sessions {
+                    div .bucket-list-item {
+                        span .bucket-list-col {
+                            @if session.user_agent.is_empty() { "Unknown" } @else { (session.user_agent) }
+                            @if session.current { strong { " (this device)" } }
+                        }
+                        span .bucket-list-col { (session.ip) }
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(session.created_at)) }
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(session.last_seen)) }
+                        span .bucket-list-col {
+                            form method="post" action=(format!("/user/sessions/{}/revoke", session.id)) {
+                                (context_html_builder.0.csrf_input())
+                                button .btn .btn-sky-blue type="submit" {
+                                    @if session.current { "Log out" } @else { "Revoke" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            form method="post" action="/user/sessions/revoke-others" {
+                (context_html_builder.0.csrf_input())
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Log out all other sessions" }
+            }
+        })
+        .build())
+}
+
+#[get("/auth-events")]
+pub async fn auth_events(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    auth_event_service: UserDep<AuthEventService, LogoutFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let events = auth_event_service
+        .0
+        .list_own()
+        .map_err(ErrorReportResponse)?;
+    let title = "Account activity";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "Recent logins and account changes. If you do not recognize one, change your password." }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Time" }
+                    span .bucket-list-col { "Event" }
+                    span .bucket-list-col { "Outcome" }
+                    span .bucket-list-col { "IP" }
+                    span .bucket-list-col { "Device" }
+                    span .bucket-list-col { "Detail" }
+                }
+                @for event in &events {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(event.created_at)) }
+                        span .bucket-list-col { (AuthEventKind::label(&event.event)) }
+                        span .bucket-list-col { (event.outcome) }
+                        span .bucket-list-col { (event.ip) }
+                        span .bucket-list-col {
+                            @if event.user_agent.is_empty() { "Unknown" } @else { (event.user_agent) }
+                        }
+                        span .bucket-list-col {
+                            @if event.detail.is_empty() { "-" } @else { (event.detail) }
+                        }
+                    }
+                }
+            }
+        })
+        .build())
+}
+
+#[post("/sessions/<id>/revoke", data = "<_csrf>")]
+pub async fn revoke_session(
+    id: i64,
+    _csrf: CsrfPost,
+    user_session_service: UserDep<UserSessionService, LogoutFlag>,
+    session_cookie: Dep<SessionCookie>,
+    jar: &CookieJar<'_>,
+) -> Flash<Redirect> {
+    match user_session_service.0.revoke_session(id) {
+        Some(true) => {
+            session_cookie.0.remove(jar);
+            Flash::success(Redirect::to(uri!("/user")), "Logout succeeded.")
+        }
+        Some(false) => Flash::success(Redirect::to(uri!("/user/sessions")), "Session revoked."),
+        None => Flash::error(
+            Redirect::to(uri!("/user/sessions")),
+            "Session could not be revoked.",
+        ),
+    }
+}
+
+#[post("/sessions/revoke-others", data = "<_csrf>")]
+pub async fn revoke_other_sessions(
+    _csrf: CsrfPost,
+    user_session_service: UserDep<UserSessionService, LogoutFlag>,
+) -> Flash<Redirect> {
+    if user_session_service.0.revoke_other_sessions() {
+        Flash::success(
+            Redirect::to(uri!("/user/sessions")),
+            "All other sessions have been logged out.",
+        )
+    } else {
+        Flash::error(
+            Redirect::to(uri!("/user/sessions")),
+            "Sessions could not be revoked.",
+        )
+    }
+}
+
+#[get("/totp")]
+pub async fn totp(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_totp_service: UserDep<UserTotpService, LogoutFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let totp = user_totp_service
+        .0
+        .get_totp()
+        .map_err(ErrorReportResponse)?;
+    let title = "Two-factor authentication";
+
+    let content = if totp.secret.is_some() {
+        let recovery_codes_left = user_totp_service
+            .0
+            .recovery_codes_left()
+            .map_err(ErrorReportResponse)?;
+        html! {
+            h1 .mt-3 { (title) }
+            p { "Two-factor authentication is enabled." }
+            p { "You have " (recovery_codes_left) " unused recovery codes left." }
+            p .mt-3 { "Enter a code from your authenticator app, or a recovery code, to disable it." }
+            form method="post" action="/user/totp/disable" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="code" placeholder="Code" autocomplete="one-time-code";
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Disable" };
+            }
+        }
+    } else {
+        let (secret, uri) = user_totp_service
+            .0
+            .start_enrollment(&totp)
+            .map_err(ErrorReportResponse)?;
+        html! {
+            h1 .mt-3 { (title) }
+            p { "Add this account to your authenticator app, by opening the link or entering the secret by hand." }
+            p .mt-3 { a href=(uri) { (uri) } }
+            p .mt-3 { "Secret: " code { (secret) } }
+            p .mt-3 { "Then enter the 6 digit code the app shows to enable two-factor authentication." }
+            form method="post" action="/user/totp/enable" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="code" placeholder="Code" autocomplete="one-time-code";
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Enable" };
+            }
+        }
+    };
+
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(content)
+        .build())
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum TotpEnablePostResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[post("/totp/enable", data = "<data>")]
+async fn totp_enable_post(
+    data: CsrfForm<UserTotpCodeForm>,
+    user_totp_service: UserDep<UserTotpService, LogoutFlag>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+) -> TotpEnablePostResponse {
+    let Some(recovery_codes) = user_totp_service.0.enable(data.code.clone()) else {
+        return TotpEnablePostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/user/totp")),
+            "The code is not valid, please try again.",
+        ));
+    };
+
+    let title = "Two-factor authentication enabled";
+    TotpEnablePostResponse::Markup(
+        context_html_builder
+            .0
+            .attach_title(title.to_string())
+            .set_current_tag("user".to_string())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                p { "Keep these recovery codes somewhere safe, each one can be used once instead of a code from your app." }
+                p { "They will not be shown again." }
+                ul .ul-bullet .mt-3 {
+                    @for code in &recovery_codes {
+                        li { code { (code) } }
+                    }
+                }
+                a .btn .btn-sky-blue .mt-3 href="/user/" { "Done" }
+            })
+            .build(),
+    )
+}
+
+#[post("/totp/disable", data = "<data>")]
+async fn totp_disable_post(
+    data: CsrfForm<UserTotpCodeForm>,
+    user_totp_service: UserDep<UserTotpService, LogoutFlag>,
+) -> Flash<Redirect> {
+    match user_totp_service.0.disable(data.code.clone()) {
+        Ok(()) => Flash::success(
+            Redirect::to(uri!("/user/")),
+            "Two-factor authentication disabled.",
+        ),
+        Err(LoginFailure::Invalid) => Flash::error(
+            Redirect::to(uri!("/user/totp")),
+            "The code is not valid, please try again.",
+        ),
+        Err(failure) => login_failure_flash(failure, uri!("/user/totp")),
+    }
+}
+
+#[get("/api-tokens")]
+pub async fn api_tokens(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_api_token_service: UserDep<UserApiTokenService, LogoutFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let api_tokens = user_api_token_service
+        .0
+        .list_tokens()
+        .map_err(ErrorReportResponse)?;
+    let title = "API tokens";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "Scripts can call the bucket list JSON API with a token in the "
+                code { "Authorization: Bearer <token>" } " header." }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Name" }
+                    span .bucket-list-col { "Scopes" }
+                    span .bucket-list-col { "Created" }
+                    span .bucket-list-col { "Expires" }
+                    span .bucket-list-col { "Last used" }
+                    span .bucket-list-col { "Action" }
+                }
+                @for api_token in &api_tokens {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (api_token.name) }
+                        span .bucket-list-col {
+                            (api_token.scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(", "))
+                        }
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(api_token.created_at)) }
+                        span .bucket-list-col {
+                            @match api_token.expire_after {
+                                Some(expire_after) => (context_html_builder.1.format_datetime(expire_after)),
+                                None => "Never",
+                            }
+                        }
+                        span .bucket-list-col {
+                            @match api_token.last_used {
+                                Some(last_used) => (context_html_builder.1.format_datetime(last_used)),
+                                None => "Never",
+                            }
+                        }
+                        span .bucket-list-col {
+                            form method="post" action=(format!("/user/api-tokens/{}/revoke", api_token.id)) {
+                                (context_html_builder.0.csrf_input())
+                                button .btn .btn-sky-blue type="submit" { "Revoke" }
+                            }
+                        }
+                    }
+                }
+            }
+            h2 .mt-5 { "New token" }
+            form method="post" action="/user/api-tokens" .form {
+                (context_html_builder.0.csrf_input())
+                input .form-item type="text" name="name" placeholder="Name" maxlength="100";
+                label .form-item { input type="checkbox" name="read" value="true" checked; " Read" }
+                label .form-item { input type="checkbox" name="write" value="true"; " Write" }
+                select .form-item name="expires_in_days" {
+                    option value="30" { "Expires in 30 days" }
+                    option value="90" selected { "Expires in 90 days" }
+                    option value="365" { "Expires in a year" }
+                    option value="0" { "Never expires" }
+                }
+                button .btn .btn-sky-blue .mt-3 type="submit" { "Create token" };
+            }
+        })
+        .build())
+}
+
+#[derive(FromForm)]
+pub struct UserApiTokenForm {
+    pub name: String,
+    pub read: bool,
+    pub write: bool,
+    pub expires_in_days: u32,
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum ApiTokenPostResponse {
+    Redirect(Flash<Redirect>),
+    Markup(Markup),
+}
+
+#[post("/api-tokens", data = "<data>")]
+async fn api_tokens_post(
+    data: CsrfForm<UserApiTokenForm>,
+    user_api_token_service: UserDep<UserApiTokenService, LogoutFlag>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+) -> ApiTokenPostResponse {
+    let name = data.name.trim().to_string();
+    let scopes = ApiScope::ALL
+        .into_iter()
+        .filter(|scope| match scope {
+            ApiScope::Read => data.read,
+            ApiScope::Write => data.write,
+        })
+        .collect::<Vec<_>>();
+
+    if name.is_empty() || name.chars().count() > 100 {
+        return ApiTokenPostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/user/api-tokens")),
+            "The name must be between 1 and 100 characters.",
+        ));
+    }
+    if scopes.is_empty() {
+        return ApiTokenPostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/user/api-tokens")),
+            "Pick at least one scope.",
+        ));
+    }
+
+    let Some(token) = user_api_token_service.0.create_token(
+        name.clone(),
+        &scopes,
+        data.expires_in_days.min(3650),
+    ) else {
+        return ApiTokenPostResponse::Redirect(Flash::error(
+            Redirect::to(uri!("/user/api-tokens")),
+            "The token could not be created.",
+        ));
+    };
+
+    let title = "API token created";
+    ApiTokenPostResponse::Markup(
+        context_html_builder
+            .0
+            .attach_title(title.to_string())
+            .set_current_tag("user".to_string())
+            .attach_content(html! {
+                h1 .mt-3 { (title) }
+                p { "Copy the token for '" (name) "' now, it will not be shown again." }
+                p .mt-3 { code { (token) } }
+                a .btn .btn-sky-blue .mt-3 href="/user/api-tokens" { "Done" }
+            })
+            .build(),
+    )
+}
+
+#[post("/api-tokens/<id>/revoke", data = "<_csrf>")]
+pub async fn revoke_api_token(
+    id: i64,
+    _csrf: CsrfPost,
+    user_api_token_service: UserDep<UserApiTokenService, LogoutFlag>,
+) -> Flash<Redirect> {
+    if user_api_token_service.0.revoke_token(id) {
+        Flash::success(Redirect::to(uri!("/user/api-tokens")), "Token revoked.")
+    } else {
+        Flash::error(
+            Redirect::to(uri!("/user/api-tokens")),
+            "Token could not be revoked.",
+        )
+    }
+}
+
+const OIDC_STATE_COOKIE: &str = "oidc-state";
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum OidcStartResponse {
+    Redirect(Redirect),
+    Flash(Flash<Redirect>),
+    Error(ErrorReportResponse<OidcError>),
+}
+
+fn oidc_failure_message(failure: &OidcLoginFailure) -> &'static str {
+    match failure {
+        OidcLoginFailure::InvalidState => {
+            "The sign-in has expired or was started in another browser, please try again."
+        }
+        OidcLoginFailure::NotLinked => {
+            "No account is linked to this identity, log in and link it from the user page first."
+        }
+        OidcLoginFailure::LinkedToOtherUser => {
+            "This identity is already linked to another account."
+        }
+        OidcLoginFailure::PasswordResetRequired => {
+            "Your password has to be changed, use 'Forgot your password?' to choose a new one."
+        }
+        OidcLoginFailure::Provider(_) | OidcLoginFailure::Failed => {
+            "Single sign-on failed, please try again."
+        }
+    }
+}
+
+/// The state cookie is lax same-site, so it comes along on the provider's redirect back.
+async fn start_oidc(
+    user_oidc: &UserOidcService,
+    jar: &CookieJar<'_>,
+    redirect_to: Origin<'static>,
+) -> OidcStartResponse {
+    match user_oidc.start().await {
+        Ok((url, state)) => {
+            jar.add(
+                Cookie::build((OIDC_STATE_COOKIE, state))
+                    .path("/user/oidc")
+                    .http_only(true)
+                    .same_site(SameSite::Lax)
+                    .max_age(Duration::minutes(10))
+                    .build(),
+            );
+            OidcStartResponse::Redirect(Redirect::to(url))
+        }
+        Err(OidcLoginFailure::Provider(report)) => {
+            OidcStartResponse::Error(ErrorReportResponse(report))
+        }
+        Err(failure) => OidcStartResponse::Flash(Flash::error(
+            Redirect::to(redirect_to),
+            oidc_failure_message(&failure),
+        )),
+    }
+}
+
+#[get("/oidc/login")]
+async fn oidc_login(
+    user_oidc: UserDep<UserOidcService, LoginFlag>,
+    jar: &CookieJar<'_>,
+) -> OidcStartResponse {
+    start_oidc(&user_oidc.0, jar, uri!("/user/login")).await
+}
+
+#[post("/oidc/link", data = "<_csrf>")]
+async fn oidc_link(
+    _csrf: CsrfPost,
+    user_oidc: UserDep<UserOidcService, LogoutFlag>,
+    jar: &CookieJar<'_>,
+) -> OidcStartResponse {
+    start_oidc(&user_oidc.0, jar, uri!("/user/identities")).await
+}
+
+/// Answers with a page instead of a redirect: the strict same-site session cookie is only sent
+/// once the browser navigates away from the provider's redirect chain.
+#[get("/oidc/callback?<state>&<code>&<error>")]
+async fn oidc_callback(
+    state: Option<String>,
+    code: Option<String>,
+    error: Option<String>,
+    user_oidc: UserDep<UserOidcService>,
+    context_html_builder: UserDep<ContextHtmlBuilder>,
+    session_cookie: Dep<SessionCookie>,
+    jar: &CookieJar<'_>,
+) -> Result<Markup, ErrorReportResponse<OidcError>> {
+    let state_cookie = jar
+        .get(OIDC_STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    jar.remove(Cookie::build(OIDC_STATE_COOKIE).path("/user/oidc"));
+
+    let result = match (state, code, error) {
+        (Some(state), Some(code), None) => {
+            user_oidc
+                .0
+                .finish(state_cookie.as_deref(), state, code)
+                .await
+        }
+        _ => Err(OidcLoginFailure::Failed),
+    };
+
+    let (title, message, continue_to) = match result {
+        Ok(OidcLoginSuccess::Token(token)) => {
+            session_cookie.0.add(jar, token);
+            ("Login succeeded", "You are signed in.", "/user/")
+        }
+        Ok(OidcLoginSuccess::Linked) => (
+            "Account linked",
+            "You can now sign in with this identity.",
+            "/user/identities",
+        ),
+        Err(OidcLoginFailure::Provider(report)) => return Err(ErrorReportResponse(report)),
+        Err(failure) => (
+            "Login failed",
+            oidc_failure_message(&failure),
+            "/user/login",
+        ),
+    };
+
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .attach_head(html! {
+            meta http-equiv="refresh" content=(format!("0; url={}", continue_to));
+        })
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { (message) }
+            a .btn .btn-sky-blue .mt-3 href=(continue_to) { "Continue" }
+        })
+        .build())
+}
+
+#[get("/identities")]
+pub async fn identities(
+    context_html_builder: UserDep<ContextHtmlBuilder, LogoutFlag>,
+    user_oidc: UserDep<UserOidcService, LogoutFlag>,
+) -> Result<Markup, ErrorReportResponse<UserRepositoryError>> {
+    let identities = user_oidc.0.list_identities().map_err(ErrorReportResponse)?;
+    let title = "Linked accounts";
+    Ok(context_html_builder
+        .0
+        .attach_title(title.to_string())
+        .set_current_tag("user".to_string())
+        .attach_content(html! {
+            h1 .mt-3 { (title) }
+            p { "Linked identities can sign you in with '" (user_oidc.0.button_label()) "'." }
+            div .mt-3 {
+                div .bucket-list-header {
+                    span .bucket-list-col { "Provider" }
+                    span .bucket-list-col { "Email" }
+                    span .bucket-list-col { "Linked" }
+                    span .bucket-list-col { "Action" }
+                }
+                @for identity in &identities {
+                    div .bucket-list-item {
+                        span .bucket-list-col { (identity.issuer) }
+                        span .bucket-list-col { (identity.email.as_deref().unwrap_or("-")) }
+                        span .bucket-list-col { (context_html_builder.1.format_datetime(identity.created_at)) }
+                        span .bucket-list-col {
+                            form method="post" action=(format!("/user/identities/{}/unlink", identity.id)) {
+                                (context_html_builder.0.csrf_input())
+                                button .btn .btn-sky-blue type="submit" { "Unlink" }
+                            }
+                        }
+                    }
+                }
+            }
+            @if user_oidc.0.is_enabled() {
+                form method="post" action="/user/oidc/link" {
+                    (context_html_builder.0.csrf_input())
+                    button .btn .btn-sky-blue .mt-3 type="submit" { "Link an identity" }
+                }
+            }
+        })
+        .build())
+}
+
+#[post("/identities/<id>/unlink", data = "<_csrf>")]
+pub async fn unlink_identity(
+    id: i64,
+    _csrf: CsrfPost,
+    user_oidc: UserDep<UserOidcService, LogoutFlag>,
+) -> Flash<Redirect> {
+    if user_oidc.0.unlink(id) {
+        Flash::success(Redirect::to(uri!("/user/identities")), "Identity unlinked.")
+    } else {
+        Flash::error(
+            Redirect::to(uri!("/user/identities")),
+            "Identity could not be unlinked, an account without a password needs one identity.",
+        )
     }
 }
 
@@ -148,11 +1287,41 @@ impl UserRoute {
                 "/user",
                 routes![
                     display_user,
+                    profile,
+                    profile_post,
+                    upload_avatar,
+                    delete_avatar,
+                    avatar,
                     login,
                     login_post,
+                    login_totp,
+                    login_totp_post,
                     logout,
                     register,
-                    register_post
+                    register_post,
+                    verify_email,
+                    resend_verify_email,
+                    change_password,
+                    change_password_post,
+                    password_reset,
+                    password_reset_post,
+                    password_reset_token,
+                    password_reset_token_post,
+                    sessions,
+                    revoke_session,
+                    revoke_other_sessions,
+                    auth_events,
+                    totp,
+                    totp_enable_post,
+                    totp_disable_post,
+                    api_tokens,
+                    api_tokens_post,
+                    revoke_api_token,
+                    oidc_login,
+                    oidc_link,
+                    oidc_callback,
+                    identities,
+                    unlink_identity
                 ],
             )
         })
@@ -1,48 +1,150 @@
-use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::config::{
+    EmailVerificationConfig, LoginThrottleConfig, ProfileConfig, RegistrationMode, UsernameConfig,
+};
+use crate::dependency::{ApiScope, DependencyError, DependencyGlobalContext, FromGlobalContext};
+use crate::mail::Mailer;
+use crate::oidc::{IdTokenClaims, OidcClient};
+use crate::user::api_token::bearer_token;
+use crate::user::breached_password::BreachedPasswordList;
 use crate::user::dependency::{DependencyUserContext, FromUserContext};
-use crate::user::model::{IdUsername, UserContext};
-use crate::user::password::Password;
+use crate::user::email_verification::EmailVerificationToken;
+use crate::user::external_identity::username_from_claims;
+use crate::user::model::{
+    AuthEvent, AuthEventKind, AuthOutcome, AvatarFailure, ExternalIdentity, LoginFailure,
+    LoginSuccess, OidcLoginFailure, OidcLoginSuccess, SessionUser, UserApiToken, UserAuth,
+    UserAvatar, UserContext, UserEmailStatus, UserProfileFormValidated, UserSession, UserTotp,
+};
+use crate::user::password::{PasswordHashPool, PasswordHashPoolError, PasswordState};
+use crate::user::profile::{UserProfile, sniff_avatar};
 use crate::user::repository::UserRepository;
-use crate::user::validate::username::IsUsernameTaken;
+use crate::user::repository::UserRepositoryError;
+use crate::user::session_cookie::SessionCookie;
+use crate::user::throttle::{LoginAttempt, LoginThrottle, ThrottleState};
+use crate::user::token::{TokenHasher, generate_token};
+use crate::user::totp::{
+    generate_recovery_codes, generate_secret, normalize_code, otpauth_uri, verify_code,
+};
+use crate::user::validate::email::IsEmailTaken;
+use crate::user::validate::invite::IsInviteValid;
+use crate::user::validate::password::{IsCurrentPassword, IsPasswordBreached};
+use crate::user::validate::username::{IsUsernameTaken, Username, username_key};
+use chrono::{DateTime, Duration, Utc};
 use error_stack::Report;
-use uuid::Uuid;
+use rocket::http::CookieJar;
+use std::sync::{Arc, Mutex};
 
 pub struct NoopService;
 
+/// Accepts a TOTP code that was not used before, or else an unused recovery code.
+fn verify_second_factor(
+    user_repository: &UserRepository,
+    user_id: i64,
+    secret: &str,
+    code: &str,
+) -> bool {
+    if let Some(step) = verify_code(secret, code, Utc::now().timestamp() as u64) {
+        return user_repository
+            .update_totp_last_step(user_id, step)
+            .unwrap_or(false);
+    }
+
+    user_repository
+        .consume_recovery_code(user_id, normalize_code(code))
+        .unwrap_or(false)
+}
+
+/// Own auth events shown to a user, the admin endpoint pages through all of them.
+const AUTH_EVENTS_SHOWN: i64 = 100;
+
 pub struct UserCheckService {
     user_repository: UserRepository,
+    session_cookie: SessionCookie,
     token_cookie: Option<String>,
+    bearer_token: Option<String>,
 }
 
 impl UserCheckService {
-    fn new(user_repository: UserRepository, token_cookie: Option<String>) -> Self {
+    fn new(
+        user_repository: UserRepository,
+        session_cookie: SessionCookie,
+        token_cookie: Option<String>,
+        bearer_token: Option<String>,
+    ) -> Self {
         Self {
             user_repository,
+            session_cookie,
             token_cookie,
+            bearer_token,
         }
     }
 
-    pub fn get_user_context(&self) -> UserContext {
-        if let Some(id_username) = self.is_logged_in() {
+    /// A bearer token takes precedence over the cookie, so a scripted request never picks up
+    /// the browser session. A sliding session gets its cookie renewed through `jar`.
+    pub fn get_user_context(&self, jar: &CookieJar<'_>) -> UserContext {
+        if let Some(token) = &self.bearer_token {
+            return match self.user_repository.find_by_api_token(token.clone()) {
+                Ok(api_token_user) => {
+                    let _ = self.user_repository.touch_api_token(token.clone());
+                    UserContext {
+                        id: api_token_user.id,
+                        is_user: true,
+                        username: api_token_user.username,
+                        is_admin: false,
+                        auth: UserAuth::ApiToken {
+                            scopes: api_token_user.scopes,
+                        },
+                        profile: self.get_profile(api_token_user.id),
+                    }
+                }
+                Err(_) => Self::visitor_context(UserAuth::InvalidApiToken),
+            };
+        }
+
+        if let Some(session_user) = self.is_logged_in(jar) {
             UserContext {
-                id: id_username.id,
+                id: session_user.id,
                 is_user: true,
-                username: id_username.username,
+                username: session_user.username,
+                is_admin: session_user.is_admin,
+                auth: UserAuth::Session,
+                profile: self.get_profile(session_user.id),
             }
         } else {
-            UserContext {
-                id: 0,
-                is_user: false,
-                username: "Visitor".to_string(),
-            }
+            Self::visitor_context(UserAuth::Visitor)
         }
     }
 
-    fn is_logged_in(&self) -> Option<IdUsername> {
-        if let Some(token) = &self.token_cookie {
-            if let Ok(id_username) = self.user_repository.find_by_token(token.clone()) {
-                return Some(id_username);
+    fn visitor_context(auth: UserAuth) -> UserContext {
+        UserContext {
+            id: 0,
+            is_user: false,
+            username: "Visitor".to_string(),
+            is_admin: false,
+            auth,
+            profile: UserProfile::default(),
+        }
+    }
+
+    /// Users who never saved their profile get the defaults.
+    fn get_profile(&self, user_id: i64) -> UserProfile {
+        self.user_repository
+            .get_user_profile(user_id)
+            .unwrap_or_default()
+    }
+
+    fn is_logged_in(&self, jar: &CookieJar<'_>) -> Option<SessionUser> {
+        if let Some(token) = &self.token_cookie
+            && let Ok(session_user) = self.user_repository.find_by_token(token.clone())
+        {
+            let renewed = self.user_repository.touch_token(
+                token.clone(),
+                self.session_cookie.is_sliding(),
+                self.session_cookie.lifetime_days(),
+            );
+            if self.session_cookie.is_sliding() && matches!(renewed, Ok(true)) {
+                self.session_cookie.add(jar, token.clone());
             }
+            return Some(session_user);
         }
 
         None
@@ -51,40 +153,404 @@ impl UserCheckService {
 
 pub struct UserLoginService {
     user_repository: UserRepository,
+    password_hash_pool: PasswordHashPool,
+    session_cookie: SessionCookie,
     token_cookie: Option<String>,
+    user_agent: String,
+    ip: String,
+    login_throttle: LoginThrottleConfig,
+    user_email_verification: UserEmailVerificationService,
+    auth_events: AuthEventService,
 }
 
 impl UserLoginService {
-    fn new(user_repository: UserRepository, token_cookie: Option<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        user_repository: UserRepository,
+        password_hash_pool: PasswordHashPool,
+        session_cookie: SessionCookie,
+        token_cookie: Option<String>,
+        user_agent: String,
+        ip: String,
+        login_throttle: LoginThrottleConfig,
+        user_email_verification: UserEmailVerificationService,
+        auth_events: AuthEventService,
+    ) -> Self {
         Self {
             user_repository,
+            password_hash_pool,
+            session_cookie,
             token_cookie,
+            user_agent,
+            ip,
+            login_throttle,
+            user_email_verification,
+            auth_events,
         }
     }
-    pub fn validate_login(&self, username: String, password: String) -> Option<String> {
-        if let Ok(id_password) = self.user_repository.get_user_password(username) {
-            let password_status = Password::verify_password(id_password.password, password);
-            if let Ok(password_status) = password_status {
-                if password_status.is_valid() {
-                    let uuid_token = Uuid::new_v4().to_string();
 
-                    if self
-                        .user_repository
-                        .add_token(uuid_token.clone(), id_password.id)
-                        .is_err()
-                    {
-                        return None;
-                    }
+    /// Throttle keys with their limits, attempts are counted per username and per client IP.
+    fn throttle_keys(&self, username: &str) -> Vec<(String, LoginThrottle<'_>)> {
+        let mut keys = vec![(
+            format!("username:{}", username_key(username)),
+            LoginThrottle::new(
+                &self.login_throttle,
+                self.login_throttle.max_failures_per_username,
+            ),
+        )];
+        if !self.ip.is_empty() {
+            keys.push((
+                format!("ip:{}", self.ip),
+                LoginThrottle::new(
+                    &self.login_throttle,
+                    self.login_throttle.max_failures_per_ip,
+                ),
+            ));
+        }
+        keys
+    }
+
+    /// Stores a fresh hash when the stored one was made with outdated parameters.
+    /// The right password of a user who has to reset it or verify the email does not log in
+    /// either, the latter gets a new verification link.
+    async fn verify_login(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<Option<i64>, LoginFailure> {
+        let Ok(id_password) = self.user_repository.get_user_password(username) else {
+            return Ok(None);
+        };
+        let must_reset_password = id_password.must_reset_password;
+        let password_state = match self
+            .password_hash_pool
+            .verify_password(id_password.password, password)
+            .await
+        {
+            Ok(password_state) => password_state,
+            Err(err) => {
+                return match err.current_context() {
+                    PasswordHashPoolError::Busy => Err(LoginFailure::Busy),
+                    PasswordHashPoolError::Failed => Ok(None),
+                };
+            }
+        };
 
-                    return Some(uuid_token);
+        if let PasswordState::ValidRehashed(password) = &password_state
+            && let Ok(password) = password.encode_to_msg_pack()
+        {
+            let _ = self
+                .user_repository
+                .rehash_user_password(id_password.id, password);
+        }
+
+        if password_state.is_valid() && must_reset_password {
+            return Err(LoginFailure::PasswordResetRequired);
+        }
+        if password_state.is_valid()
+            && id_password.email_unverified
+            && self.user_email_verification.is_required()
+        {
+            self.user_email_verification
+                .send_verification(id_password.id)
+                .await;
+            return Err(LoginFailure::EmailNotVerified);
+        }
+
+        Ok(password_state.is_valid().then_some(id_password.id))
+    }
+
+    /// Counts the attempt as a failure up front, failing early when any key is throttled.
+    /// Concurrent attempts see it right away, `release_attempt` takes it back on success.
+    /// Returns the attempts from before.
+    fn reserve_attempt(
+        &self,
+        keys: &[(String, LoginThrottle<'_>)],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Option<LoginAttempt>>, LoginFailure> {
+        let key_names: Vec<String> = keys.iter().map(|(key, _)| key.clone()).collect();
+        let mut throttled = None;
+        let attempts = self
+            .user_repository
+            .update_login_attempts(&key_names, |attempts| {
+                for ((_, throttle), attempt) in keys.iter().zip(attempts) {
+                    match throttle.check(attempt.as_ref(), now) {
+                        ThrottleState::Allowed => {}
+                        ThrottleState::Backoff {
+                            retry_after_seconds,
+                        } => {
+                            throttled = Some(LoginFailure::Backoff {
+                                retry_after_seconds,
+                            });
+                            return None;
+                        }
+                        ThrottleState::Locked { until } => {
+                            throttled = Some(LoginFailure::Locked { until });
+                            return None;
+                        }
+                    }
                 }
+                Some(
+                    keys.iter()
+                        .zip(attempts)
+                        .map(|((_, throttle), attempt)| {
+                            Some(throttle.record_failure(attempt.clone(), now))
+                        })
+                        .collect(),
+                )
+            })
+            .map_err(|_| LoginFailure::Invalid)?;
+
+        match throttled {
+            Some(failure) => Err(failure),
+            None => Ok(attempts),
+        }
+    }
+
+    /// Puts back the attempts from before `reserve_attempt`, unless another failure was counted
+    /// in the meantime.
+    fn release_attempt(
+        &self,
+        keys: &[(String, LoginThrottle<'_>)],
+        attempts: Vec<Option<LoginAttempt>>,
+        now: DateTime<Utc>,
+    ) {
+        let key_names: Vec<String> = keys.iter().map(|(key, _)| key.clone()).collect();
+        let _ = self
+            .user_repository
+            .update_login_attempts(&key_names, |current| {
+                Some(
+                    keys.iter()
+                        .zip(current)
+                        .zip(attempts)
+                        .map(|(((_, throttle), current), attempt)| {
+                            let reserved = throttle.record_failure(attempt.clone(), now);
+                            match current {
+                                Some(current) if *current == reserved => attempt,
+                                _ => current.clone(),
+                            }
+                        })
+                        .collect(),
+                )
+            });
+    }
+
+    /// The failure was already counted by `reserve_attempt`, only a lockout is left to log.
+    fn record_failure(
+        &self,
+        keys: &[(String, LoginThrottle<'_>)],
+        attempts: Vec<Option<LoginAttempt>>,
+        now: DateTime<Utc>,
+    ) -> LoginFailure {
+        let mut locked_until = None;
+        for ((key, throttle), attempt) in keys.iter().zip(attempts) {
+            let attempt = throttle.record_failure(attempt, now);
+            if attempt.locked_until.is_some() {
+                let _ = self
+                    .user_repository
+                    .add_login_lockout(key.clone(), &attempt);
+                locked_until = locked_until.max(attempt.locked_until);
             }
         }
+        match locked_until {
+            Some(until) => LoginFailure::Locked { until },
+            None => LoginFailure::Invalid,
+        }
+    }
 
-        None
+    fn issue_token(
+        &self,
+        keys: &[(String, LoginThrottle<'_>)],
+        user_id: i64,
+    ) -> Result<String, LoginFailure> {
+        if let Some((key, _)) = keys.first() {
+            let _ = self.user_repository.delete_login_attempt(key.clone());
+        }
+
+        let token = generate_token();
+        self.user_repository
+            .add_token(
+                token.clone(),
+                user_id,
+                self.user_agent.clone(),
+                self.ip.clone(),
+                self.session_cookie.lifetime_days(),
+            )
+            .map_err(|_| LoginFailure::Invalid)?;
+
+        Ok(token)
+    }
+
+    /// Checks the throttle before running the password hash, so a locked key costs nothing.
+    /// Accounts with TOTP get a challenge token instead of a login token, their login is
+    /// recorded once the code passed.
+    pub async fn validate_login(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<LoginSuccess, LoginFailure> {
+        let result = self.password_login(&username, password).await;
+        match &result {
+            Ok((user_id, LoginSuccess::Token(_))) => self.auth_events.record(
+                AuthEventKind::Login,
+                AuthOutcome::Success,
+                Some(*user_id),
+                &username,
+                "password",
+            ),
+            Ok((_, LoginSuccess::SecondFactor(_))) => {}
+            Err(failure) => self.auth_events.record(
+                AuthEventKind::Login,
+                AuthOutcome::Failure,
+                None,
+                &username,
+                failure.as_str(),
+            ),
+        }
+        result.map(|(_, login_success)| login_success)
+    }
+
+    async fn password_login(
+        &self,
+        username: &str,
+        password: String,
+    ) -> Result<(i64, LoginSuccess), LoginFailure> {
+        let now = Utc::now();
+        let keys = self.throttle_keys(username);
+        let attempts = self.reserve_attempt(&keys, now)?;
+
+        let user_id = match self.verify_login(username.to_string(), password).await {
+            Ok(Some(user_id)) => user_id,
+            Ok(None) => return Err(self.record_failure(&keys, attempts, now)),
+            Err(failure) => {
+                self.release_attempt(&keys, attempts, now);
+                return Err(failure);
+            }
+        };
+        self.release_attempt(&keys, attempts, now);
+
+        let totp = self
+            .user_repository
+            .get_user_totp(user_id)
+            .map_err(|_| LoginFailure::Invalid)?;
+        if totp.secret.is_some() {
+            let challenge = generate_token();
+            self.user_repository
+                .add_login_challenge(challenge.clone(), user_id)
+                .map_err(|_| LoginFailure::Invalid)?;
+            return Ok((user_id, LoginSuccess::SecondFactor(challenge)));
+        }
+
+        self.issue_token(&keys, user_id)
+            .map(|token| (user_id, LoginSuccess::Token(token)))
+    }
+
+    /// Second login step, failed codes count against the same throttle as passwords.
+    pub fn validate_second_factor(
+        &self,
+        challenge: String,
+        code: String,
+    ) -> Result<String, LoginFailure> {
+        let login_challenge = self
+            .user_repository
+            .find_login_challenge(challenge.clone())
+            .map_err(|_| LoginFailure::Invalid)?;
+
+        let now = Utc::now();
+        let keys = self.throttle_keys(&login_challenge.username);
+        let record = |outcome: AuthOutcome, detail: &str| {
+            self.auth_events.record(
+                AuthEventKind::Login,
+                outcome,
+                Some(login_challenge.user_id),
+                &login_challenge.username,
+                detail,
+            )
+        };
+        let attempts = self.reserve_attempt(&keys, now).inspect_err(|failure| {
+            record(AuthOutcome::Failure, failure.as_str());
+        })?;
+
+        let verified = match &login_challenge.totp_secret {
+            Some(secret) => verify_second_factor(
+                &self.user_repository,
+                login_challenge.user_id,
+                secret,
+                &code,
+            ),
+            None => false,
+        };
+        if !verified {
+            let failure = self.record_failure(&keys, attempts, now);
+            if matches!(failure, LoginFailure::Locked { .. }) {
+                let _ = self.user_repository.delete_login_challenge(challenge);
+            }
+            record(AuthOutcome::Failure, "invalid_second_factor");
+            return Err(failure);
+        }
+
+        self.release_attempt(&keys, attempts, now);
+        let _ = self.user_repository.delete_login_challenge(challenge);
+        let token = self.issue_token(&keys, login_challenge.user_id)?;
+        record(AuthOutcome::Success, "totp");
+        Ok(token)
+    }
+
+    /// Checks a second factor of a logged in user, e.g. before TOTP is turned off. Failed codes
+    /// count against the login throttle, so this is no way around it.
+    pub fn check_second_factor(
+        &self,
+        user_id: i64,
+        username: &str,
+        secret: &str,
+        code: &str,
+    ) -> Result<(), LoginFailure> {
+        let now = Utc::now();
+        let keys = self.throttle_keys(username);
+        let attempts = self.reserve_attempt(&keys, now)?;
+
+        if !verify_second_factor(&self.user_repository, user_id, secret, code) {
+            return Err(self.record_failure(&keys, attempts, now));
+        }
+        self.release_attempt(&keys, attempts, now);
+        Ok(())
+    }
+
+    /// Login vouched for by the identity provider. It replaces both the password and TOTP, the
+    /// provider handles its own second factor. A password reset asked for by an admin still
+    /// applies, the account has to go through 'Forgot your password?' first.
+    pub fn login_external(&self, user_id: i64) -> Result<String, LoginFailure> {
+        let must_reset_password = self
+            .user_repository
+            .get_user_password_by_id(user_id)
+            .map_err(|_| LoginFailure::Invalid)?
+            .must_reset_password;
+        if must_reset_password {
+            let failure = LoginFailure::PasswordResetRequired;
+            self.auth_events.record(
+                AuthEventKind::Login,
+                AuthOutcome::Failure,
+                Some(user_id),
+                "",
+                failure.as_str(),
+            );
+            return Err(failure);
+        }
+
+        let token = self.issue_token(&[], user_id)?;
+        self.auth_events.record(
+            AuthEventKind::Login,
+            AuthOutcome::Success,
+            Some(user_id),
+            "",
+            "oidc",
+        );
+        Ok(token)
     }
 
     pub fn logout(&self) -> bool {
+        self.auth_events
+            .record_current_user(AuthEventKind::Logout, AuthOutcome::Success, "");
         if let Some(token) = &self.token_cookie {
             self.user_repository.delete_token(token.clone()).is_ok()
         } else {
@@ -93,32 +559,912 @@ impl UserLoginService {
     }
 }
 
+/// Writes the append-only auth events with the client's IP and user agent.
+pub struct AuthEventService {
+    user_repository: UserRepository,
+    user_context: Arc<UserContext>,
+    ip: String,
+    user_agent: String,
+}
+
+impl AuthEventService {
+    fn new(
+        user_repository: UserRepository,
+        user_context: Arc<UserContext>,
+        ip: String,
+        user_agent: String,
+    ) -> Self {
+        Self {
+            user_repository,
+            user_context,
+            ip,
+            user_agent,
+        }
+    }
+
+    /// A failed write does not fail the action it records.
+    pub fn record(
+        &self,
+        event: AuthEventKind,
+        outcome: AuthOutcome,
+        user_id: Option<i64>,
+        username: &str,
+        detail: &str,
+    ) {
+        let _ = self.user_repository.add_auth_event(
+            event,
+            outcome,
+            user_id,
+            username.to_string(),
+            self.ip.clone(),
+            self.user_agent.clone(),
+            detail.to_string(),
+        );
+    }
+
+    pub fn record_current_user(&self, event: AuthEventKind, outcome: AuthOutcome, detail: &str) {
+        self.record(
+            event,
+            outcome,
+            Some(self.user_context.id),
+            &self.user_context.username,
+            detail,
+        );
+    }
+
+    pub fn list_own(&self) -> Result<Box<[AuthEvent]>, Report<UserRepositoryError>> {
+        self.user_repository
+            .get_auth_events(self.user_context.id, AUTH_EVENTS_SHOWN)
+    }
+}
+
 pub struct UserRegisterService {
     user_repository: UserRepository,
+    password_hash_pool: PasswordHashPool,
+    user_email_verification: UserEmailVerificationService,
+    username_config: UsernameConfig,
+    breached_password_list: BreachedPasswordList,
+    registration_mode: RegistrationMode,
+    auth_events: AuthEventService,
 }
 
 impl UserRegisterService {
-    pub fn new(user_repository: UserRepository) -> Self {
-        Self { user_repository }
+    pub fn new(
+        user_repository: UserRepository,
+        password_hash_pool: PasswordHashPool,
+        user_email_verification: UserEmailVerificationService,
+        username_config: UsernameConfig,
+        breached_password_list: BreachedPasswordList,
+        registration_mode: RegistrationMode,
+        auth_events: AuthEventService,
+    ) -> Self {
+        Self {
+            user_repository,
+            password_hash_pool,
+            user_email_verification,
+            username_config,
+            breached_password_list,
+            registration_mode,
+            auth_events,
+        }
     }
 
-    pub fn register_user(&self, username: String, password: String) -> bool {
-        let password = match Password::hash_password(password) {
-            Ok(password) => password,
-            Err(_) => return false,
+    /// Also closed until the first admin was set up, so nobody takes the first account.
+    pub fn is_registration_closed(&self) -> bool {
+        self.registration_mode == RegistrationMode::Closed
+            || !self.user_repository.has_users().unwrap_or(false)
+    }
+
+    pub fn username_config(&self) -> &UsernameConfig {
+        &self.username_config
+    }
+
+    /// Mails the verification link right away, a failed mail can be resent from the user page.
+    /// Fails when the invite code was used up since the form was checked.
+    pub async fn register_user(
+        &self,
+        username: String,
+        email: String,
+        password: String,
+        invite_code: Option<String>,
+    ) -> Result<bool, Report<PasswordHashPoolError>> {
+        let password = self.password_hash_pool.hash_password(password).await?;
+        let detail = if invite_code.is_some() { "invite" } else { "" };
+
+        let Ok(user_id) =
+            self.user_repository
+                .register_user(username.clone(), email, password, invite_code)
+        else {
+            self.auth_events.record(
+                AuthEventKind::Register,
+                AuthOutcome::Failure,
+                None,
+                &username,
+                detail,
+            );
+            return Ok(false);
         };
-        let password = match password.encode_to_msg_pack() {
-            Ok(password) => password,
+        self.auth_events.record(
+            AuthEventKind::Register,
+            AuthOutcome::Success,
+            Some(user_id),
+            &username,
+            detail,
+        );
+
+        self.user_email_verification
+            .send_verification(user_id)
+            .await;
+        Ok(true)
+    }
+
+    pub fn is_verification_required(&self) -> bool {
+        self.user_email_verification.is_required()
+    }
+}
+
+impl IsUsernameTaken for UserRegisterService {
+    async fn is_username_taken(&self, username: &str) -> bool {
+        self.user_repository
+            .username_taken(username.to_string())
+            .is_ok()
+    }
+}
+
+impl IsEmailTaken for UserRegisterService {
+    async fn is_email_taken(&self, email: &str) -> bool {
+        self.user_repository.email_taken(email.to_string()).is_ok()
+    }
+}
+
+impl IsInviteValid for UserRegisterService {
+    fn is_invite_required(&self) -> bool {
+        self.registration_mode == RegistrationMode::InviteOnly
+    }
+
+    async fn is_invite_valid(&self, invite_code: &str) -> bool {
+        self.user_repository
+            .invite_usable(invite_code.to_string())
+            .is_ok()
+    }
+}
+
+impl IsPasswordBreached for UserRegisterService {
+    async fn is_password_breached(&self, password: &str) -> bool {
+        self.breached_password_list.contains(password).await
+    }
+}
+
+pub struct UserPasswordService {
+    user_repository: UserRepository,
+    password_hash_pool: PasswordHashPool,
+    user_context: Arc<UserContext>,
+    token_cookie: Option<String>,
+    breached_password_list: BreachedPasswordList,
+    auth_events: AuthEventService,
+    hash_pool_error: Mutex<Option<Report<PasswordHashPoolError>>>,
+}
+
+impl UserPasswordService {
+    fn new(
+        user_repository: UserRepository,
+        password_hash_pool: PasswordHashPool,
+        user_context: Arc<UserContext>,
+        token_cookie: Option<String>,
+        breached_password_list: BreachedPasswordList,
+        auth_events: AuthEventService,
+    ) -> Self {
+        Self {
+            user_repository,
+            password_hash_pool,
+            user_context,
+            token_cookie,
+            breached_password_list,
+            auth_events,
+            hash_pool_error: Mutex::new(None),
+        }
+    }
+
+    /// Stores the new password and revokes every other login token of the user.
+    pub async fn change_password(
+        &self,
+        password: String,
+    ) -> Result<bool, Report<PasswordHashPoolError>> {
+        let password = self.password_hash_pool.hash_password(password).await?;
+
+        if self
+            .user_repository
+            .update_user_password(self.user_context.id, password)
+            .is_err()
+        {
+            return Ok(false);
+        }
+        self.auth_events.record_current_user(
+            AuthEventKind::PasswordChange,
+            AuthOutcome::Success,
+            "",
+        );
+
+        Ok(self
+            .user_repository
+            .delete_other_tokens(
+                self.user_context.id,
+                self.token_cookie.clone().unwrap_or_default(),
+            )
+            .is_ok())
+    }
+
+    /// The validator only takes a bool, so a busy pool while checking the current password is
+    /// kept here for the route to answer with a 503 instead of "incorrect".
+    pub fn take_hash_pool_error(&self) -> Option<Report<PasswordHashPoolError>> {
+        self.hash_pool_error.lock().ok()?.take()
+    }
+}
+
+impl IsCurrentPassword for UserPasswordService {
+    async fn is_current_password(&self, password: &str) -> bool {
+        match self
+            .user_repository
+            .get_user_password_by_id(self.user_context.id)
+        {
+            Ok(id_password) => match self
+                .password_hash_pool
+                .verify_password(id_password.password, password.to_string())
+                .await
+            {
+                Ok(password_state) if password_state.is_valid() => true,
+                Ok(_) => {
+                    self.auth_events.record_current_user(
+                        AuthEventKind::PasswordChange,
+                        AuthOutcome::Failure,
+                        "wrong_current_password",
+                    );
+                    false
+                }
+                Err(err) => {
+                    if let Ok(mut hash_pool_error) = self.hash_pool_error.lock() {
+                        *hash_pool_error = Some(err);
+                    }
+                    false
+                }
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+impl IsPasswordBreached for UserPasswordService {
+    async fn is_password_breached(&self, password: &str) -> bool {
+        self.breached_password_list.contains(password).await
+    }
+}
+
+pub struct UserPasswordResetService {
+    user_repository: UserRepository,
+    password_hash_pool: PasswordHashPool,
+    mailer: Mailer,
+    public_url: String,
+    breached_password_list: BreachedPasswordList,
+    auth_events: AuthEventService,
+}
+
+impl UserPasswordResetService {
+    fn new(
+        user_repository: UserRepository,
+        password_hash_pool: PasswordHashPool,
+        mailer: Mailer,
+        public_url: String,
+        breached_password_list: BreachedPasswordList,
+        auth_events: AuthEventService,
+    ) -> Self {
+        Self {
+            user_repository,
+            password_hash_pool,
+            mailer,
+            public_url,
+            breached_password_list,
+            auth_events,
+        }
+    }
+
+    /// Mails a reset link if the user exists and has an email address, the caller must not
+    /// reveal the outcome to the visitor.
+    pub async fn request_reset(&self, username: String) -> bool {
+        let (id, email) = match self.user_repository.get_user_email(username.clone()) {
+            Ok(id_email) => match id_email.email {
+                Some(email) => (id_email.id, email),
+                None => return false,
+            },
             Err(_) => return false,
         };
 
+        let token = generate_token();
+        if self
+            .user_repository
+            .add_password_reset_token(token.clone(), id)
+            .is_err()
+        {
+            return false;
+        }
+
+        let body = format!(
+            "Hello {},\n\n\
+            Use the link below to choose a new password, it is valid for 30 minutes.\n\n\
+            {}/user/password-reset/{}\n\n\
+            If you did not ask for a password reset, you can ignore this mail.\n",
+            username,
+            self.public_url.trim_end_matches('/'),
+            token
+        );
+
+        self.mailer
+            .send(&email, "Password reset", body)
+            .await
+            .is_ok()
+    }
+
+    /// Runs `request_reset` off the request, so the response time does not tell whether the
+    /// user exists or has an email address.
+    pub fn spawn_request_reset(self, username: String) {
+        tokio::spawn(async move {
+            self.request_reset(username).await;
+        });
+    }
+
+    pub fn is_valid_token(&self, token: &str) -> bool {
+        self.user_repository
+            .find_password_reset_token(token.to_string())
+            .is_ok()
+    }
+
+    /// Uses up the token, stores the new password and logs the user out everywhere.
+    pub async fn reset_password(
+        &self,
+        token: &str,
+        password: String,
+    ) -> Result<bool, Report<PasswordHashPoolError>> {
+        let password = self.password_hash_pool.hash_password(password).await?;
+
+        match self
+            .user_repository
+            .reset_password_with_token(token.to_string(), password)
+        {
+            Ok(user_id) => {
+                self.auth_events.record(
+                    AuthEventKind::PasswordReset,
+                    AuthOutcome::Success,
+                    Some(user_id),
+                    "",
+                    "",
+                );
+                Ok(true)
+            }
+            Err(_) => {
+                self.auth_events.record(
+                    AuthEventKind::PasswordReset,
+                    AuthOutcome::Failure,
+                    None,
+                    "",
+                    "invalid_token",
+                );
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl IsPasswordBreached for UserPasswordResetService {
+    async fn is_password_breached(&self, password: &str) -> bool {
+        self.breached_password_list.contains(password).await
+    }
+}
+
+pub struct UserEmailVerificationService {
+    user_repository: UserRepository,
+    token_hasher: TokenHasher,
+    mailer: Mailer,
+    public_url: String,
+    config: EmailVerificationConfig,
+    user_context: Arc<UserContext>,
+}
+
+impl UserEmailVerificationService {
+    fn new(
+        user_repository: UserRepository,
+        token_hasher: TokenHasher,
+        mailer: Mailer,
+        public_url: String,
+        config: EmailVerificationConfig,
+        user_context: Arc<UserContext>,
+    ) -> Self {
+        Self {
+            user_repository,
+            token_hasher,
+            mailer,
+            public_url,
+            config,
+            user_context,
+        }
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.config.required
+    }
+
+    /// Status of the logged in user, `None` for visitors.
+    pub fn email_status(&self) -> Option<UserEmailStatus> {
+        if !self.user_context.is_user {
+            return None;
+        }
         self.user_repository
-            .register_user(username, password)
+            .get_user_email_status(self.user_context.id)
+            .ok()
+    }
+
+    /// Mails a link for the current address, nothing is sent when it is verified already.
+    pub async fn send_verification(&self, user_id: i64) -> bool {
+        let Ok(status) = self.user_repository.get_user_email_status(user_id) else {
+            return false;
+        };
+        let Some(email) = status.email.filter(|_| status.email_verified_at.is_none()) else {
+            return false;
+        };
+
+        let token = EmailVerificationToken::sign(
+            &self.token_hasher,
+            user_id,
+            &email,
+            Utc::now() + Duration::hours(self.config.link_valid_hours),
+        );
+        let body = format!(
+            "Hello {},\n\n\
+            Use the link below to verify your email address, it is valid for {} hours.\n\n\
+            {}/user/verify-email/{}\n\n\
+            If you did not create an account, you can ignore this mail.\n",
+            status.username,
+            self.config.link_valid_hours,
+            self.public_url.trim_end_matches('/'),
+            token
+        );
+
+        self.mailer
+            .send(&email, "Verify your email address", body)
+            .await
             .is_ok()
     }
+
+    pub fn verify(&self, token: &str) -> bool {
+        let Some(token) = EmailVerificationToken::parse(token) else {
+            return false;
+        };
+        let Some(email) = self
+            .user_repository
+            .get_user_email_status(token.user_id)
+            .ok()
+            .and_then(|status| status.email)
+        else {
+            return false;
+        };
+        if !token.verify(&self.token_hasher, &email, Utc::now()) {
+            return false;
+        }
+
+        self.user_repository
+            .set_email_verified(token.user_id, email)
+            .unwrap_or(false)
+    }
 }
 
-impl IsUsernameTaken for UserRegisterService {
+pub struct UserProfileService {
+    user_repository: UserRepository,
+    profile_config: ProfileConfig,
+    user_context: Arc<UserContext>,
+}
+
+impl UserProfileService {
+    fn new(
+        user_repository: UserRepository,
+        profile_config: ProfileConfig,
+        user_context: Arc<UserContext>,
+    ) -> Self {
+        Self {
+            user_repository,
+            profile_config,
+            user_context,
+        }
+    }
+
+    pub fn save_profile(&self, profile: UserProfileFormValidated) -> bool {
+        self.user_repository
+            .save_user_profile(
+                self.user_context.id,
+                &UserProfile {
+                    display_name: profile.display_name.into_inner(),
+                    time_zone: profile.time_zone.as_tz(),
+                    locale: profile.locale,
+                    theme: profile.theme,
+                    avatar_updated_at: None,
+                },
+            )
+            .is_ok()
+    }
+
+    pub fn set_avatar(&self, data: Vec<u8>) -> Result<(), AvatarFailure> {
+        if data.len() > self.profile_config.avatar_max_kib * 1024 {
+            return Err(AvatarFailure::TooLarge);
+        }
+        let content_type = sniff_avatar(&data).ok_or(AvatarFailure::UnsupportedType)?;
+
+        self.user_repository
+            .set_user_avatar(
+                self.user_context.id,
+                &UserAvatar {
+                    content_type: content_type.to_string(),
+                    data: data.into_boxed_slice(),
+                },
+            )
+            .map_err(|_| AvatarFailure::Failed)
+    }
+
+    pub fn delete_avatar(&self) -> bool {
+        self.user_repository
+            .delete_user_avatar(self.user_context.id)
+            .is_ok()
+    }
+
+    pub fn get_avatar(&self, user_id: i64) -> Option<UserAvatar> {
+        self.user_repository.get_user_avatar(user_id).ok()
+    }
+
+    pub fn avatar_max_kib(&self) -> usize {
+        self.profile_config.avatar_max_kib
+    }
+}
+
+pub struct UserSessionService {
+    user_repository: UserRepository,
+    user_context: Arc<UserContext>,
+    token_cookie: Option<String>,
+}
+
+impl UserSessionService {
+    fn new(
+        user_repository: UserRepository,
+        user_context: Arc<UserContext>,
+        token_cookie: Option<String>,
+    ) -> Self {
+        Self {
+            user_repository,
+            user_context,
+            token_cookie,
+        }
+    }
+
+    pub fn list_sessions(&self) -> Result<Box<[UserSession]>, Report<UserRepositoryError>> {
+        self.user_repository.get_sessions(
+            self.user_context.id,
+            self.token_cookie.clone().unwrap_or_default(),
+        )
+    }
+
+    /// Returns `Some(true)` when the revoked session was the current one, `None` if the
+    /// session does not exist or belongs to another user.
+    pub fn revoke_session(&self, id: i64) -> Option<bool> {
+        self.user_repository
+            .delete_session(
+                id,
+                self.user_context.id,
+                self.token_cookie.clone().unwrap_or_default(),
+            )
+            .ok()
+    }
+
+    pub fn revoke_other_sessions(&self) -> bool {
+        self.user_repository
+            .delete_other_tokens(
+                self.user_context.id,
+                self.token_cookie.clone().unwrap_or_default(),
+            )
+            .is_ok()
+    }
+}
+
+pub struct UserTotpService {
+    user_repository: UserRepository,
+    user_login_service: UserLoginService,
+    user_context: Arc<UserContext>,
+}
+
+impl UserTotpService {
+    fn new(
+        user_repository: UserRepository,
+        user_login_service: UserLoginService,
+        user_context: Arc<UserContext>,
+    ) -> Self {
+        Self {
+            user_repository,
+            user_login_service,
+            user_context,
+        }
+    }
+
+    pub fn get_totp(&self) -> Result<UserTotp, Report<UserRepositoryError>> {
+        self.user_repository.get_user_totp(self.user_context.id)
+    }
+
+    pub fn recovery_codes_left(&self) -> Result<i64, Report<UserRepositoryError>> {
+        self.user_repository
+            .count_recovery_codes(self.user_context.id)
+    }
+
+    /// Returns the pending secret and its otpauth URI, a secret is created on first use.
+    pub fn start_enrollment(
+        &self,
+        totp: &UserTotp,
+    ) -> Result<(String, String), Report<UserRepositoryError>> {
+        let secret = match &totp.pending_secret {
+            Some(secret) => secret.clone(),
+            None => {
+                let secret = generate_secret();
+                self.user_repository
+                    .set_totp_pending_secret(self.user_context.id, secret.clone())?;
+                secret
+            }
+        };
+        let uri = otpauth_uri(&secret, &self.user_context.username).unwrap_or_default();
+
+        Ok((secret, uri))
+    }
+
+    /// Enables TOTP once a code of the pending secret checks out, returns the recovery codes.
+    pub fn enable(&self, code: String) -> Option<Box<[String]>> {
+        let secret = self.get_totp().ok()?.pending_secret?;
+        let step = verify_code(&secret, &code, Utc::now().timestamp() as u64)?;
+        let recovery_codes = generate_recovery_codes();
+
+        self.user_repository
+            .enable_totp(self.user_context.id, secret, step, &recovery_codes)
+            .ok()?
+            .then_some(recovery_codes)
+    }
+
+    pub fn disable(&self, code: String) -> Result<(), LoginFailure> {
+        let Some(secret) = self.get_totp().ok().and_then(|totp| totp.secret) else {
+            return Err(LoginFailure::Invalid);
+        };
+        self.user_login_service.check_second_factor(
+            self.user_context.id,
+            &self.user_context.username,
+            &secret,
+            &code,
+        )?;
+
+        self.user_repository
+            .disable_totp(self.user_context.id)
+            .map_err(|_| LoginFailure::Invalid)
+    }
+}
+
+pub struct UserApiTokenService {
+    user_repository: UserRepository,
+    user_context: Arc<UserContext>,
+}
+
+impl UserApiTokenService {
+    fn new(user_repository: UserRepository, user_context: Arc<UserContext>) -> Self {
+        Self {
+            user_repository,
+            user_context,
+        }
+    }
+
+    pub fn list_tokens(&self) -> Result<Box<[UserApiToken]>, Report<UserRepositoryError>> {
+        self.user_repository.get_api_tokens(self.user_context.id)
+    }
+
+    /// Returns the new token, it is only stored hashed so it can not be shown again.
+    /// `expires_in_days` of 0 means the token does not expire.
+    pub fn create_token(
+        &self,
+        name: String,
+        scopes: &[ApiScope],
+        expires_in_days: u32,
+    ) -> Option<String> {
+        let token = generate_token();
+        self.user_repository
+            .add_api_token(
+                self.user_context.id,
+                name,
+                token.clone(),
+                scopes,
+                expires_in_days,
+            )
+            .ok()?;
+
+        Some(token)
+    }
+
+    pub fn revoke_token(&self, id: i64) -> bool {
+        self.user_repository
+            .delete_api_token(id, self.user_context.id)
+            .unwrap_or(false)
+    }
+}
+
+pub struct UserOidcService {
+    user_repository: UserRepository,
+    oidc_client: OidcClient,
+    user_login_service: UserLoginService,
+    username_config: UsernameConfig,
+    registration_mode: RegistrationMode,
+    user_context: Arc<UserContext>,
+}
+
+impl UserOidcService {
+    fn new(
+        user_repository: UserRepository,
+        oidc_client: OidcClient,
+        user_login_service: UserLoginService,
+        username_config: UsernameConfig,
+        registration_mode: RegistrationMode,
+        user_context: Arc<UserContext>,
+    ) -> Self {
+        Self {
+            user_repository,
+            oidc_client,
+            user_login_service,
+            username_config,
+            registration_mode,
+            user_context,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.oidc_client.is_enabled()
+    }
+
+    pub fn button_label(&self) -> &str {
+        self.oidc_client.button_label()
+    }
+
+    /// Returns the provider url to redirect to and the state, which the browser keeps in a cookie.
+    /// Started by a logged in user, the callback links the identity instead of logging in.
+    pub async fn start(&self) -> Result<(String, String), OidcLoginFailure> {
+        let request = self
+            .oidc_client
+            .authorization_request()
+            .await
+            .map_err(OidcLoginFailure::Provider)?;
+        let link_user_id = self.user_context.is_user.then_some(self.user_context.id);
+        self.user_repository
+            .add_oidc_login(
+                request.state.clone(),
+                request.nonce,
+                request.code_verifier,
+                link_user_id,
+            )
+            .map_err(|_| OidcLoginFailure::Failed)?;
+
+        Ok((request.url, request.state))
+    }
+
+    /// `state_cookie` ties the callback to the browser that started the login.
+    pub async fn finish(
+        &self,
+        state_cookie: Option<&str>,
+        state: String,
+        code: String,
+    ) -> Result<OidcLoginSuccess, OidcLoginFailure> {
+        if state_cookie != Some(state.as_str()) {
+            return Err(OidcLoginFailure::InvalidState);
+        }
+        let login = self
+            .user_repository
+            .consume_oidc_login(state)
+            .map_err(|_| OidcLoginFailure::InvalidState)?;
+        let claims = self
+            .oidc_client
+            .exchange_code(&code, &login.code_verifier, &login.nonce)
+            .await
+            .map_err(OidcLoginFailure::Provider)?;
+
+        let linked_user = match self
+            .user_repository
+            .find_by_external_identity(claims.iss.clone(), claims.sub.clone())
+        {
+            Ok(user) => Some(user.id),
+            Err(err) if matches!(err.current_context(), UserRepositoryError::NotFoundError) => None,
+            Err(_) => return Err(OidcLoginFailure::Failed),
+        };
+
+        // The session cookie is strict same-site and missing on the provider's redirect,
+        // the state cookie already proves this is the browser that started the linking.
+        if let Some(link_user_id) = login.link_user_id {
+            return match linked_user {
+                Some(user_id) if user_id == link_user_id => Ok(OidcLoginSuccess::Linked),
+                Some(_) => Err(OidcLoginFailure::LinkedToOtherUser),
+                None => self
+                    .user_repository
+                    .add_external_identity(link_user_id, claims.iss, claims.sub, claims.email)
+                    .map(|_| OidcLoginSuccess::Linked)
+                    .map_err(|_| OidcLoginFailure::Failed),
+            };
+        }
+
+        let user_id = match linked_user {
+            Some(user_id) => user_id,
+            // Users without an invite would otherwise get in through the provider.
+            None if self.oidc_client.auto_register()
+                && self.registration_mode == RegistrationMode::Open
+                && self.user_repository.has_users().unwrap_or(false) =>
+            {
+                self.register(claims).await?
+            }
+            None => return Err(OidcLoginFailure::NotLinked),
+        };
+
+        self.user_login_service
+            .login_external(user_id)
+            .map(OidcLoginSuccess::Token)
+            .map_err(|failure| match failure {
+                LoginFailure::PasswordResetRequired => OidcLoginFailure::PasswordResetRequired,
+                _ => OidcLoginFailure::Failed,
+            })
+    }
+
+    /// The new user has no password, logging in with one always fails.
+    /// The last attempt drops the claims, for names the username rules refuse.
+    async fn register(&self, claims: IdTokenClaims) -> Result<i64, OidcLoginFailure> {
+        for attempt in 0..5 {
+            let suffix = (attempt > 0).then(|| generate_token()[..6].to_string());
+            let username = match attempt {
+                4 => format!("user-{}", suffix.unwrap_or_default()),
+                _ => username_from_claims(&claims, suffix.as_deref()),
+            };
+            let Ok(username) = Username::parse(username, &self.username_config, None) else {
+                continue;
+            };
+            if self.is_username_taken(username.as_str()).await {
+                continue;
+            }
+
+            if let Ok(user_id) = self.user_repository.register_external_user(
+                username.as_str().to_string(),
+                Box::new([]),
+                claims.iss.clone(),
+                claims.sub.clone(),
+                claims.email.clone(),
+            ) {
+                return Ok(user_id);
+            }
+        }
+
+        Err(OidcLoginFailure::Failed)
+    }
+
+    pub fn list_identities(&self) -> Result<Box<[ExternalIdentity]>, Report<UserRepositoryError>> {
+        self.user_repository
+            .get_external_identities(self.user_context.id)
+    }
+
+    /// Refused for the last identity of a user without a password, who could not log in anymore.
+    pub fn unlink(&self, id: i64) -> bool {
+        let has_password = self
+            .user_repository
+            .get_user_password_by_id(self.user_context.id)
+            .is_ok_and(|id_password| !id_password.password.is_empty());
+        let identities = self.list_identities().map(|identities| identities.len());
+        if !has_password && identities.unwrap_or(0) <= 1 {
+            return false;
+        }
+
+        self.user_repository
+            .delete_external_identity(id, self.user_context.id)
+            .unwrap_or(false)
+    }
+}
+
+impl IsUsernameTaken for UserOidcService {
     async fn is_username_taken(&self, username: &str) -> bool {
         self.user_repository
             .username_taken(username.to_string())
@@ -151,9 +1497,17 @@ impl FromGlobalContext for UserCheckService {
             .ok_or(DependencyError::NeedsRequest)?;
         let cookies = request.cookies();
 
+        let session_cookie: SessionCookie = dependency_global_context.inject().await?;
+
         Ok(Self::new(
             dependency_global_context.inject().await?,
-            cookies.get("login-token").map(|c| c.value().to_string()),
+            session_cookie.clone(),
+            session_cookie.get(cookies),
+            request
+                .headers()
+                .get_one("Authorization")
+                .and_then(bearer_token)
+                .map(|token| token.to_string()),
         ))
     }
 }
@@ -167,9 +1521,57 @@ impl FromUserContext for UserLoginService {
             .ok_or(DependencyError::NeedsRequest)?;
         let cookies = request.cookies();
 
+        let session_cookie: SessionCookie = dependency_user_context.inject_global().await?;
+
         Ok(Self::new(
             dependency_user_context.inject_global().await?,
-            cookies.get("login-token").map(|c| c.value().to_string()),
+            dependency_user_context.inject_global().await?,
+            session_cookie.clone(),
+            session_cookie.get(cookies),
+            request
+                .headers()
+                .get_one("User-Agent")
+                .unwrap_or_default()
+                .chars()
+                .take(255)
+                .collect(),
+            request
+                .client_ip()
+                .map(|ip| ip.to_string())
+                .unwrap_or_default(),
+            dependency_user_context
+                .global_context
+                .config
+                .login_throttle
+                .clone(),
+            dependency_user_context.inject().await?,
+            dependency_user_context.inject().await?,
+        ))
+    }
+}
+
+impl FromUserContext for AuthEventService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let request = dependency_user_context
+            .request
+            .ok_or(DependencyError::NeedsRequest)?;
+
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            Arc::clone(&dependency_user_context.user_context),
+            request
+                .client_ip()
+                .map(|ip| ip.to_string())
+                .unwrap_or_default(),
+            request
+                .headers()
+                .get_one("User-Agent")
+                .unwrap_or_default()
+                .chars()
+                .take(255)
+                .collect(),
         ))
     }
 }
@@ -178,6 +1580,161 @@ impl FromUserContext for UserRegisterService {
     async fn from_user_context(
         dependency_user_context: &DependencyUserContext<'_, '_>,
     ) -> Result<Self, Report<DependencyError>> {
-        Ok(Self::new(dependency_user_context.inject_global().await?))
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+            dependency_user_context
+                .global_context
+                .config
+                .username
+                .clone(),
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.global_context.config.registration,
+            dependency_user_context.inject().await?,
+        ))
+    }
+}
+
+impl FromUserContext for UserEmailVerificationService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context
+                .global_context
+                .config
+                .public_url
+                .clone(),
+            dependency_user_context
+                .global_context
+                .config
+                .email_verification
+                .clone(),
+            Arc::clone(&dependency_user_context.user_context),
+        ))
+    }
+}
+
+impl FromUserContext for UserPasswordService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let request = dependency_user_context
+            .request
+            .ok_or(DependencyError::NeedsRequest)?;
+        let cookies = request.cookies();
+
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            Arc::clone(&dependency_user_context.user_context),
+            dependency_user_context
+                .inject_global::<SessionCookie>()
+                .await?
+                .get(cookies),
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+        ))
+    }
+}
+
+impl FromUserContext for UserPasswordResetService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context
+                .global_context
+                .config
+                .public_url
+                .clone(),
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+        ))
+    }
+}
+
+impl FromUserContext for UserProfileService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context
+                .global_context
+                .config
+                .profile
+                .clone(),
+            Arc::clone(&dependency_user_context.user_context),
+        ))
+    }
+}
+
+impl FromUserContext for UserSessionService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let request = dependency_user_context
+            .request
+            .ok_or(DependencyError::NeedsRequest)?;
+        let cookies = request.cookies();
+
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            Arc::clone(&dependency_user_context.user_context),
+            dependency_user_context
+                .inject_global::<SessionCookie>()
+                .await?
+                .get(cookies),
+        ))
+    }
+}
+
+impl FromUserContext for UserTotpService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+            Arc::clone(&dependency_user_context.user_context),
+        ))
+    }
+}
+
+impl FromUserContext for UserApiTokenService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            Arc::clone(&dependency_user_context.user_context),
+        ))
+    }
+}
+
+impl FromUserContext for UserOidcService {
+    async fn from_user_context(
+        dependency_user_context: &DependencyUserContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject_global().await?,
+            dependency_user_context.inject().await?,
+            dependency_user_context
+                .global_context
+                .config
+                .username
+                .clone(),
+            dependency_user_context.global_context.config.registration,
+            Arc::clone(&dependency_user_context.user_context),
+        ))
     }
 }
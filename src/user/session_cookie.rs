@@ -0,0 +1,76 @@
+use crate::config::{SessionConfig, SessionSameSite};
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use error_stack::Report;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::time::Duration;
+
+const LOGIN_TOKEN_COOKIE: &str = "login-token";
+
+/// Reads and writes the `login-token` cookie, plain or encrypted as configured.
+#[derive(Clone)]
+pub struct SessionCookie {
+    session_config: SessionConfig,
+}
+
+impl SessionCookie {
+    fn new(session_config: SessionConfig) -> Self {
+        Self { session_config }
+    }
+
+    pub fn lifetime_days(&self) -> u32 {
+        self.session_config.lifetime_days
+    }
+
+    pub fn is_sliding(&self) -> bool {
+        self.session_config.sliding
+    }
+
+    /// A plain cookie left over from before `private` was switched on reads as logged out.
+    pub fn get(&self, jar: &CookieJar<'_>) -> Option<String> {
+        if self.session_config.private {
+            jar.get_private(LOGIN_TOKEN_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+        } else {
+            jar.get(LOGIN_TOKEN_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+        }
+    }
+
+    pub fn add(&self, jar: &CookieJar<'_>, token: String) {
+        let cookie = Cookie::build((LOGIN_TOKEN_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .secure(self.session_config.secure)
+            .same_site(match self.session_config.same_site {
+                SessionSameSite::Strict => SameSite::Strict,
+                SessionSameSite::Lax => SameSite::Lax,
+                SessionSameSite::None => SameSite::None,
+            })
+            .max_age(Duration::days(self.session_config.lifetime_days.into()))
+            .build();
+
+        if self.session_config.private {
+            jar.add_private(cookie);
+        } else {
+            jar.add(cookie);
+        }
+    }
+
+    pub fn remove(&self, jar: &CookieJar<'_>) {
+        jar.remove(Cookie::build(LOGIN_TOKEN_COOKIE).path("/"));
+    }
+}
+
+impl FromGlobalContext for SessionCookie {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        Ok(Self::new(
+            dependency_global_context
+                .global_context
+                .config
+                .session
+                .clone(),
+        ))
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::LoginThrottleConfig;
+use chrono::{DateTime, Duration, Utc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginAttempt {
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ThrottleState {
+    Allowed,
+    Backoff { retry_after_seconds: i64 },
+    Locked { until: DateTime<Utc> },
+}
+
+/// Limits apply per key, a key is either a username or a client IP.
+pub struct LoginThrottle<'a> {
+    config: &'a LoginThrottleConfig,
+    max_failures: u32,
+}
+
+impl<'a> LoginThrottle<'a> {
+    pub fn new(config: &'a LoginThrottleConfig, max_failures: u32) -> Self {
+        Self {
+            config,
+            max_failures,
+        }
+    }
+
+    fn lockout(&self) -> Duration {
+        Duration::minutes(self.config.lockout_minutes)
+    }
+
+    /// Wait after the n-th failure, doubling each time and never longer than a lockout.
+    fn backoff(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(30);
+        let seconds = self
+            .config
+            .backoff_base_seconds
+            .saturating_mul(1i64 << exponent);
+        Duration::seconds(seconds).min(self.lockout())
+    }
+
+    /// Failures are forgotten once a lockout has run out, or after a lockout-long quiet spell.
+    fn is_stale(&self, attempt: &LoginAttempt, now: DateTime<Utc>) -> bool {
+        match attempt.locked_until {
+            Some(locked_until) => locked_until <= now,
+            None => attempt.last_failure + self.lockout() <= now,
+        }
+    }
+
+    pub fn check(&self, attempt: Option<&LoginAttempt>, now: DateTime<Utc>) -> ThrottleState {
+        let attempt = match attempt {
+            Some(attempt) if !self.is_stale(attempt, now) => attempt,
+            _ => return ThrottleState::Allowed,
+        };
+
+        if let Some(locked_until) = attempt.locked_until {
+            return ThrottleState::Locked {
+                until: locked_until,
+            };
+        }
+
+        let retry_at = attempt.last_failure + self.backoff(attempt.failures);
+        if retry_at > now {
+            ThrottleState::Backoff {
+                retry_after_seconds: (retry_at - now).num_seconds().max(1),
+            }
+        } else {
+            ThrottleState::Allowed
+        }
+    }
+
+    /// Returns the attempt to store, it carries `locked_until` once the limit is reached.
+    pub fn record_failure(
+        &self,
+        attempt: Option<LoginAttempt>,
+        now: DateTime<Utc>,
+    ) -> LoginAttempt {
+        let failures = match attempt {
+            Some(attempt) if !self.is_stale(&attempt, now) => attempt.failures + 1,
+            _ => 1,
+        };
+
+        LoginAttempt {
+            failures,
+            last_failure: now,
+            locked_until: (failures >= self.max_failures).then(|| now + self.lockout()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LoginThrottleConfig {
+        LoginThrottleConfig {
+            max_failures_per_username: 3,
+            max_failures_per_ip: 10,
+            backoff_base_seconds: 2,
+            lockout_minutes: 15,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2025-01-01T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_check_allowed_without_attempt() {
+        let config = config();
+        let throttle = LoginThrottle::new(&config, 3);
+        assert_eq!(throttle.check(None, now()), ThrottleState::Allowed);
+    }
+
+    #[test]
+    fn test_check_backoff_doubles() {
+        let config = config();
+        let throttle = LoginThrottle::new(&config, 3);
+        let first = throttle.record_failure(None, now());
+        assert_eq!(
+            throttle.check(Some(&first), now()),
+            ThrottleState::Backoff {
+                retry_after_seconds: 2
+            }
+        );
+        let second = throttle.record_failure(Some(first), now());
+        assert_eq!(
+            throttle.check(Some(&second), now()),
+            ThrottleState::Backoff {
+                retry_after_seconds: 4
+            }
+        );
+        assert_eq!(
+            throttle.check(Some(&second), now() + Duration::seconds(4)),
+            ThrottleState::Allowed
+        );
+    }
+
+    #[test]
+    fn test_record_failure_locks_at_limit() {
+        let config = config();
+        let throttle = LoginThrottle::new(&config, 3);
+        let mut attempt = None;
+        for _ in 0..3 {
+            attempt = Some(throttle.record_failure(attempt, now()));
+        }
+        let attempt = attempt.unwrap();
+        let until = now() + Duration::minutes(15);
+        assert_eq!(attempt.locked_until, Some(until));
+        assert_eq!(
+            throttle.check(Some(&attempt), now() + Duration::minutes(14)),
+            ThrottleState::Locked { until }
+        );
+    }
+
+    #[test]
+    fn test_lock_expires_and_failures_reset() {
+        let config = config();
+        let throttle = LoginThrottle::new(&config, 3);
+        let attempt = LoginAttempt {
+            failures: 3,
+            last_failure: now(),
+            locked_until: Some(now() + Duration::minutes(15)),
+        };
+        let later = now() + Duration::minutes(15);
+        assert_eq!(
+            throttle.check(Some(&attempt), later),
+            ThrottleState::Allowed
+        );
+        assert_eq!(throttle.record_failure(Some(attempt), later).failures, 1);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::dependency::{DependencyError, DependencyGlobalContext, FromGlobalContext};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use error_stack::Report;
+use hmac::{Hmac, Mac};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use sha2::Sha256;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+/// Random token that is handed out once, e.g. in a cookie or a mail, only its hash gets stored.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Keyed hash of tokens, a leaked database alone does not give working tokens.
+#[derive(Clone)]
+pub struct TokenHasher {
+    secret: Arc<[u8]>,
+}
+
+impl TokenHasher {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: Arc::from(secret),
+        }
+    }
+
+    pub fn hash(&self, token: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(token.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Key for `SecretCipher`, derived so the token secret itself never encrypts anything.
+    pub fn secret_cipher(&self) -> SecretCipher {
+        let key = self.hash("secret-cipher-v1");
+        let key = hex::decode(key).expect("hash returns hex");
+        let key = UnboundKey::new(&AES_256_GCM, &key).expect("a SHA-256 MAC is 32 bytes");
+        SecretCipher {
+            key: Arc::new(LessSafeKey::new(key)),
+        }
+    }
+
+    /// Checks a hash from `hash` in constant time, for signatures that travel in links.
+    pub fn verify(&self, token: &str, hash: &str) -> bool {
+        let Ok(hash) = hex::decode(hash) else {
+            return false;
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(token.as_bytes());
+        mac.verify_slice(&hash).is_ok()
+    }
+}
+
+/// Encrypts secrets that have to be read back, e.g. TOTP secrets, with AES-256-GCM under a key
+/// derived from the token secret. `aad` ties a value to its row, so it can not be copied over.
+#[derive(Clone)]
+pub struct SecretCipher {
+    key: Arc<LessSafeKey>,
+}
+
+const SECRET_CIPHER_PREFIX: &str = "v1:";
+
+impl SecretCipher {
+    pub fn encrypt(&self, plaintext: &str, aad: &[u8]) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let mut in_out = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .expect("AES-GCM accepts any plaintext this short");
+
+        format!(
+            "{SECRET_CIPHER_PREFIX}{}{}",
+            hex::encode(nonce),
+            hex::encode(in_out)
+        )
+    }
+
+    /// Values stored before encryption was added come back as they are, see `is_encrypted`.
+    /// `None` when the value was tampered with or encrypted under another key.
+    pub fn decrypt(&self, stored: &str, aad: &[u8]) -> Option<String> {
+        let Some(encoded) = stored.strip_prefix(SECRET_CIPHER_PREFIX) else {
+            return Some(stored.to_string());
+        };
+        let bytes = hex::decode(encoded).ok()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .ok()?;
+
+        String::from_utf8(plaintext.to_vec()).ok()
+    }
+
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(SECRET_CIPHER_PREFIX)
+    }
+}
+
+static TOKEN_HASHER: OnceCell<TokenHasher> = OnceCell::const_new();
+
+/// Where the generated secret is kept when `token_secret` is empty, next to the database.
+fn secret_file_path(sqlite_path: &str) -> PathBuf {
+    PathBuf::from(format!("{sqlite_path}.token_secret"))
+}
+
+/// Reads the secret from `path`, or writes a new random one readable only by the owner.
+fn load_or_create_secret(path: &Path) -> io::Result<String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    match options.open(path) {
+        Ok(mut file) => {
+            let secret = generate_token();
+            file.write_all(secret.as_bytes())?;
+            file.sync_all()?;
+            Ok(secret)
+        }
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            let secret = fs::read_to_string(path)?.trim().to_string();
+            if secret.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is empty", path.display()),
+                ));
+            }
+            Ok(secret)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+impl FromGlobalContext for TokenHasher {
+    async fn from_global_context(
+        dependency_global_context: &DependencyGlobalContext<'_, '_>,
+    ) -> Result<Self, Report<DependencyError>> {
+        let token_hasher = TOKEN_HASHER
+            .get_or_try_init(|| async {
+                let config = &dependency_global_context.global_context.config;
+                if !config.token_secret.is_empty() {
+                    return Ok(Self::new(config.token_secret.as_bytes()));
+                }
+
+                // Tokens have to outlive a restart, so a generated secret is kept on disk.
+                let path = secret_file_path(&config.sqlite_path);
+                load_or_create_secret(&path)
+                    .map(|secret| Self::new(secret.as_bytes()))
+                    .map_err(|error| {
+                        Report::new(DependencyError::Other(format!(
+                            "Could not read or create the token secret {}: {error}",
+                            path.display()
+                        )))
+                    })
+            })
+            .await?;
+
+        Ok(token_hasher.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn test_hash() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            TokenHasher::new(b"Jefe").hash("what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let token_hasher = TokenHasher::new(b"secret");
+        let hash = token_hasher.hash("token");
+        assert!(token_hasher.verify("token", &hash));
+        assert!(!token_hasher.verify("other token", &hash));
+        assert!(!token_hasher.verify("token", "not hex"));
+        assert!(!TokenHasher::new(b"other secret").verify("token", &hash));
+    }
+
+    #[test]
+    fn test_load_or_create_secret_is_kept() {
+        let path = std::env::temp_dir().join(format!("token_secret_test_{}", generate_token()));
+        let secret = load_or_create_secret(&path).unwrap();
+        assert_eq!(secret.len(), 64);
+        assert_eq!(load_or_create_secret(&path).unwrap(), secret);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_secret_cipher() {
+        let cipher = TokenHasher::new(b"secret").secret_cipher();
+        let stored = cipher.encrypt("JBSWY3DPEHPK3PXP", b"totp:1");
+        assert!(SecretCipher::is_encrypted(&stored));
+        assert!(!stored.contains("JBSWY3DPEHPK3PXP"));
+        assert_ne!(stored, cipher.encrypt("JBSWY3DPEHPK3PXP", b"totp:1"));
+        assert_eq!(
+            cipher.decrypt(&stored, b"totp:1").as_deref(),
+            Some("JBSWY3DPEHPK3PXP")
+        );
+        assert_eq!(cipher.decrypt(&stored, b"totp:2"), None);
+        assert_eq!(
+            TokenHasher::new(b"other secret")
+                .secret_cipher()
+                .decrypt(&stored, b"totp:1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_secret_cipher_reads_plaintext() {
+        let cipher = TokenHasher::new(b"secret").secret_cipher();
+        assert!(!SecretCipher::is_encrypted("JBSWY3DPEHPK3PXP"));
+        assert_eq!(
+            cipher.decrypt("JBSWY3DPEHPK3PXP", b"totp:1").as_deref(),
+            Some("JBSWY3DPEHPK3PXP")
+        );
+    }
+
+    #[test]
+    fn test_hash_depends_on_secret() {
+        assert_ne!(
+            TokenHasher::new(b"secret").hash("token"),
+            TokenHasher::new(b"other secret").hash("token")
+        );
+    }
+}
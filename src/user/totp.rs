@@ -0,0 +1,113 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const ISSUER: &str = "Rust Vue Exercise";
+const STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// 160 bit secret as recommended by RFC 4226, base32 encoded for authenticator apps.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+fn build_totp(secret: &str, account_name: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP,
+        secret,
+        Some(ISSUER.to_string()),
+        account_name.replace(':', ""),
+    )
+    .ok()
+}
+
+pub fn otpauth_uri(secret: &str, account_name: &str) -> Option<String> {
+    Some(build_totp(secret, account_name)?.get_url())
+}
+
+/// Returns the time step of a matching code, one step of clock drift is accepted either way.
+/// The caller has to reject steps that were already used.
+pub fn verify_code(secret: &str, code: &str, now: u64) -> Option<i64> {
+    let code = normalize_code(code);
+    if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let totp = build_totp(secret, "")?;
+    [now.checked_sub(STEP), Some(now), now.checked_add(STEP)]
+        .into_iter()
+        .flatten()
+        .find(|time| totp.check(&code, *time))
+        .map(|time| (time / STEP) as i64)
+}
+
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// One-time codes for when the authenticator is lost, shown once and stored hashed.
+pub fn generate_recovery_codes() -> Box<[String]> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RFC 6238 test secret "12345678901234567890".
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_verify_code() {
+        assert_eq!(verify_code(RFC_SECRET, "287082", 59), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "287 082", 59), Some(1));
+    }
+
+    #[test]
+    fn test_verify_code_clock_drift() {
+        assert_eq!(verify_code(RFC_SECRET, "287082", 89), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 150), None);
+    }
+
+    #[test]
+    fn test_verify_code_error_malformed() {
+        assert_eq!(verify_code(RFC_SECRET, "28708", 59), None);
+        assert_eq!(verify_code(RFC_SECRET, "abcdef", 59), None);
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let uri = otpauth_uri(RFC_SECRET, "default").unwrap();
+        assert!(uri.starts_with("otpauth://totp/Rust%20Vue%20Exercise:default?"));
+        assert!(uri.contains(&format!("secret={}", RFC_SECRET)));
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert!(otpauth_uri(&secret, "default").is_some());
+    }
+
+    #[test]
+    fn test_generate_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), 10);
+        assert_eq!(codes[0].len(), 11);
+        assert_ne!(codes[0], codes[1]);
+    }
+}
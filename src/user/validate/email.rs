@@ -0,0 +1,190 @@
+use crate::validation::{
+    OptionValidateErrorItemTrait, StrValidationExtension, ValidateErrorItem, ValidateErrorItemTrait,
+};
+use error_stack::Report;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Email is invalid")]
+pub struct EmailError(ValidateErrorItem);
+
+impl ValidateErrorItemTrait for EmailError {
+    fn get_validate_error_item(&self) -> Option<ValidateErrorItem> {
+        Some(self.0.clone())
+    }
+}
+
+/// Only the shape is checked here, the verification mail proves the address works.
+fn is_email_shape(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !local.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && !label.starts_with('-') && !label.ends_with('-'))
+        && domain.contains('.')
+}
+
+#[derive(Default)]
+pub struct Email(String);
+
+impl Email {
+    /// Surrounding whitespace is dropped, the address is otherwise kept as typed.
+    pub fn parse(email: String, field_name: Option<String>) -> Result<Self, Report<EmailError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("email".to_string());
+        let field_name_no_underscore = field_name.replace("_", " ");
+        let email = email.trim().to_string();
+        let email_validator = email.as_string_validator();
+
+        let mut check_shape = true;
+        email_validator.is_empty().then(|| {
+            message.push(format!("{} cannot be empty", &field_name_no_underscore));
+            check_shape = false;
+        });
+        check_shape.then(|| {
+            (email.len() > 254).then(|| {
+                message.push(format!(
+                    "{} must be at most 254 characters",
+                    &field_name_no_underscore
+                ));
+            });
+            (!is_email_shape(&email)).then(|| {
+                message.push(format!(
+                    "{} must be an address like name@example.com",
+                    &field_name_no_underscore
+                ));
+            });
+        });
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(EmailError)?;
+        Ok(Self(email))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+pub trait IsEmailTaken {
+    fn is_email_taken(&self, email: &str) -> impl Future<Output = bool>;
+}
+
+trait Sealed {}
+
+#[allow(private_bounds)]
+pub trait EmailCheckResult: Sealed {
+    fn check_email_result<T: IsEmailTaken>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> impl Future<Output = Self>;
+}
+
+impl Sealed for Result<Email, Report<EmailError>> {}
+
+impl EmailCheckResult for Result<Email, Report<EmailError>> {
+    async fn check_email_result<T: IsEmailTaken>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> Self {
+        match self {
+            Ok(v) => {
+                let mut message: Vec<String> = vec![];
+                let field_name = field_name.unwrap_or("email".to_string());
+                let field_name_no_underscore = field_name.replace("_", " ");
+
+                service.is_email_taken(v.as_str()).await.then(|| {
+                    message.push(format!("{} is already in use", &field_name_no_underscore));
+                });
+
+                ValidateErrorItem::from_vec(field_name, message).then_err_report(EmailError)?;
+
+                Ok(v)
+            }
+            Err(_) => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_parse() {
+        let email = Email::parse(" jane.doe@example.com ".to_string(), None);
+        assert_eq!(email.unwrap().as_str(), "jane.doe@example.com");
+    }
+
+    #[test]
+    fn test_email_parse_error_empty_string() {
+        assert!(Email::parse("".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_email_parse_error_shape() {
+        for email in [
+            "jane.doe",
+            "@example.com",
+            "jane@",
+            "jane@localhost",
+            "jane@@example.com",
+            "jane doe@example.com",
+            "jane@example..com",
+            "jane@-example.com",
+        ] {
+            assert!(Email::parse(email.to_string(), None).is_err(), "{}", email);
+        }
+    }
+
+    #[test]
+    fn test_email_parse_error_too_long() {
+        let email = format!("{}@example.com", "a".repeat(250));
+        assert!(Email::parse(email, None).is_err());
+    }
+
+    struct FakeEmailCheckService(String);
+
+    impl IsEmailTaken for FakeEmailCheckService {
+        async fn is_email_taken(&self, email: &str) -> bool {
+            email.eq_ignore_ascii_case(self.0.as_str())
+        }
+    }
+
+    #[tokio::test]
+    async fn email_is_taken() {
+        let email_result: Result<Email, Report<EmailError>> =
+            Ok(Email("Taken@example.com".to_string()));
+
+        assert!(
+            email_result
+                .check_email_result(
+                    &FakeEmailCheckService("taken@example.com".to_string()),
+                    None
+                )
+                .await
+                .is_err()
+        )
+    }
+
+    #[tokio::test]
+    async fn email_is_not_taken() {
+        let email_result: Result<Email, Report<EmailError>> =
+            Ok(Email("free@example.com".to_string()));
+
+        assert!(
+            email_result
+                .check_email_result(
+                    &FakeEmailCheckService("taken@example.com".to_string()),
+                    None
+                )
+                .await
+                .is_ok()
+        )
+    }
+}
@@ -0,0 +1,144 @@
+use crate::validation::{
+    OptionValidateErrorItemTrait, StrValidationExtension, ValidateErrorItem, ValidateErrorItemTrait,
+};
+use error_stack::Report;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Invite code is invalid")]
+pub struct InviteCodeError(ValidateErrorItem);
+
+impl ValidateErrorItemTrait for InviteCodeError {
+    fn get_validate_error_item(&self) -> Option<ValidateErrorItem> {
+        Some(self.0.clone())
+    }
+}
+
+#[derive(Default)]
+pub struct InviteCode(String);
+
+impl InviteCode {
+    pub fn parse(
+        invite_code: String,
+        field_name: Option<String>,
+    ) -> Result<Self, Report<InviteCodeError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("invite_code".to_string());
+        let field_name_no_underscore = field_name.replace("_", " ");
+        let invite_code = invite_code.trim().to_string();
+
+        invite_code.as_string_validator().is_empty().then(|| {
+            message.push(format!("{} cannot be empty", &field_name_no_underscore));
+        });
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(InviteCodeError)?;
+        Ok(Self(invite_code))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+pub trait IsInviteValid {
+    /// Only asked for an invite code when registration is invite-only.
+    fn is_invite_required(&self) -> bool;
+
+    fn is_invite_valid(&self, invite_code: &str) -> impl Future<Output = bool>;
+}
+
+trait Sealed {}
+
+#[allow(private_bounds)]
+pub trait InviteCodeCheckResult: Sealed {
+    fn check_invite_code_result<T: IsInviteValid>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> impl Future<Output = Self>;
+}
+
+impl Sealed for Result<InviteCode, Report<InviteCodeError>> {}
+
+impl InviteCodeCheckResult for Result<InviteCode, Report<InviteCodeError>> {
+    async fn check_invite_code_result<T: IsInviteValid>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> Self {
+        match self {
+            Ok(v) => {
+                let mut message: Vec<String> = vec![];
+                let field_name = field_name.unwrap_or("invite_code".to_string());
+                let field_name_no_underscore = field_name.replace("_", " ");
+
+                (!service.is_invite_valid(v.as_str()).await).then(|| {
+                    message.push(format!(
+                        "{} is unknown, expired or used up",
+                        &field_name_no_underscore
+                    ));
+                });
+
+                ValidateErrorItem::from_vec(field_name, message)
+                    .then_err_report(InviteCodeError)?;
+
+                Ok(v)
+            }
+            Err(_) => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_code_parse() {
+        let invite_code = InviteCode::parse(" abc123 ".to_string(), None);
+        assert_eq!(invite_code.unwrap().as_str(), "abc123");
+    }
+
+    #[test]
+    fn test_invite_code_parse_error_empty_string() {
+        assert!(InviteCode::parse(" ".to_string(), None).is_err());
+    }
+
+    struct FakeInviteCheckService(String);
+
+    impl IsInviteValid for FakeInviteCheckService {
+        fn is_invite_required(&self) -> bool {
+            true
+        }
+
+        async fn is_invite_valid(&self, invite_code: &str) -> bool {
+            invite_code == self.0.as_str()
+        }
+    }
+
+    #[tokio::test]
+    async fn invite_code_is_valid() {
+        let invite_code_result: Result<InviteCode, Report<InviteCodeError>> =
+            Ok(InviteCode("valid".to_string()));
+
+        assert!(
+            invite_code_result
+                .check_invite_code_result(&FakeInviteCheckService("valid".to_string()), None)
+                .await
+                .is_ok()
+        )
+    }
+
+    #[tokio::test]
+    async fn invite_code_is_invalid() {
+        let invite_code_result: Result<InviteCode, Report<InviteCodeError>> =
+            Ok(InviteCode("other".to_string()));
+
+        assert!(
+            invite_code_result
+                .check_invite_code_result(&FakeInviteCheckService("valid".to_string()), None)
+                .await
+                .is_err()
+        )
+    }
+}
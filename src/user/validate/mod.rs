@@ -1,2 +1,5 @@
+pub mod email;
+pub mod invite;
 pub mod password;
+pub mod profile;
 pub mod username;
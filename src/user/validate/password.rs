@@ -65,7 +65,7 @@ impl Password {
             })
         });
 
-        ValidateErrorItem::from_vec(field_name, message).then_err_report(|s| PasswordError(s))?;
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(PasswordError)?;
         Ok(Self(password))
     }
 
@@ -81,15 +81,83 @@ impl Password {
         (password_confirm != self.as_str())
             .then(|| message.push(format!("{} does not match", &field_name_no_underscore)));
 
-        ValidateErrorItem::from_vec(field_name, message).then_err_report(|s| PasswordError(s))?;
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(PasswordError)?;
         Ok(Self(password_confirm))
     }
 
+    pub async fn parse_current<T: IsCurrentPassword>(
+        password: String,
+        service: &T,
+        field_name: Option<String>,
+    ) -> Result<Self, Report<PasswordError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("current_password".to_string());
+        let field_name_no_underscore = field_name.replace("_", " ");
+
+        if password.is_empty() {
+            message.push(format!("{} cannot be empty", &field_name_no_underscore));
+        } else if !service.is_current_password(&password).await {
+            message.push(format!("{} is incorrect", &field_name_no_underscore));
+        }
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(PasswordError)?;
+        Ok(Self(password))
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
 }
 
+pub trait IsCurrentPassword {
+    fn is_current_password(&self, password: &str) -> impl Future<Output = bool>;
+}
+
+pub trait IsPasswordBreached {
+    fn is_password_breached(&self, password: &str) -> impl Future<Output = bool>;
+}
+
+trait Sealed {}
+
+#[allow(private_bounds)]
+pub trait PasswordCheckResult: Sealed {
+    fn check_breached_result<T: IsPasswordBreached>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> impl Future<Output = Self>;
+}
+
+impl Sealed for Result<Password, Report<PasswordError>> {}
+
+impl PasswordCheckResult for Result<Password, Report<PasswordError>> {
+    async fn check_breached_result<T: IsPasswordBreached>(
+        self,
+        service: &T,
+        field_name: Option<String>,
+    ) -> Self {
+        match self {
+            Ok(v) => {
+                let mut message: Vec<String> = vec![];
+                let field_name = field_name.unwrap_or("password".to_string());
+                let field_name_no_underscore = field_name.replace("_", " ");
+
+                service.is_password_breached(v.as_str()).await.then(|| {
+                    message.push(format!(
+                        "{} appears in a known data breach, choose another one",
+                        &field_name_no_underscore
+                    ));
+                });
+
+                ValidateErrorItem::from_vec(field_name, message).then_err_report(PasswordError)?;
+
+                Ok(v)
+            }
+            Err(_) => self,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,4 +224,67 @@ mod tests {
         let password = password.parse_confirm("match".to_string(), None);
         assert!(password.is_ok());
     }
+
+    struct FakeCurrentPasswordService(String);
+
+    impl IsCurrentPassword for FakeCurrentPasswordService {
+        async fn is_current_password(&self, password: &str) -> bool {
+            password == self.0.as_str()
+        }
+    }
+
+    #[tokio::test]
+    async fn current_password_is_correct() {
+        let service = FakeCurrentPasswordService("current".to_string());
+        let password = Password::parse_current("current".to_string(), &service, None).await;
+        assert!(password.is_ok());
+    }
+
+    #[tokio::test]
+    async fn current_password_is_incorrect() {
+        let service = FakeCurrentPasswordService("current".to_string());
+        let password = Password::parse_current("wrong".to_string(), &service, None).await;
+        assert!(password.is_err());
+    }
+
+    struct FakeBreachedPasswordService(String);
+
+    impl IsPasswordBreached for FakeBreachedPasswordService {
+        async fn is_password_breached(&self, password: &str) -> bool {
+            password == self.0.as_str()
+        }
+    }
+
+    #[tokio::test]
+    async fn password_is_breached() {
+        let password_result: Result<Password, Report<PasswordError>> =
+            Ok(Password("Password1!".to_string()));
+
+        assert!(
+            password_result
+                .check_breached_result(&FakeBreachedPasswordService("Password1!".to_string()), None)
+                .await
+                .is_err()
+        )
+    }
+
+    #[tokio::test]
+    async fn password_is_not_breached() {
+        let password_result: Result<Password, Report<PasswordError>> =
+            Ok(Password("Hello@Wor1d".to_string()));
+
+        assert!(
+            password_result
+                .check_breached_result(&FakeBreachedPasswordService("Password1!".to_string()), None)
+                .await
+                .is_ok()
+        )
+    }
+
+    #[tokio::test]
+    async fn current_password_is_empty() {
+        let service = FakeCurrentPasswordService("".to_string());
+        let password = Password::parse_current("".to_string(), &service, None).await;
+        assert!(password.is_err());
+    }
 }
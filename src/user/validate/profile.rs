@@ -0,0 +1,167 @@
+use crate::user::profile::{Locale, Theme};
+use crate::validation::{
+    OptionValidateErrorItemTrait, StrValidationExtension, ValidateErrorItem, ValidateErrorItemTrait,
+};
+use chrono_tz::Tz;
+use error_stack::Report;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Profile is invalid")]
+pub struct ProfileError(ValidateErrorItem);
+
+impl ValidateErrorItemTrait for ProfileError {
+    fn get_validate_error_item(&self) -> Option<ValidateErrorItem> {
+        Some(self.0.clone())
+    }
+}
+
+/// Optional, an empty display name falls back to the username.
+#[derive(Default)]
+pub struct DisplayName(Option<String>);
+
+impl DisplayName {
+    pub fn parse(
+        display_name: String,
+        field_name: Option<String>,
+    ) -> Result<Self, Report<ProfileError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("display_name".to_string());
+        let field_name_no_underscore = field_name.replace("_", " ");
+        let display_name = display_name.trim().to_string();
+        let display_name_validator = display_name.as_string_validator();
+
+        (display_name_validator.count_graphemes() > 50).then(|| {
+            message.push(format!(
+                "{} must be at most 50 characters",
+                &field_name_no_underscore
+            ));
+        });
+        display_name.chars().any(char::is_control).then(|| {
+            message.push(format!(
+                "{} cannot contain control characters",
+                &field_name_no_underscore
+            ));
+        });
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(ProfileError)?;
+        Ok(Self((!display_name.is_empty()).then_some(display_name)))
+    }
+
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+}
+
+/// IANA time zone name, e.g. `Europe/Berlin`.
+pub struct TimeZone(Tz);
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self(Tz::UTC)
+    }
+}
+
+impl TimeZone {
+    pub fn parse(
+        time_zone: String,
+        field_name: Option<String>,
+    ) -> Result<Self, Report<ProfileError>> {
+        let mut message: Vec<String> = vec![];
+        let field_name = field_name.unwrap_or("time_zone".to_string());
+        let field_name_no_underscore = field_name.replace("_", " ");
+        let tz = time_zone.trim().parse::<Tz>().ok();
+
+        tz.is_none().then(|| {
+            message.push(format!(
+                "{} must be a time zone like Europe/Berlin",
+                &field_name_no_underscore
+            ));
+        });
+
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(ProfileError)?;
+        Ok(Self(tz.unwrap_or(Tz::UTC)))
+    }
+
+    pub fn as_tz(&self) -> Tz {
+        self.0
+    }
+}
+
+/// For select fields, the value has to be one of the offered options.
+fn parse_choice<T: Default>(
+    choice: Option<T>,
+    field_name: String,
+) -> Result<T, Report<ProfileError>> {
+    let mut message: Vec<String> = vec![];
+    let field_name_no_underscore = field_name.replace("_", " ");
+
+    choice.is_none().then(|| {
+        message.push(format!(
+            "{} is not one of the choices",
+            &field_name_no_underscore
+        ));
+    });
+
+    ValidateErrorItem::from_vec(field_name, message).then_err_report(ProfileError)?;
+    Ok(choice.unwrap_or_default())
+}
+
+pub fn parse_locale(
+    locale: &str,
+    field_name: Option<String>,
+) -> Result<Locale, Report<ProfileError>> {
+    parse_choice(
+        Locale::parse(locale),
+        field_name.unwrap_or("locale".to_string()),
+    )
+}
+
+pub fn parse_theme(theme: &str, field_name: Option<String>) -> Result<Theme, Report<ProfileError>> {
+    parse_choice(
+        Theme::parse(theme),
+        field_name.unwrap_or("theme".to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_name_parse() {
+        let display_name = DisplayName::parse("  Jane Doe ".to_string(), None).unwrap();
+        assert_eq!(display_name.into_inner().as_deref(), Some("Jane Doe"));
+    }
+
+    #[test]
+    fn test_display_name_parse_empty() {
+        let display_name = DisplayName::parse(" ".to_string(), None).unwrap();
+        assert_eq!(display_name.into_inner(), None);
+    }
+
+    #[test]
+    fn test_display_name_parse_error() {
+        assert!(DisplayName::parse("a".repeat(51), None).is_err());
+        assert!(DisplayName::parse("Jane\u{0}Doe".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_time_zone_parse() {
+        assert_eq!(
+            TimeZone::parse("Europe/Berlin".to_string(), None)
+                .unwrap()
+                .as_tz(),
+            Tz::Europe__Berlin
+        );
+        assert!(TimeZone::parse("Mars/Olympus".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_choices() {
+        assert_eq!(parse_locale("fr-FR", None).unwrap(), Locale::FrFr);
+        assert!(parse_locale("fr", None).is_err());
+        assert_eq!(parse_theme("light", None).unwrap(), Theme::Light);
+        assert!(parse_theme("", None).is_err());
+    }
+}
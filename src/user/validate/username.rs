@@ -1,8 +1,10 @@
+use crate::config::{UsernameCharset, UsernameConfig};
 use crate::validation::{
     OptionValidateErrorItemTrait, StrValidationExtension, ValidateErrorItem, ValidateErrorItemTrait,
 };
 use error_stack::Report;
 use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, Error)]
 #[error("Username is invalid")]
@@ -14,17 +16,34 @@ impl ValidateErrorItemTrait for UsernameError {
     }
 }
 
+/// Key for uniqueness and lookups: NFKC folds compatibility forms like fullwidth letters,
+/// lowercasing makes `Alice` and `alice` the same name.
+pub fn username_key(username: &str) -> String {
+    username.nfkc().collect::<String>().to_lowercase()
+}
+
+fn is_allowed_char(c: char, charset: UsernameCharset) -> bool {
+    let is_letter_or_digit = match charset {
+        UsernameCharset::Ascii => c.is_ascii_alphanumeric(),
+        UsernameCharset::Unicode => c.is_alphanumeric(),
+    };
+    is_letter_or_digit || matches!(c, '.' | '_' | '-')
+}
+
 #[derive(Default)]
 pub struct Username(String);
 
 impl Username {
+    /// The username is kept in NFKC form, its case as typed.
     pub fn parse(
         username: String,
+        username_config: &UsernameConfig,
         field_name: Option<String>,
     ) -> Result<Self, Report<UsernameError>> {
         let mut message: Vec<String> = vec![];
         let field_name = field_name.unwrap_or("username".to_string());
         let field_name_no_underscore = field_name.replace("_", " ");
+        let username: String = username.nfkc().collect();
         let username_validator = username.as_string_validator();
 
         let mut check_count = true;
@@ -45,9 +64,30 @@ impl Username {
                     &field_name_no_underscore
                 ))
             });
+            (!username
+                .chars()
+                .all(|c| is_allowed_char(c, username_config.charset)))
+            .then(|| {
+                message.push(match username_config.charset {
+                    UsernameCharset::Ascii => format!(
+                        "{} can only contain letters a-z, digits, '.', '_' and '-'",
+                        &field_name_no_underscore
+                    ),
+                    UsernameCharset::Unicode => format!(
+                        "{} can only contain letters, digits, '.', '_' and '-'",
+                        &field_name_no_underscore
+                    ),
+                })
+            });
+            let key = username_key(&username);
+            username_config
+                .reserved
+                .iter()
+                .any(|reserved| username_key(reserved) == key)
+                .then(|| message.push(format!("{} is reserved", &field_name_no_underscore)));
         });
 
-        ValidateErrorItem::from_vec(field_name, message).then_err_report(|s| UsernameError(s))?;
+        ValidateErrorItem::from_vec(field_name, message).then_err_report(UsernameError)?;
         Ok(Self(username))
     }
 
@@ -89,8 +129,7 @@ impl UsernameCheckResult for Result<Username, Report<UsernameError>> {
                     message.push(format!("{} is already taken", &field_name_no_underscore));
                 });
 
-                ValidateErrorItem::from_vec(field_name, message)
-                    .then_err_report(|s| UsernameError(s))?;
+                ValidateErrorItem::from_vec(field_name, message).then_err_report(UsernameError)?;
 
                 Ok(v)
             }
@@ -102,29 +141,84 @@ impl UsernameCheckResult for Result<Username, Report<UsernameError>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rocket::serde::json::serde_json::{from_value, to_value};
+
+    fn config() -> UsernameConfig {
+        UsernameConfig::default()
+    }
+
+    fn messages(username: &str, username_config: &UsernameConfig) -> Vec<String> {
+        Username::parse(username.to_string(), username_config, None)
+            .get_validate_error_item()
+            .and_then(|item| from_value(to_value(item).unwrap()["messages"].take()).ok())
+            .unwrap_or_default()
+    }
 
     #[test]
     fn test_username_parse() {
-        let username = Username::parse("Hello".to_string(), None);
+        let username = Username::parse("Hello".to_string(), &config(), None);
         assert!(username.is_ok());
     }
 
+    #[test]
+    fn test_username_parse_normalizes() {
+        let username = Username::parse("\u{FF28}ello_World".to_string(), &config(), None);
+        assert_eq!(username.unwrap().as_str(), "Hello_World");
+    }
+
+    #[test]
+    fn test_username_key() {
+        assert_eq!(username_key("Alice"), username_key("alice"));
+        assert_eq!(username_key("\u{FF21}lice"), "alice");
+        assert_ne!(username_key("alice"), username_key("alice2"));
+    }
+
+    #[test]
+    fn test_username_parse_error_charset() {
+        assert_eq!(
+            messages("hello world", &config()),
+            ["username can only contain letters a-z, digits, '.', '_' and '-'"]
+        );
+        assert_eq!(messages("jürgen", &config()).len(), 1);
+
+        let unicode = UsernameConfig {
+            charset: UsernameCharset::Unicode,
+            ..config()
+        };
+        assert!(messages("jürgen", &unicode).is_empty());
+        assert_eq!(messages("hello world", &unicode).len(), 1);
+    }
+
+    #[test]
+    fn test_username_parse_error_reserved() {
+        assert_eq!(messages("Admin", &config()), ["username is reserved"]);
+        assert_eq!(
+            messages("\u{FF21}DMIN", &config()),
+            ["username is reserved"]
+        );
+    }
+
+    #[test]
+    fn test_username_parse_error_each_rule() {
+        assert_eq!(messages("a b", &config()).len(), 2);
+    }
+
     #[test]
     fn test_username_parse_error_empty_string() {
-        let username = Username::parse("".to_string(), None);
+        let username = Username::parse("".to_string(), &config(), None);
         assert!(username.is_err());
     }
 
     #[test]
     fn test_username_parse_error_too_short() {
-        let username = Username::parse("a".to_string(), None);
+        let username = Username::parse("a".to_string(), &config(), None);
         assert!(username.is_err());
     }
 
     #[test]
     fn test_username_parse_error_too_long() {
         let username_str = "a".repeat(31);
-        let username = Username::parse(username_str, None);
+        let username = Username::parse(username_str, &config(), None);
         assert!(username.is_err());
     }
 
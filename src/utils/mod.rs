@@ -6,8 +6,8 @@ use rocket::request::{FromRequest, Outcome};
 
 struct EtagStamp;
 
-impl<'o> Into<Header<'o>> for EtagStamp {
-    fn into(self) -> Header<'o> {
+impl<'o> From<EtagStamp> for Header<'o> {
+    fn from(_: EtagStamp) -> Self {
         match option_env!("ETAG") {
             Some(stamp) => Header::new("ETag", stamp),
             None => Header::new("X-Etag", "not-set"),
@@ -51,3 +51,37 @@ impl<'r> FromRequest<'r> for EtagCheck {
         }
     }
 }
+
+#[derive(Responder)]
+pub struct WithEtag<T> {
+    inner: T,
+    etag: Header<'static>,
+}
+
+impl<T> WithEtag<T> {
+    pub fn new(inner: T, etag: String) -> Self {
+        WithEtag {
+            inner,
+            etag: Header::new("ETag", etag),
+        }
+    }
+}
+
+pub struct IfMatch(Option<String>);
+
+impl IfMatch {
+    pub fn etag(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfMatch(
+            req.headers().get_one("If-Match").map(|s| s.to_string()),
+        ))
+    }
+}
@@ -125,7 +125,7 @@ impl ValidationErrorResponse {
                     let new_messages = item.messages.clone();
                     let merge_message = current_messages
                         .into_iter()
-                        .chain(new_messages.into_iter())
+                        .chain(new_messages)
                         .collect::<Vec<_>>()
                         .into_boxed_slice();
                     let new_item = ValidateErrorItem {
@@ -142,9 +142,10 @@ impl ValidationErrorResponse {
 
 impl Display for ValidationErrorResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Ok(for item in &self.0.0 {
-            write!(f, "{};\n", item.messages.join(", "))?
-        })
+        for item in &self.0.0 {
+            writeln!(f, "{};", item.messages.join(", "))?
+        }
+        Ok(())
     }
 }
 
@@ -155,7 +156,7 @@ pub struct ValidationErrorMergedResponse(Json<HashMap<String, Box<[ValidateError
 impl ValidationErrorMergedResponse {
     pub fn as_map(&self) -> HashMap<String, ValidateErrorItem> {
         let mut map = HashMap::new();
-        for (_, items) in &self.0.0 {
+        for items in self.0.0.values() {
             for item in items {
                 match map.get(&item.field_name) {
                     None => {
@@ -166,7 +167,7 @@ impl ValidationErrorMergedResponse {
                         let new_messages = item.messages.clone();
                         let merge_message = current_messages
                             .into_iter()
-                            .chain(new_messages.into_iter())
+                            .chain(new_messages)
                             .collect::<Vec<_>>()
                             .into_boxed_slice();
                         let new_item = ValidateErrorItem {
@@ -184,16 +185,23 @@ impl ValidationErrorMergedResponse {
 
 impl Display for ValidationErrorMergedResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Ok(for (_, items) in &self.0.0 {
+        for items in self.0.0.values() {
             for item in items {
-                write!(f, "{};\n", item.messages.join(", "))?
+                writeln!(f, "{};", item.messages.join(", "))?
             }
-        })
+        }
+        Ok(())
     }
 }
 
 pub struct ValidationErrorsBuilder(Vec<ValidateErrorItem>);
 
+impl Default for ValidationErrorsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ValidationErrorsBuilder {
     pub fn new() -> Self {
         Self(Vec::new())
@@ -228,6 +236,12 @@ impl ValidationErrorsBuilder {
 #[derive(Clone, Debug)]
 pub struct ValidationErrorsMergeBuilder(HashMap<String, Box<[ValidateErrorItem]>>);
 
+impl Default for ValidationErrorsMergeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ValidationErrorsMergeBuilder {
     pub fn new() -> Self {
         Self(HashMap::new())